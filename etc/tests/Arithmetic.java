@@ -0,0 +1,14 @@
+// Fixture for the interpreter tests in src/code.rs, compiled with javac -d etc/tests
+public class Arithmetic {
+    static int idiv(int a, int b) { return a / b; }
+    static int irem(int a, int b) { return a % b; }
+    static long ldiv(long a, long b) { return a / b; }
+    static long lrem(long a, long b) { return a % b; }
+
+    static int ishl(int value, int distance) { return value << distance; }
+    static int ishr(int value, int distance) { return value >> distance; }
+    static int iushr(int value, int distance) { return value >>> distance; }
+    static long lshl(long value, int distance) { return value << distance; }
+    static long lshr(long value, int distance) { return value >> distance; }
+    static long lushr(long value, int distance) { return value >>> distance; }
+}
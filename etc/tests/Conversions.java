@@ -0,0 +1,11 @@
+// Fixture for the interpreter tests in src/code.rs, compiled with javac -d etc/tests
+public class Conversions {
+    static int f2i(float value) { return (int) value; }
+    static int d2i(double value) { return (int) value; }
+    static long f2l(float value) { return (long) value; }
+    static long d2l(double value) { return (long) value; }
+
+    static int i2b(int value) { return (byte) value; }
+    static int i2c(int value) { return (char) value; }
+    static int i2s(int value) { return (short) value; }
+}
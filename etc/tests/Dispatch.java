@@ -0,0 +1,50 @@
+// Fixture for the method selection tests in src/class.rs, compiled with javac -d etc/tests
+public class Dispatch {
+    interface K {
+        default int k() { return 1; }
+        int abstractK();
+    }
+
+    interface K2 extends K {
+        default int k() { return 2; }
+    }
+
+    static class Base {
+        int m() { return 1; }
+        int n() { return 10; }
+    }
+
+    static class Derived extends Base implements K2 {
+        int m() { return 2; }
+        int extra() { return 3; }
+        public int abstractK() { return 4; }
+    }
+
+    // K comes first, the default method of K2 still wins
+    static abstract class Both implements K, K2 {
+    }
+
+    interface WithPrivate {
+        private int hidden() { return 5; }
+        default int visible() { return hidden(); }
+        static int helper() { return 6; }
+    }
+
+    static class UsesPrivate implements WithPrivate {
+    }
+
+    static int kOfDerived() {
+        K k = new Derived();
+        return k.k();
+    }
+
+    static int mOfDerived() {
+        Base base = new Derived();
+        return base.m() * 100 + base.n();
+    }
+
+    static int visibleOfUsesPrivate() {
+        WithPrivate withPrivate = new UsesPrivate();
+        return withPrivate.visible();
+    }
+}
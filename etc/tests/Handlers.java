@@ -0,0 +1,81 @@
+// Fixture for the interpreter tests in src/code.rs, compiled with javac -d etc/tests
+public class Handlers {
+    static class Failure extends RuntimeException {
+        Failure(String message) { super(message); }
+    }
+
+    static int log;
+
+    static int nested(int a, int b) {
+        try {
+            try {
+                return a / b;
+            } catch (NullPointerException e) {
+                return -1;
+            }
+        } catch (ArithmeticException e) {
+            return -2;
+        }
+    }
+
+    static int bySuperclass(int a, int b) {
+        try {
+            return a / b;
+        } catch (RuntimeException e) {
+            return -3;
+        }
+    }
+
+    static int divideLogged(int a, int b) {
+        try {
+            return a / b;
+        } finally {
+            log = log * 10 + 1;
+        }
+    }
+
+    // The finally block runs before the exception reaches the caller's handler
+    static int finallyThenCatch(int b) {
+        log = 0;
+        try {
+            divideLogged(1, b);
+        } catch (ArithmeticException e) {
+            return log + 100;
+        }
+        return log;
+    }
+
+    // The division comes after the end of the try block
+    static int afterTry(int a, int b) {
+        int result = 0;
+        try {
+            result = a;
+        } catch (ArithmeticException e) {
+            return -1;
+        }
+        return result / b;
+    }
+
+    static int userException(int value) {
+        try {
+            if (value < 0) {
+                throw new Failure("negative");
+            }
+            return value;
+        } catch (Failure e) {
+            return -4;
+        }
+    }
+
+    static void fail(String message) {
+        throw new Failure(message);
+    }
+
+    static int rethrow(int a, int b) {
+        try {
+            return a / b;
+        } catch (ArithmeticException e) {
+            throw e;
+        }
+    }
+}
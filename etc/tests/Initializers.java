@@ -0,0 +1,28 @@
+// Fixture for the interpreter tests in src/code.rs, compiled with javac -d etc/tests
+public class Initializers {
+    static class Failure extends RuntimeException {
+        Failure(String message) { super(message); }
+    }
+
+    static class Broken extends Error {
+    }
+
+    static class ThrowsException {
+        static int value = fail();
+        static int fail() { throw new Failure("from <clinit>"); }
+    }
+
+    static class ThrowsPlatformError {
+        static int value = link();
+        static native int link();
+    }
+
+    static class ThrowsUserError {
+        static int value = fail();
+        static int fail() { throw new Broken(); }
+    }
+
+    static int readException() { return ThrowsException.value; }
+    static int readPlatformError() { return ThrowsPlatformError.value; }
+    static int readUserError() { return ThrowsUserError.value; }
+}
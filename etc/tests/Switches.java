@@ -0,0 +1,23 @@
+// Fixture for the interpreter tests in src/code.rs, compiled with javac -d etc/tests
+public class Switches {
+    // Dense cases, compiled to a tableswitch
+    static int table(int value) {
+        switch (value) {
+            case -1: return 10;
+            case 0: return 20;
+            case 1: return 30;
+            case 2: return 40;
+            default: return -1;
+        }
+    }
+
+    // Sparse cases, compiled to a lookupswitch
+    static int lookup(int value) {
+        switch (value) {
+            case -1000: return 10;
+            case 7: return 20;
+            case 100000: return 30;
+            default: return -1;
+        }
+    }
+}
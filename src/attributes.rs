@@ -0,0 +1,444 @@
+use std::io::Cursor;
+use crate::class_file::{ClassFileFeature, Constant};
+use crate::code::CodeAttribute;
+use crate::error::{JvmError, JvmResult, Location};
+use crate::utils::ReadFromCursor;
+
+pub struct AttributeInfo {
+    pub attribute_name_index: u16,
+    pub info: Vec<u8>
+}
+
+pub enum Attribute {
+    ConstantValue(ConstantValueAttribute),
+    Code(CodeAttribute),
+    Exceptions(ExceptionsAttribute),
+    InnerClasses(InnerClassesAttribute),
+    EnclosingMethod(EnclosingMethodAttribute),
+    Synthetic,
+    Signature(SignatureAttribute),
+    SourceFile(SourceFileAttribute),
+    LineNumberTable(LineNumberTableAttribute),
+    LocalVariableTable(LocalVariableTableAttribute),
+    LocalVariableTypeTable(LocalVariableTypeTableAttribute),
+    Deprecated,
+    BootstrapMethods(BootstrapMethodsAttribute),
+    MethodParameters(MethodParametersAttribute),
+    NestHost(NestHostAttribute),
+    NestMembers(NestMembersAttribute),
+    PermittedSubclasses(PermittedSubclassesAttribute),
+    Record(RecordAttribute),
+    // Any attribute not decoded above, kept as it was in the file
+    Unknown(AttributeInfo)
+}
+
+pub struct ConstantValueAttribute {
+    pub constant_value_index: u16
+}
+
+pub struct ExceptionsAttribute {
+    pub exception_index_table: Vec<u16>
+}
+
+pub struct InnerClass {
+    pub inner_class_info_index: u16,
+    pub outer_class_info_index: u16,
+    pub inner_name_index: u16,
+    pub inner_class_access_flags: u16
+}
+
+pub struct InnerClassesAttribute {
+    pub classes: Vec<InnerClass>
+}
+
+pub struct EnclosingMethodAttribute {
+    pub class_index: u16,
+    pub method_index: u16
+}
+
+pub struct SignatureAttribute {
+    pub signature_index: u16
+}
+
+pub struct SourceFileAttribute {
+    pub source_file_index: u16
+}
+
+pub struct LineNumber {
+    pub start_pc: u16,
+    pub line_number: u16
+}
+
+pub struct LineNumberTableAttribute {
+    pub line_number_table: Vec<LineNumber>
+}
+
+pub struct LocalVariable {
+    pub start_pc: u16,
+    pub length: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub index: u16
+}
+
+pub struct LocalVariableTableAttribute {
+    pub local_variable_table: Vec<LocalVariable>
+}
+
+pub struct LocalVariableType {
+    pub start_pc: u16,
+    pub length: u16,
+    pub name_index: u16,
+    pub signature_index: u16,
+    pub index: u16
+}
+
+pub struct LocalVariableTypeTableAttribute {
+    pub local_variable_type_table: Vec<LocalVariableType>
+}
+
+pub struct BootstrapMethod {
+    pub bootstrap_method_ref: u16,
+    pub bootstrap_arguments: Vec<u16>
+}
+
+pub struct BootstrapMethodsAttribute {
+    pub bootstrap_methods: Vec<BootstrapMethod>
+}
+
+pub struct MethodParameter {
+    pub name_index: u16,
+    pub access_flags: u16
+}
+
+pub struct MethodParametersAttribute {
+    pub parameters: Vec<MethodParameter>
+}
+
+pub struct NestHostAttribute {
+    pub host_class_index: u16
+}
+
+pub struct NestMembersAttribute {
+    pub classes: Vec<u16>
+}
+
+pub struct PermittedSubclassesAttribute {
+    pub classes: Vec<u16>
+}
+
+pub struct RecordComponent {
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub attributes: Vec<Attribute>
+}
+
+pub struct RecordAttribute {
+    pub components: Vec<RecordComponent>
+}
+
+impl Attribute {
+    pub fn name<'a>(&'a self, constants_pool: &'a [Constant]) -> &'a str {
+        match self {
+            Attribute::ConstantValue(_) => "ConstantValue",
+            Attribute::Code(_) => "Code",
+            Attribute::Exceptions(_) => "Exceptions",
+            Attribute::InnerClasses(_) => "InnerClasses",
+            Attribute::EnclosingMethod(_) => "EnclosingMethod",
+            Attribute::Synthetic => "Synthetic",
+            Attribute::Signature(_) => "Signature",
+            Attribute::SourceFile(_) => "SourceFile",
+            Attribute::LineNumberTable(_) => "LineNumberTable",
+            Attribute::LocalVariableTable(_) => "LocalVariableTable",
+            Attribute::LocalVariableTypeTable(_) => "LocalVariableTypeTable",
+            Attribute::Deprecated => "Deprecated",
+            Attribute::BootstrapMethods(_) => "BootstrapMethods",
+            Attribute::MethodParameters(_) => "MethodParameters",
+            Attribute::NestHost(_) => "NestHost",
+            Attribute::NestMembers(_) => "NestMembers",
+            Attribute::PermittedSubclasses(_) => "PermittedSubclasses",
+            Attribute::Record(_) => "Record",
+            Attribute::Unknown(info) => get_utf8(constants_pool, info.attribute_name_index).unwrap_or("")
+        }
+    }
+}
+
+fn get_utf8(constants_pool: &[Constant], index: u16) -> Option<&str> {
+    match constants_pool.get((index as usize).wrapping_sub(1)) {
+        Some(Constant::Utf8(utf8)) => Some(utf8.data.as_str()),
+        _ => None
+    }
+}
+
+fn read_u16_table(cursor: &mut Cursor<Vec<u8>>) -> JvmResult<Vec<u16>> {
+    let count = cursor.read_u16()?;
+    let mut table: Vec<u16> = Vec::with_capacity(count as usize);
+
+    for _ in 0..count {
+        table.push(cursor.read_u16()?);
+    }
+
+    Ok(table)
+}
+
+/// Attributes introduced after the version of the class file are not recognized, they are
+/// kept as unknown attributes (JVMS 4.7)
+fn is_recognized(name: &str, major: u16) -> bool {
+    match name {
+        "NestHost" | "NestMembers" => ClassFileFeature::NestMates.is_legal_in(major),
+        "Record" => ClassFileFeature::Records.is_legal_in(major),
+        "PermittedSubclasses" => ClassFileFeature::SealedClasses.is_legal_in(major),
+        _ => true
+    }
+}
+
+fn read_attribute(cursor: &mut Cursor<Vec<u8>>, constants_pool: &[Constant], major: u16) -> JvmResult<Attribute> {
+    let offset = cursor.position();
+    let attribute_name_index: u16 = cursor.read_u16()?;
+    let length: u32 = cursor.read_u32()?;
+
+    let name = get_utf8(constants_pool, attribute_name_index).ok_or(JvmError::UnexpectedConstant {
+        index: attribute_name_index,
+        expected: "Utf8",
+        location: Location::Offset(offset)
+    })?;
+
+    if !is_recognized(name, major) {
+        return Ok(Attribute::Unknown(AttributeInfo { attribute_name_index, info: cursor.read_bytes(length as usize)? }));
+    }
+
+    let start = cursor.position();
+    let attribute = match name {
+        "ConstantValue" => Attribute::ConstantValue(ConstantValueAttribute {
+            constant_value_index: cursor.read_u16()?
+        }),
+        "Code" => Attribute::Code(CodeAttribute::new(cursor, constants_pool, major)?),
+        "Exceptions" => Attribute::Exceptions(ExceptionsAttribute {
+            exception_index_table: read_u16_table(cursor)?
+        }),
+        "InnerClasses" => {
+            let count = cursor.read_u16()?;
+            let mut classes: Vec<InnerClass> = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                classes.push(InnerClass {
+                    inner_class_info_index: cursor.read_u16()?,
+                    outer_class_info_index: cursor.read_u16()?,
+                    inner_name_index: cursor.read_u16()?,
+                    inner_class_access_flags: cursor.read_u16()?
+                });
+            }
+            Attribute::InnerClasses(InnerClassesAttribute { classes })
+        },
+        "EnclosingMethod" => Attribute::EnclosingMethod(EnclosingMethodAttribute {
+            class_index: cursor.read_u16()?,
+            method_index: cursor.read_u16()?
+        }),
+        "Synthetic" => Attribute::Synthetic,
+        "Signature" => {
+            let signature_index: u16 = cursor.read_u16()?;
+            if get_utf8(constants_pool, signature_index).is_none() {
+                return Err(JvmError::UnexpectedConstant {
+                    index: signature_index,
+                    expected: "Utf8",
+                    location: Location::Offset(start)
+                });
+            }
+            Attribute::Signature(SignatureAttribute { signature_index })
+        },
+        "SourceFile" => Attribute::SourceFile(SourceFileAttribute {
+            source_file_index: cursor.read_u16()?
+        }),
+        "LineNumberTable" => {
+            let count = cursor.read_u16()?;
+            let mut line_number_table: Vec<LineNumber> = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                line_number_table.push(LineNumber {
+                    start_pc: cursor.read_u16()?,
+                    line_number: cursor.read_u16()?
+                });
+            }
+            Attribute::LineNumberTable(LineNumberTableAttribute { line_number_table })
+        },
+        "LocalVariableTable" => {
+            let count = cursor.read_u16()?;
+            let mut local_variable_table: Vec<LocalVariable> = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                local_variable_table.push(LocalVariable {
+                    start_pc: cursor.read_u16()?,
+                    length: cursor.read_u16()?,
+                    name_index: cursor.read_u16()?,
+                    descriptor_index: cursor.read_u16()?,
+                    index: cursor.read_u16()?
+                });
+            }
+            Attribute::LocalVariableTable(LocalVariableTableAttribute { local_variable_table })
+        },
+        "LocalVariableTypeTable" => {
+            let count = cursor.read_u16()?;
+            let mut local_variable_type_table: Vec<LocalVariableType> = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                local_variable_type_table.push(LocalVariableType {
+                    start_pc: cursor.read_u16()?,
+                    length: cursor.read_u16()?,
+                    name_index: cursor.read_u16()?,
+                    signature_index: cursor.read_u16()?,
+                    index: cursor.read_u16()?
+                });
+            }
+            Attribute::LocalVariableTypeTable(LocalVariableTypeTableAttribute { local_variable_type_table })
+        },
+        "Deprecated" => Attribute::Deprecated,
+        "BootstrapMethods" => {
+            let count = cursor.read_u16()?;
+            let mut bootstrap_methods: Vec<BootstrapMethod> = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                bootstrap_methods.push(BootstrapMethod {
+                    bootstrap_method_ref: cursor.read_u16()?,
+                    bootstrap_arguments: read_u16_table(cursor)?
+                });
+            }
+            Attribute::BootstrapMethods(BootstrapMethodsAttribute { bootstrap_methods })
+        },
+        "MethodParameters" => {
+            let count = cursor.read_u8()?;
+            let mut parameters: Vec<MethodParameter> = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                parameters.push(MethodParameter {
+                    name_index: cursor.read_u16()?,
+                    access_flags: cursor.read_u16()?
+                });
+            }
+            Attribute::MethodParameters(MethodParametersAttribute { parameters })
+        },
+        "NestHost" => Attribute::NestHost(NestHostAttribute {
+            host_class_index: cursor.read_u16()?
+        }),
+        "NestMembers" => Attribute::NestMembers(NestMembersAttribute {
+            classes: read_u16_table(cursor)?
+        }),
+        "PermittedSubclasses" => Attribute::PermittedSubclasses(PermittedSubclassesAttribute {
+            classes: read_u16_table(cursor)?
+        }),
+        "Record" => {
+            let count = cursor.read_u16()?;
+            let mut components: Vec<RecordComponent> = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let name_index: u16 = cursor.read_u16()?;
+                let descriptor_index: u16 = cursor.read_u16()?;
+                let attributes_count: u16 = cursor.read_u16()?;
+                components.push(RecordComponent {
+                    name_index,
+                    descriptor_index,
+                    attributes: read_attributes(cursor, constants_pool, attributes_count as usize, major)?
+                });
+            }
+            Attribute::Record(RecordAttribute { components })
+        },
+        _ => Attribute::Unknown(AttributeInfo {
+            attribute_name_index,
+            info: cursor.read_bytes(length as usize)?
+        })
+    };
+
+    // The decoded attribute has to take up exactly the length declared in its header
+    if cursor.position() != start + length as u64 {
+        return Err(JvmError::MalformedAttribute { name: name.to_string(), offset });
+    }
+
+    Ok(attribute)
+}
+
+pub fn read_attributes(cursor: &mut Cursor<Vec<u8>>, constants_pool: &[Constant], count: usize, major: u16) -> JvmResult<Vec<Attribute>> {
+    let mut attributes: Vec<Attribute> = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        attributes.push(read_attribute(cursor, constants_pool, major)?);
+    }
+
+    Ok(attributes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class_file::{ConstantInteger, ConstantUft8};
+
+    const NAMES: [&str; 5] = ["ConstantValue", "NestHost", "Record", "PermittedSubclasses", "Custom"];
+
+    // The names above at indexes 1 to 5, then an Integer at index 6
+    fn constants_pool() -> Vec<Constant> {
+        let mut pool: Vec<Constant> = NAMES.iter().map(|name| Constant::Utf8(ConstantUft8 {
+            tag: String::from("CONSTANT_UTF8"),
+            data: name.to_string(),
+            utf16: name.encode_utf16().collect()
+        })).collect();
+        pool.push(Constant::Integer(ConstantInteger { tag: String::from("CONSTANT_INTEGER"), value: 0 }));
+        pool
+    }
+
+    fn attribute_bytes(name_index: u16, length: u32, info: &[u8]) -> Vec<u8> {
+        let mut bytes = name_index.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&length.to_be_bytes());
+        bytes.extend_from_slice(info);
+        bytes
+    }
+
+    fn read(bytes: Vec<u8>, major: u16) -> JvmResult<Attribute> {
+        let mut cursor = Cursor::new(bytes);
+        let mut attributes = read_attributes(&mut cursor, &constants_pool(), 1, major)?;
+        Ok(attributes.remove(0))
+    }
+
+    #[test]
+    fn attribute_length_has_to_match_the_contents() {
+        let attribute = read(attribute_bytes(1, 2, &[0, 7]), 52).unwrap();
+        assert!(matches!(attribute, Attribute::ConstantValue(ConstantValueAttribute { constant_value_index: 7 })));
+
+        for length in [1, 3] {
+            let result = read(attribute_bytes(1, length, &[0, 7, 0]), 52);
+            assert!(matches!(result, Err(JvmError::MalformedAttribute { ref name, offset: 0 }) if name == "ConstantValue"));
+        }
+        assert!(matches!(read(attribute_bytes(1, 2, &[0]), 52), Err(JvmError::Truncated { .. })));
+    }
+
+    #[test]
+    fn newer_attributes_are_unknown_in_older_class_files() {
+        // NestHost needs Java 11 (55), Record Java 16 (60) and PermittedSubclasses Java 17 (61)
+        for (name_index, major) in [(2, 55), (3, 60), (4, 61)] {
+            let info = [0, 0];
+            let attribute = read(attribute_bytes(name_index, 2, &info), major - 1).unwrap();
+            assert!(matches!(attribute, Attribute::Unknown(AttributeInfo { attribute_name_index, ref info })
+                if attribute_name_index == name_index && info == &[0, 0]));
+
+            let attribute = read(attribute_bytes(name_index, 2, &info), major).unwrap();
+            assert_eq!(attribute.name(&constants_pool()), NAMES[name_index as usize - 1]);
+            assert!(!matches!(attribute, Attribute::Unknown(_)));
+        }
+
+        // Unrecognized attributes are skipped without looking at the contents
+        let attribute = read(attribute_bytes(2, 3, &[0, 0, 0]), 54).unwrap();
+        assert!(matches!(attribute, Attribute::Unknown(_)));
+    }
+
+    #[test]
+    fn unknown_attributes_are_kept_as_bytes() {
+        let attribute = read(attribute_bytes(5, 3, &[1, 2, 3]), 61).unwrap();
+        assert_eq!(attribute.name(&constants_pool()), "Custom");
+        assert!(matches!(attribute, Attribute::Unknown(AttributeInfo { attribute_name_index: 5, ref info }) if info == &[1, 2, 3]));
+
+        // The next attribute starts right after the bytes
+        let mut bytes = attribute_bytes(5, 0, &[]);
+        bytes.extend(attribute_bytes(1, 2, &[0, 9]));
+        let attributes = read_attributes(&mut Cursor::new(bytes), &constants_pool(), 2, 61).unwrap();
+        assert!(matches!(attributes[1], Attribute::ConstantValue(ConstantValueAttribute { constant_value_index: 9 })));
+    }
+
+    #[test]
+    fn attribute_name_has_to_be_utf8() {
+        for name_index in [0, 6, 7] {
+            let result = read(attribute_bytes(name_index, 0, &[]), 61);
+            assert!(matches!(result, Err(JvmError::UnexpectedConstant { index, expected: "Utf8", location: Location::Offset(0) })
+                if index == name_index));
+        }
+    }
+}
@@ -0,0 +1,744 @@
+use std::io::Cursor;
+use crate::error::{JvmError, JvmResult};
+use crate::opcodes::*;
+use crate::utils::ReadFromCursor;
+
+// Decoded form of a method's bytecode, shared by the interpreter and the disassembler.
+// The _n forms keep their own variant so the original opcode can always be recovered, while
+// the wide forms decode to the same variant as the narrow ones with a wider index.
+// Branch offsets are relative to the pc of the instruction, as in the class file.
+
+pub struct TableSwitch {
+    pub default: i32,
+    pub low: i32,
+    pub high: i32,
+    // One offset for each value from low to high
+    pub offsets: Vec<i32>
+}
+
+pub struct LookupSwitch {
+    pub default: i32,
+    // Sorted by match value
+    pub pairs: Vec<(i32, i32)>
+}
+
+pub enum Op {
+    Nop,
+    AconstNull,
+    IconstM1,
+    Iconst0,
+    Iconst1,
+    Iconst2,
+    Iconst3,
+    Iconst4,
+    Iconst5,
+    Lconst0,
+    Lconst1,
+    Fconst0,
+    Fconst1,
+    Fconst2,
+    Dconst0,
+    Dconst1,
+    BiPush(i8),
+    SiPush(i16),
+    Ldc(u16),
+    LdcW(u16),
+    Ldc2W(u16),
+    Iload(u16),
+    Lload(u16),
+    Fload(u16),
+    Dload(u16),
+    Aload(u16),
+    Iload0,
+    Iload1,
+    Iload2,
+    Iload3,
+    Lload0,
+    Lload1,
+    Lload2,
+    Lload3,
+    Fload0,
+    Fload1,
+    Fload2,
+    Fload3,
+    Dload0,
+    Dload1,
+    Dload2,
+    Dload3,
+    Aload0,
+    Aload1,
+    Aload2,
+    Aload3,
+    Iaload,
+    Laload,
+    Faload,
+    Daload,
+    Aaload,
+    Baload,
+    Caload,
+    Saload,
+    Istore(u16),
+    Lstore(u16),
+    Fstore(u16),
+    Dstore(u16),
+    Astore(u16),
+    Istore0,
+    Istore1,
+    Istore2,
+    Istore3,
+    Lstore0,
+    Lstore1,
+    Lstore2,
+    Lstore3,
+    Fstore0,
+    Fstore1,
+    Fstore2,
+    Fstore3,
+    Dstore0,
+    Dstore1,
+    Dstore2,
+    Dstore3,
+    Astore0,
+    Astore1,
+    Astore2,
+    Astore3,
+    Iastore,
+    Lastore,
+    Fastore,
+    Dastore,
+    Aastore,
+    Bastore,
+    Castore,
+    Sastore,
+    Pop,
+    Pop2,
+    Dup,
+    DupX1,
+    DupX2,
+    Dup2,
+    Dup2X1,
+    Dup2X2,
+    Swap,
+    Iadd,
+    Ladd,
+    Fadd,
+    Dadd,
+    Isub,
+    Lsub,
+    Fsub,
+    Dsub,
+    Imul,
+    Lmul,
+    Fmul,
+    Dmul,
+    Idiv,
+    Ldiv,
+    Fdiv,
+    Ddiv,
+    Irem,
+    Lrem,
+    Frem,
+    Drem,
+    Ineg,
+    Lneg,
+    Fneg,
+    Dneg,
+    Ishl,
+    Lshl,
+    Ishr,
+    Lshr,
+    Iushr,
+    Lushr,
+    Iand,
+    Land,
+    Ior,
+    Lor,
+    Ixor,
+    Lxor,
+    Iinc { index: u16, constant: i16 },
+    I2l,
+    I2f,
+    I2d,
+    L2i,
+    L2f,
+    L2d,
+    F2i,
+    F2l,
+    F2d,
+    D2i,
+    D2l,
+    D2f,
+    I2b,
+    I2c,
+    I2s,
+    Lcmp,
+    Fcmpl,
+    Fcmpg,
+    Dcmpl,
+    Dcmpg,
+    IfEq(i32),
+    IfNe(i32),
+    IfLt(i32),
+    IfGe(i32),
+    IfGt(i32),
+    IfLe(i32),
+    IfIcmpEq(i32),
+    IfIcmpNe(i32),
+    IfIcmpLt(i32),
+    IfIcmpGe(i32),
+    IfIcmpGt(i32),
+    IfIcmpLe(i32),
+    IfAcmpEq(i32),
+    IfAcmpNe(i32),
+    Goto(i32),
+    Jsr(i32),
+    Ret(u16),
+    TableSwitch(TableSwitch),
+    LookupSwitch(LookupSwitch),
+    Ireturn,
+    Lreturn,
+    Freturn,
+    Dreturn,
+    Areturn,
+    Return,
+    GetStatic(u16),
+    PutStatic(u16),
+    GetField(u16),
+    PutField(u16),
+    InvokeVirtual(u16),
+    InvokeSpecial(u16),
+    InvokeStatic(u16),
+    InvokeInterface { index: u16, count: u8 },
+    InvokeDynamic(u16),
+    New(u16),
+    NewArray(u8),
+    AnewArray(u16),
+    ArrayLength,
+    Athrow,
+    CheckCast(u16),
+    InstanceOf(u16),
+    MonitorEnter,
+    MonitorExit,
+    MultiAnewArray { index: u16, dimensions: u8 },
+    IfNull(i32),
+    IfNonNull(i32),
+    GotoW(i32),
+    JsrW(i32),
+}
+
+pub struct Instruction {
+    pub pc: u32,
+    // For wide instructions the opcode being widened
+    pub opcode: u8,
+    pub wide: bool,
+    pub op: Op
+}
+
+impl Instruction {
+    pub fn branch_target(&self, offset: i32) -> u32 {
+        (self.pc as i64 + offset as i64) as u32
+    }
+}
+
+impl TableSwitch {
+    pub fn offset_for(&self, value: i32) -> i32 {
+        if value < self.low || value > self.high {
+            return self.default;
+        }
+        self.offsets[(value as i64 - self.low as i64) as usize]
+    }
+}
+
+impl LookupSwitch {
+    pub fn offset_for(&self, value: i32) -> i32 {
+        match self.pairs.binary_search_by_key(&value, |&(key, _)| key) {
+            Ok(index) => self.pairs[index].1,
+            Err(_) => self.default
+        }
+    }
+}
+
+fn skip_padding(cursor: &mut Cursor<Vec<u8>>) -> JvmResult<()> {
+    // Switch operands start at the next multiple of 4 from the start of the code
+    while !cursor.position().is_multiple_of(4) {
+        cursor.read_u8()?;
+    }
+    Ok(())
+}
+
+fn decode_table_switch(cursor: &mut Cursor<Vec<u8>>, pc: u32) -> JvmResult<TableSwitch> {
+    skip_padding(cursor)?;
+    let default = cursor.read_u32()? as i32;
+    let low = cursor.read_u32()? as i32;
+    let high = cursor.read_u32()? as i32;
+    if low > high {
+        return Err(JvmError::InvalidInstruction { opcode: OP_CODE_TABLE_SWITCH, pc });
+    }
+
+    let count = (high as i64 - low as i64 + 1) as usize;
+    // Each offset takes 4 bytes, don't trust the count before allocating
+    if count > cursor.get_ref().len() / 4 {
+        return Err(JvmError::Truncated { offset: cursor.position() });
+    }
+    let mut offsets: Vec<i32> = Vec::with_capacity(count);
+    for _ in 0..count {
+        offsets.push(cursor.read_u32()? as i32);
+    }
+
+    Ok(TableSwitch { default, low, high, offsets })
+}
+
+fn decode_lookup_switch(cursor: &mut Cursor<Vec<u8>>, pc: u32) -> JvmResult<LookupSwitch> {
+    skip_padding(cursor)?;
+    let default = cursor.read_u32()? as i32;
+    let npairs = cursor.read_u32()? as i32;
+    if npairs < 0 || npairs as usize > cursor.get_ref().len() / 8 {
+        return Err(JvmError::InvalidInstruction { opcode: OP_CODE_LOOKUP_SWITCH, pc });
+    }
+
+    let mut pairs: Vec<(i32, i32)> = Vec::with_capacity(npairs as usize);
+    for _ in 0..npairs {
+        let key = cursor.read_u32()? as i32;
+        let offset = cursor.read_u32()? as i32;
+        // The keys have to be sorted so the lookup can be a binary search
+        if pairs.last().is_some_and(|&(previous, _)| previous >= key) {
+            return Err(JvmError::InvalidInstruction { opcode: OP_CODE_LOOKUP_SWITCH, pc });
+        }
+        pairs.push((key, offset));
+    }
+
+    Ok(LookupSwitch { default, pairs })
+}
+
+fn decode_wide(cursor: &mut Cursor<Vec<u8>>, pc: u32) -> JvmResult<(u8, Op)> {
+    let opcode = cursor.read_u8()?;
+    let op = match opcode {
+        OP_CODE_ILOAD => Op::Iload(cursor.read_u16()?),
+        OP_CODE_LLOAD => Op::Lload(cursor.read_u16()?),
+        OP_CODE_FLOAD => Op::Fload(cursor.read_u16()?),
+        OP_CODE_DLOAD => Op::Dload(cursor.read_u16()?),
+        OP_CODE_ALOAD => Op::Aload(cursor.read_u16()?),
+        OP_CODE_ISTORE => Op::Istore(cursor.read_u16()?),
+        OP_CODE_LSTORE => Op::Lstore(cursor.read_u16()?),
+        OP_CODE_FSTORE => Op::Fstore(cursor.read_u16()?),
+        OP_CODE_DSTORE => Op::Dstore(cursor.read_u16()?),
+        OP_CODE_ASTORE => Op::Astore(cursor.read_u16()?),
+        OP_CODE_RET => Op::Ret(cursor.read_u16()?),
+        OP_CODE_IINC => Op::Iinc {
+            index: cursor.read_u16()?,
+            constant: cursor.read_u16()? as i16
+        },
+        _ => return Err(JvmError::InvalidInstruction { opcode, pc })
+    };
+
+    Ok((opcode, op))
+}
+
+fn decode_op(cursor: &mut Cursor<Vec<u8>>, opcode: u8, pc: u32) -> JvmResult<Op> {
+    let op = match opcode {
+        OP_CODE_BI_PUSH => Op::BiPush(cursor.read_u8()? as i8),
+        OP_CODE_SI_PUSH => Op::SiPush(cursor.read_u16()? as i16),
+        OP_CODE_IINC => Op::Iinc {
+            index: cursor.read_u8()? as u16,
+            constant: cursor.read_u8()? as i8 as i16
+        },
+        OP_CODE_TABLE_SWITCH => Op::TableSwitch(decode_table_switch(cursor, pc)?),
+        OP_CODE_LOOKUP_SWITCH => Op::LookupSwitch(decode_lookup_switch(cursor, pc)?),
+        OP_CODE_INVOKE_INTERFACE => {
+            let index = cursor.read_u16()?;
+            let count = cursor.read_u8()?;
+            // Always 0, kept for historical reasons
+            let zero = cursor.read_u8()?;
+            if count == 0 || zero != 0 {
+                return Err(JvmError::InvalidInstruction { opcode, pc });
+            }
+            Op::InvokeInterface { index, count }
+        }
+        OP_CODE_INVOKE_DYNAMIC => {
+            let index = cursor.read_u16()?;
+            cursor.read_u16()?;
+            Op::InvokeDynamic(index)
+        }
+        OP_CODE_MULTI_ANEW_ARRAY => Op::MultiAnewArray {
+            index: cursor.read_u16()?,
+            dimensions: cursor.read_u8()?
+        },
+        OP_CODE_NEW_ARRAY => Op::NewArray(cursor.read_u8()?),
+        OP_CODE_NOP => Op::Nop,
+        OP_CODE_ACONST_NULL => Op::AconstNull,
+        OP_CODE_ICONST_M1 => Op::IconstM1,
+        OP_CODE_ICONST_0 => Op::Iconst0,
+        OP_CODE_ICONST_1 => Op::Iconst1,
+        OP_CODE_ICONST_2 => Op::Iconst2,
+        OP_CODE_ICONST_3 => Op::Iconst3,
+        OP_CODE_ICONST_4 => Op::Iconst4,
+        OP_CODE_ICONST_5 => Op::Iconst5,
+        OP_CODE_LCONST_0 => Op::Lconst0,
+        OP_CODE_LCONST_1 => Op::Lconst1,
+        OP_CODE_FCONST_0 => Op::Fconst0,
+        OP_CODE_FCONST_1 => Op::Fconst1,
+        OP_CODE_FCONST_2 => Op::Fconst2,
+        OP_CODE_DCONST_0 => Op::Dconst0,
+        OP_CODE_DCONST_1 => Op::Dconst1,
+        OP_CODE_LDC => Op::Ldc(cursor.read_u8()? as u16),
+        OP_CODE_LDC_W => Op::LdcW(cursor.read_u16()?),
+        OP_CODE_LDC2_W => Op::Ldc2W(cursor.read_u16()?),
+        OP_CODE_ILOAD => Op::Iload(cursor.read_u8()? as u16),
+        OP_CODE_LLOAD => Op::Lload(cursor.read_u8()? as u16),
+        OP_CODE_FLOAD => Op::Fload(cursor.read_u8()? as u16),
+        OP_CODE_DLOAD => Op::Dload(cursor.read_u8()? as u16),
+        OP_CODE_ALOAD => Op::Aload(cursor.read_u8()? as u16),
+        OP_CODE_ILOAD_0 => Op::Iload0,
+        OP_CODE_ILOAD_1 => Op::Iload1,
+        OP_CODE_ILOAD_2 => Op::Iload2,
+        OP_CODE_ILOAD_3 => Op::Iload3,
+        OP_CODE_LLOAD_0 => Op::Lload0,
+        OP_CODE_LLOAD_1 => Op::Lload1,
+        OP_CODE_LLOAD_2 => Op::Lload2,
+        OP_CODE_LLOAD_3 => Op::Lload3,
+        OP_CODE_FLOAD_0 => Op::Fload0,
+        OP_CODE_FLOAD_1 => Op::Fload1,
+        OP_CODE_FLOAD_2 => Op::Fload2,
+        OP_CODE_FLOAD_3 => Op::Fload3,
+        OP_CODE_DLOAD_0 => Op::Dload0,
+        OP_CODE_DLOAD_1 => Op::Dload1,
+        OP_CODE_DLOAD_2 => Op::Dload2,
+        OP_CODE_DLOAD_3 => Op::Dload3,
+        OP_CODE_ALOAD_0 => Op::Aload0,
+        OP_CODE_ALOAD_1 => Op::Aload1,
+        OP_CODE_ALOAD_2 => Op::Aload2,
+        OP_CODE_ALOAD_3 => Op::Aload3,
+        OP_CODE_IALOAD => Op::Iaload,
+        OP_CODE_LALOAD => Op::Laload,
+        OP_CODE_FALOAD => Op::Faload,
+        OP_CODE_DALOAD => Op::Daload,
+        OP_CODE_AALOAD => Op::Aaload,
+        OP_CODE_BALOAD => Op::Baload,
+        OP_CODE_CALOAD => Op::Caload,
+        OP_CODE_SALOAD => Op::Saload,
+        OP_CODE_ISTORE => Op::Istore(cursor.read_u8()? as u16),
+        OP_CODE_LSTORE => Op::Lstore(cursor.read_u8()? as u16),
+        OP_CODE_FSTORE => Op::Fstore(cursor.read_u8()? as u16),
+        OP_CODE_DSTORE => Op::Dstore(cursor.read_u8()? as u16),
+        OP_CODE_ASTORE => Op::Astore(cursor.read_u8()? as u16),
+        OP_CODE_ISTORE_0 => Op::Istore0,
+        OP_CODE_ISTORE_1 => Op::Istore1,
+        OP_CODE_ISTORE_2 => Op::Istore2,
+        OP_CODE_ISTORE_3 => Op::Istore3,
+        OP_CODE_LSTORE_0 => Op::Lstore0,
+        OP_CODE_LSTORE_1 => Op::Lstore1,
+        OP_CODE_LSTORE_2 => Op::Lstore2,
+        OP_CODE_LSTORE_3 => Op::Lstore3,
+        OP_CODE_FSTORE_0 => Op::Fstore0,
+        OP_CODE_FSTORE_1 => Op::Fstore1,
+        OP_CODE_FSTORE_2 => Op::Fstore2,
+        OP_CODE_FSTORE_3 => Op::Fstore3,
+        OP_CODE_DSTORE_0 => Op::Dstore0,
+        OP_CODE_DSTORE_1 => Op::Dstore1,
+        OP_CODE_DSTORE_2 => Op::Dstore2,
+        OP_CODE_DSTORE_3 => Op::Dstore3,
+        OP_CODE_ASTORE_0 => Op::Astore0,
+        OP_CODE_ASTORE_1 => Op::Astore1,
+        OP_CODE_ASTORE_2 => Op::Astore2,
+        OP_CODE_ASTORE_3 => Op::Astore3,
+        OP_CODE_IASTORE => Op::Iastore,
+        OP_CODE_LASTORE => Op::Lastore,
+        OP_CODE_FASTORE => Op::Fastore,
+        OP_CODE_DASTORE => Op::Dastore,
+        OP_CODE_AASTORE => Op::Aastore,
+        OP_CODE_BASTORE => Op::Bastore,
+        OP_CODE_CASTORE => Op::Castore,
+        OP_CODE_SASTORE => Op::Sastore,
+        OP_CODE_POP => Op::Pop,
+        OP_CODE_POP2 => Op::Pop2,
+        OP_CODE_DUP => Op::Dup,
+        OP_CODE_DUP_X1 => Op::DupX1,
+        OP_CODE_DUP_X2 => Op::DupX2,
+        OP_CODE_DUP2 => Op::Dup2,
+        OP_CODE_DUP2_X1 => Op::Dup2X1,
+        OP_CODE_DUP2_X2 => Op::Dup2X2,
+        OP_CODE_SWAP => Op::Swap,
+        OP_CODE_IADD => Op::Iadd,
+        OP_CODE_LADD => Op::Ladd,
+        OP_CODE_FADD => Op::Fadd,
+        OP_CODE_DADD => Op::Dadd,
+        OP_CODE_ISUB => Op::Isub,
+        OP_CODE_LSUB => Op::Lsub,
+        OP_CODE_FSUB => Op::Fsub,
+        OP_CODE_DSUB => Op::Dsub,
+        OP_CODE_IMUL => Op::Imul,
+        OP_CODE_LMUL => Op::Lmul,
+        OP_CODE_FMUL => Op::Fmul,
+        OP_CODE_DMUL => Op::Dmul,
+        OP_CODE_IDIV => Op::Idiv,
+        OP_CODE_LDIV => Op::Ldiv,
+        OP_CODE_FDIV => Op::Fdiv,
+        OP_CODE_DDIV => Op::Ddiv,
+        OP_CODE_IREM => Op::Irem,
+        OP_CODE_LREM => Op::Lrem,
+        OP_CODE_FREM => Op::Frem,
+        OP_CODE_DREM => Op::Drem,
+        OP_CODE_INEG => Op::Ineg,
+        OP_CODE_LNEG => Op::Lneg,
+        OP_CODE_FNEG => Op::Fneg,
+        OP_CODE_DNEG => Op::Dneg,
+        OP_CODE_ISHL => Op::Ishl,
+        OP_CODE_LSHL => Op::Lshl,
+        OP_CODE_ISHR => Op::Ishr,
+        OP_CODE_LSHR => Op::Lshr,
+        OP_CODE_IUSHR => Op::Iushr,
+        OP_CODE_LUSHR => Op::Lushr,
+        OP_CODE_IAND => Op::Iand,
+        OP_CODE_LAND => Op::Land,
+        OP_CODE_IOR => Op::Ior,
+        OP_CODE_LOR => Op::Lor,
+        OP_CODE_IXOR => Op::Ixor,
+        OP_CODE_LXOR => Op::Lxor,
+        OP_CODE_I2L => Op::I2l,
+        OP_CODE_I2F => Op::I2f,
+        OP_CODE_I2D => Op::I2d,
+        OP_CODE_L2I => Op::L2i,
+        OP_CODE_L2F => Op::L2f,
+        OP_CODE_L2D => Op::L2d,
+        OP_CODE_F2I => Op::F2i,
+        OP_CODE_F2L => Op::F2l,
+        OP_CODE_F2D => Op::F2d,
+        OP_CODE_D2I => Op::D2i,
+        OP_CODE_D2L => Op::D2l,
+        OP_CODE_D2F => Op::D2f,
+        OP_CODE_I2B => Op::I2b,
+        OP_CODE_I2C => Op::I2c,
+        OP_CODE_I2S => Op::I2s,
+        OP_CODE_LCMP => Op::Lcmp,
+        OP_CODE_FCMPL => Op::Fcmpl,
+        OP_CODE_FCMPG => Op::Fcmpg,
+        OP_CODE_DCMPL => Op::Dcmpl,
+        OP_CODE_DCMPG => Op::Dcmpg,
+        OP_CODE_IF_EQ => Op::IfEq(cursor.read_u16()? as i16 as i32),
+        OP_CODE_IF_NE => Op::IfNe(cursor.read_u16()? as i16 as i32),
+        OP_CODE_IF_LT => Op::IfLt(cursor.read_u16()? as i16 as i32),
+        OP_CODE_IF_GE => Op::IfGe(cursor.read_u16()? as i16 as i32),
+        OP_CODE_IF_GT => Op::IfGt(cursor.read_u16()? as i16 as i32),
+        OP_CODE_IF_LE => Op::IfLe(cursor.read_u16()? as i16 as i32),
+        OP_CODE_IF_ICMP_EQ => Op::IfIcmpEq(cursor.read_u16()? as i16 as i32),
+        OP_CODE_IF_ICMP_NE => Op::IfIcmpNe(cursor.read_u16()? as i16 as i32),
+        OP_CODE_IF_ICMP_LT => Op::IfIcmpLt(cursor.read_u16()? as i16 as i32),
+        OP_CODE_IF_ICMP_GE => Op::IfIcmpGe(cursor.read_u16()? as i16 as i32),
+        OP_CODE_IF_ICMP_GT => Op::IfIcmpGt(cursor.read_u16()? as i16 as i32),
+        OP_CODE_IF_ICMP_LE => Op::IfIcmpLe(cursor.read_u16()? as i16 as i32),
+        OP_CODE_IF_ACMP_EQ => Op::IfAcmpEq(cursor.read_u16()? as i16 as i32),
+        OP_CODE_IF_ACMP_NE => Op::IfAcmpNe(cursor.read_u16()? as i16 as i32),
+        OP_CODE_GOTO => Op::Goto(cursor.read_u16()? as i16 as i32),
+        OP_CODE_JSR => Op::Jsr(cursor.read_u16()? as i16 as i32),
+        OP_CODE_RET => Op::Ret(cursor.read_u8()? as u16),
+        OP_CODE_IRETURN => Op::Ireturn,
+        OP_CODE_LRETURN => Op::Lreturn,
+        OP_CODE_FRETURN => Op::Freturn,
+        OP_CODE_DRETURN => Op::Dreturn,
+        OP_CODE_ARETURN => Op::Areturn,
+        OP_CODE_RETURN => Op::Return,
+        OP_CODE_GET_STATIC => Op::GetStatic(cursor.read_u16()?),
+        OP_CODE_PUT_STATIC => Op::PutStatic(cursor.read_u16()?),
+        OP_CODE_GET_FIELD => Op::GetField(cursor.read_u16()?),
+        OP_CODE_PUT_FIELD => Op::PutField(cursor.read_u16()?),
+        OP_CODE_INVOKE_VIRTUAL => Op::InvokeVirtual(cursor.read_u16()?),
+        OP_CODE_INVOKE_SPECIAL => Op::InvokeSpecial(cursor.read_u16()?),
+        OP_CODE_INVOKE_STATIC => Op::InvokeStatic(cursor.read_u16()?),
+        OP_CODE_NEW => Op::New(cursor.read_u16()?),
+        OP_CODE_ANEW_ARRAY => Op::AnewArray(cursor.read_u16()?),
+        OP_CODE_ARRAY_LENGTH => Op::ArrayLength,
+        OP_CODE_ATHROW => Op::Athrow,
+        OP_CODE_CHECK_CAST => Op::CheckCast(cursor.read_u16()?),
+        OP_CODE_INSTANCE_OF => Op::InstanceOf(cursor.read_u16()?),
+        OP_CODE_MONITOR_ENTER => Op::MonitorEnter,
+        OP_CODE_MONITOR_EXIT => Op::MonitorExit,
+        OP_CODE_IF_NULL => Op::IfNull(cursor.read_u16()? as i16 as i32),
+        OP_CODE_IF_NON_NULL => Op::IfNonNull(cursor.read_u16()? as i16 as i32),
+        OP_CODE_GOTO_W => Op::GotoW(cursor.read_u32()? as i32),
+        OP_CODE_JSR_W => Op::JsrW(cursor.read_u32()? as i32),
+        _ => return Err(JvmError::InvalidInstruction { opcode, pc })
+    };
+
+    Ok(op)
+}
+
+/// Decodes the whole code, the only error is InvalidInstruction for the first instruction that
+/// is unknown, malformed or runs past the end of the code
+pub fn decode(code: &[u8]) -> JvmResult<Vec<Instruction>> {
+    let mut cursor = Cursor::new(code.to_vec());
+    let mut instructions: Vec<Instruction> = Vec::new();
+
+    while cursor.position() < code.len() as u64 {
+        let pc = cursor.position() as u32;
+        let opcode = cursor.read_u8()?;
+        let truncated = |error: JvmError| match error {
+            JvmError::Truncated { .. } => JvmError::InvalidInstruction { opcode, pc },
+            error => error
+        };
+        let instruction = if opcode == OP_CODE_WIDE {
+            let (opcode, op) = decode_wide(&mut cursor, pc).map_err(truncated)?;
+            Instruction { pc, opcode, wide: true, op }
+        } else {
+            Instruction { pc, opcode, wide: false, op: decode_op(&mut cursor, opcode, pc).map_err(truncated)? }
+        };
+        instructions.push(instruction);
+    }
+
+    Ok(instructions)
+}
+
+/// Index of the instruction starting at pc, None if pc is not the start of an instruction
+pub fn index_of_pc(instructions: &[Instruction], pc: u32) -> Option<usize> {
+    instructions.binary_search_by_key(&pc, |instruction| instruction.pc).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int_bytes(values: &[i32]) -> Vec<u8> {
+        values.iter().flat_map(|value| value.to_be_bytes()).collect()
+    }
+
+    // Code with `pc` nops, then the switch opcode with its padding and operands, then a return
+    fn switch_code(pc: usize, opcode: u8, operands: &[i32]) -> Vec<u8> {
+        let mut code = vec![OP_CODE_NOP; pc];
+        code.push(opcode);
+        while !code.len().is_multiple_of(4) {
+            code.push(0);
+        }
+        code.extend(int_bytes(operands));
+        code.push(OP_CODE_RETURN);
+        code
+    }
+
+    fn decode_switch(pc: usize, opcode: u8, operands: &[i32]) -> Op {
+        let code = switch_code(pc, opcode, operands);
+        let mut instructions = decode(&code).unwrap();
+        assert_eq!(instructions.len(), pc + 2);
+
+        // The return right after the operands is only found if the padding was skipped correctly
+        let last = instructions.pop().unwrap();
+        assert_eq!((last.pc as usize, last.opcode), (code.len() - 1, OP_CODE_RETURN));
+        let switch = instructions.pop().unwrap();
+        assert_eq!((switch.pc as usize, switch.opcode), (pc, opcode));
+        switch.op
+    }
+
+    #[test]
+    fn table_switch_at_every_alignment() {
+        for pc in 0..4 {
+            let Op::TableSwitch(table) = decode_switch(pc, OP_CODE_TABLE_SWITCH, &[100, -1, 1, 10, 20, 30]) else {
+                panic!("not a tableswitch at pc {}", pc);
+            };
+            assert_eq!((table.default, table.low, table.high), (100, -1, 1));
+            assert_eq!(table.offsets, [10, 20, 30]);
+
+            assert_eq!(table.offset_for(-1), 10);
+            assert_eq!(table.offset_for(0), 20);
+            assert_eq!(table.offset_for(1), 30);
+            for value in [-2, 2, i32::MIN, i32::MAX] {
+                assert_eq!(table.offset_for(value), 100);
+            }
+        }
+    }
+
+    #[test]
+    fn table_switch_at_the_top_of_the_int_range() {
+        let Op::TableSwitch(table) = decode_switch(1, OP_CODE_TABLE_SWITCH, &[7, i32::MAX - 1, i32::MAX, 1, 2]) else {
+            panic!("not a tableswitch");
+        };
+        assert_eq!(table.offset_for(i32::MAX), 2);
+        assert_eq!(table.offset_for(i32::MIN), 7);
+    }
+
+    #[test]
+    fn table_switch_with_low_above_high_is_rejected() {
+        let code = switch_code(0, OP_CODE_TABLE_SWITCH, &[0, 2, 1]);
+        assert!(matches!(decode(&code), Err(JvmError::InvalidInstruction { opcode: OP_CODE_TABLE_SWITCH, pc: 0 })));
+    }
+
+    #[test]
+    fn lookup_switch_at_every_alignment() {
+        for pc in 0..4 {
+            let Op::LookupSwitch(lookup) = decode_switch(pc, OP_CODE_LOOKUP_SWITCH, &[100, 3, -5, 10, 0, 20, 1000, 30]) else {
+                panic!("not a lookupswitch at pc {}", pc);
+            };
+            assert_eq!(lookup.default, 100);
+            assert_eq!(lookup.pairs, [(-5, 10), (0, 20), (1000, 30)]);
+
+            assert_eq!(lookup.offset_for(-5), 10);
+            assert_eq!(lookup.offset_for(0), 20);
+            assert_eq!(lookup.offset_for(1000), 30);
+            for value in [-6, 1, 999, 1001, i32::MIN, i32::MAX] {
+                assert_eq!(lookup.offset_for(value), 100);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_lookup_switch_always_takes_the_default() {
+        let Op::LookupSwitch(lookup) = decode_switch(2, OP_CODE_LOOKUP_SWITCH, &[-8, 0]) else {
+            panic!("not a lookupswitch");
+        };
+        assert_eq!(lookup.offset_for(0), -8);
+    }
+
+    #[test]
+    fn lookup_switch_with_unsorted_keys_is_rejected() {
+        for operands in [[0, 2, 5, 1, 4, 2], [0, 2, 5, 1, 5, 2]] {
+            let code = switch_code(3, OP_CODE_LOOKUP_SWITCH, &operands);
+            assert!(matches!(decode(&code), Err(JvmError::InvalidInstruction { opcode: OP_CODE_LOOKUP_SWITCH, pc: 3 })));
+        }
+        let code = switch_code(0, OP_CODE_LOOKUP_SWITCH, &[0, -1]);
+        assert!(matches!(decode(&code), Err(JvmError::InvalidInstruction { opcode: OP_CODE_LOOKUP_SWITCH, pc: 0 })));
+    }
+
+    #[test]
+    fn wide_widens_the_index_and_the_increment() {
+        let code = [
+            OP_CODE_WIDE, OP_CODE_IINC, 0x01, 0x00, 0xff, 0x38,
+            OP_CODE_WIDE, OP_CODE_ILOAD, 0x12, 0x34,
+            OP_CODE_WIDE, OP_CODE_LSTORE, 0x00, 0x05,
+            OP_CODE_IINC, 0xff, 0xff
+        ];
+        let instructions = decode(&code).unwrap();
+        assert_eq!(instructions.iter().map(|instruction| (instruction.pc, instruction.wide)).collect::<Vec<_>>(),
+            [(0, true), (6, true), (10, true), (14, false)]);
+        assert_eq!(instructions[0].opcode, OP_CODE_IINC);
+        assert!(matches!(instructions[0].op, Op::Iinc { index: 256, constant: -200 }));
+        assert!(matches!(instructions[1].op, Op::Iload(0x1234)));
+        assert!(matches!(instructions[2].op, Op::Lstore(5)));
+        // Without wide the index is unsigned and the increment signed
+        assert!(matches!(instructions[3].op, Op::Iinc { index: 255, constant: -1 }));
+    }
+
+    #[test]
+    fn wide_only_applies_to_locals_instructions() {
+        assert!(matches!(decode(&[OP_CODE_WIDE, OP_CODE_NOP]), Err(JvmError::InvalidInstruction { opcode: OP_CODE_NOP, pc: 0 })));
+        // wide iinc needs four operand bytes
+        assert!(matches!(decode(&[OP_CODE_NOP, OP_CODE_WIDE, OP_CODE_IINC, 0, 1, 0]),
+            Err(JvmError::InvalidInstruction { opcode: OP_CODE_WIDE, pc: 1 })));
+    }
+
+    #[test]
+    fn invoke_interface_count_and_zero_bytes() {
+        let instructions = decode(&[OP_CODE_INVOKE_INTERFACE, 0x00, 0x07, 0x02, 0x00]).unwrap();
+        assert!(matches!(instructions[0].op, Op::InvokeInterface { index: 7, count: 2 }));
+
+        for code in [[OP_CODE_INVOKE_INTERFACE, 0x00, 0x07, 0x00, 0x00], [OP_CODE_INVOKE_INTERFACE, 0x00, 0x07, 0x01, 0x01]] {
+            assert!(matches!(decode(&code), Err(JvmError::InvalidInstruction { opcode: OP_CODE_INVOKE_INTERFACE, pc: 0 })));
+        }
+    }
+
+    #[test]
+    fn unknown_and_truncated_instructions_are_rejected() {
+        for opcode in [OP_CODE_BREAKPOINT, 0xcb, OP_CODE_IMPDEP1, OP_CODE_IMPDEP2] {
+            assert!(matches!(decode(&[OP_CODE_NOP, opcode]), Err(JvmError::InvalidInstruction { opcode: found, pc: 1 }) if found == opcode));
+        }
+        assert!(matches!(decode(&[OP_CODE_NOP, OP_CODE_NOP, OP_CODE_INVOKE_INTERFACE, 0x00]),
+            Err(JvmError::InvalidInstruction { opcode: OP_CODE_INVOKE_INTERFACE, pc: 2 })));
+        assert!(matches!(decode(&[OP_CODE_TABLE_SWITCH, 0, 0, 0, 0, 0, 0, 0]),
+            Err(JvmError::InvalidInstruction { opcode: OP_CODE_TABLE_SWITCH, pc: 0 })));
+    }
+}
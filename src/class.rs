@@ -0,0 +1,425 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use crate::attributes::Attribute;
+use crate::class_file::{ClassAccessFlags, ClassFile, Constant, FieldInfo, FieldsAccessFlags, MethodInfo, MethodsAccessFlags};
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::error::{JvmError, JvmResult};
+use crate::value::{Reference, Value};
+
+/// Initialization state of a class, JVMS 5.5. There is only one thread, so a class being
+/// initialized can only be reached again from its own initialization
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitializationState {
+    Uninitialized,
+    InProgress,
+    Initialized,
+    // <clinit> failed, the class can't be used anymore
+    Erroneous
+}
+
+/// An instance field in an object's layout
+#[derive(Clone)]
+pub struct InstanceField {
+    // Class that declares the field, fields of different classes can share a name
+    pub declaring_class: String,
+    pub name: String,
+    pub field_type: FieldType
+}
+
+/// A field reference from the constant pool resolved to its slot in the object layout.
+/// Subclasses extend the layout of their superclass, so the slot is the same for every
+/// object the reference can be used on.
+#[derive(Clone)]
+pub struct ResolvedField {
+    pub index: usize,
+    pub field_type: FieldType
+}
+
+/// A static field reference resolved to the class that declares the field
+#[derive(Clone)]
+pub struct ResolvedStaticField {
+    pub class: Rc<Class>,
+    pub name: String,
+    pub field_type: FieldType
+}
+
+/// A method that can be selected by invokevirtual or invokeinterface, JVMS 5.4.6
+#[derive(Clone)]
+pub struct VtableEntry {
+    // Weak because the entries for a class's own methods point back at the class
+    pub class: Weak<Class>,
+    pub name: String,
+    pub descriptor: String,
+    // Index into the declaring class's methods
+    pub method_index: usize,
+    pub is_abstract: bool,
+    // Default and abstract methods of interfaces lose to any method of a class
+    pub from_interface: bool
+}
+
+impl VtableEntry {
+    /// The declaring class and the method, classes are never unloaded so the class is still there
+    pub fn method(&self) -> Option<(Rc<Class>, usize)> {
+        self.class.upgrade().map(|class| (class, self.method_index))
+    }
+}
+
+/// A method reference from the constant pool resolved for invokevirtual or invokeinterface.
+/// Subclasses extend the vtable of their superclass, so the vtable index is the same for
+/// every receiver the reference can be used on.
+#[derive(Clone)]
+pub enum ResolvedMethod {
+    // Selected from the receiver's vtable
+    Virtual { index: usize },
+    // Selected from the receiver's itable for the interface, slot is an index into the interface's vtable
+    Interface { interface: Rc<Class>, slot: usize },
+    // Private methods are never overridden, they are called without selection
+    Direct { class: Rc<Class>, method_index: usize },
+    // A method of a platform class, which is never loaded, selected from the receiver's class
+    // by name. The methods of java/lang/Object are built in for classes that don't override them
+    ByName { name: String, descriptor: String }
+}
+
+/// Key of a method in the vtable indices
+pub fn method_key(name: &str, descriptor: &str) -> String {
+    format!("{}{}", name, descriptor)
+}
+
+/// A loaded class, with its superclass and superinterfaces already loaded. Classes of the
+/// Java platform are built into the interpreter and never loaded, so they never show up as
+/// a superclass or superinterface.
+pub struct Class {
+    // Binary name, e.g. java/lang/String
+    pub name: String,
+    pub class_file: Box<ClassFile>,
+    pub super_class: Option<Rc<Class>>,
+    pub interfaces: Vec<Rc<Class>>,
+    // Layout of instances, the fields of the superclass come first
+    pub instance_fields: Vec<InstanceField>,
+    // Values of the static fields declared by this class, by name
+    pub(crate) static_values: RefCell<HashMap<String, Value>>,
+    pub(crate) initialization: Cell<InitializationState>,
+    // Classes already resolved from this class's constant pool, by constant index
+    pub(crate) resolved_classes: RefCell<HashMap<u16, Rc<Class>>>,
+    // Instance fields already resolved from this class's constant pool, by constant index
+    pub(crate) resolved_fields: RefCell<HashMap<u16, ResolvedField>>,
+    pub(crate) resolved_static_fields: RefCell<HashMap<u16, ResolvedStaticField>>,
+    // Methods selected for each name and descriptor, the superclass's entries come first
+    pub vtable: Vec<VtableEntry>,
+    pub(crate) vtable_indices: HashMap<String, usize>,
+    // For every superinterface, the vtable index implementing each entry of the interface's vtable
+    pub itables: HashMap<String, Vec<Option<usize>>>,
+    // Methods already resolved from this class's constant pool, by constant index
+    pub(crate) resolved_methods: RefCell<HashMap<u16, ResolvedMethod>>,
+    // Descriptors of the method references in this class's constant pool, by constant index
+    pub(crate) method_descriptors: RefCell<HashMap<u16, Rc<MethodDescriptor>>>
+}
+
+fn is_static(field: &FieldInfo) -> bool {
+    field.access_flags & FieldsAccessFlags::STATIC as u16 != 0
+}
+
+/// Static fields of a class set to their default values, which is what preparation does
+pub fn static_values(class_file: &ClassFile) -> JvmResult<HashMap<String, Value>> {
+    let mut values: HashMap<String, Value> = HashMap::new();
+    for field in class_file.fields.iter().filter(|field| is_static(field)) {
+        let field_type = FieldType::parse(class_file.get_utf8(field.descriptor_index).unwrap_or_default())?;
+        values.insert(class_file.get_utf8(field.name_index).unwrap_or_default().to_string(), Value::default_for(&field_type));
+    }
+    Ok(values)
+}
+
+/// Lays out the instance fields of a class, after the ones inherited from its superclass
+pub fn instance_fields(class_file: &ClassFile, super_class: Option<&Rc<Class>>) -> JvmResult<Vec<InstanceField>> {
+    let mut fields: Vec<InstanceField> = super_class.map(|super_class| super_class.instance_fields.clone()).unwrap_or_default();
+    let class_name = class_file.name().unwrap_or_default();
+    for field in &class_file.fields {
+        if is_static(field) {
+            continue;
+        }
+        fields.push(InstanceField {
+            declaring_class: class_name.to_string(),
+            name: class_file.get_utf8(field.name_index).unwrap_or_default().to_string(),
+            field_type: FieldType::parse(class_file.get_utf8(field.descriptor_index).unwrap_or_default())?
+        });
+    }
+    Ok(fields)
+}
+
+/// Whether the interface method should replace the one selected so far, JVMS 5.4.3.3 picks
+/// the maximally-specific superinterface method and prefers one that is not abstract
+fn is_more_specific(entry: &VtableEntry, selected: &VtableEntry) -> bool {
+    if !selected.from_interface {
+        return false;
+    }
+    let (interface, selected_interface) = match (entry.class.upgrade(), selected.class.upgrade()) {
+        (Some(interface), Some(selected_interface)) => (interface, selected_interface),
+        _ => return false
+    };
+    if interface.name == selected_interface.name {
+        return false;
+    }
+    if interface.is_subtype_of(&selected_interface.name) {
+        return true;
+    }
+    if selected_interface.is_subtype_of(&interface.name) {
+        return false;
+    }
+    selected.is_abstract && !entry.is_abstract
+}
+
+/// Builds the vtable of a class: the superclass's vtable with the methods the class declares
+/// overriding or added to it, then the methods of the superinterfaces that no class provides.
+/// For interfaces the superclass is always absent.
+pub fn vtable(this: &Weak<Class>, class_file: &ClassFile, super_class: Option<&Rc<Class>>, interfaces: &[Rc<Class>]) -> (Vec<VtableEntry>, HashMap<String, usize>) {
+    let mut vtable: Vec<VtableEntry> = super_class.map(|super_class| super_class.vtable.clone()).unwrap_or_default();
+    let mut indices: HashMap<String, usize> = super_class.map(|super_class| super_class.vtable_indices.clone()).unwrap_or_default();
+    let is_interface = class_file.has_flag(ClassAccessFlags::INTERFACE);
+
+    for (method_index, method) in class_file.methods.iter().enumerate() {
+        let name = class_file.get_utf8(method.name_index).unwrap_or_default();
+        // Static and private methods are not selected, <init> and <clinit> are never inherited
+        if method.has_flag(MethodsAccessFlags::STATIC) || method.has_flag(MethodsAccessFlags::PRIVATE) || name.starts_with('<') {
+            continue;
+        }
+        let descriptor = class_file.get_utf8(method.descriptor_index).unwrap_or_default();
+        let entry = VtableEntry {
+            class: this.clone(),
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            method_index,
+            is_abstract: method.has_flag(MethodsAccessFlags::ABSTRACT),
+            from_interface: is_interface
+        };
+        match indices.get(&method_key(name, descriptor)) {
+            Some(&index) => vtable[index] = entry,
+            None => {
+                indices.insert(method_key(name, descriptor), vtable.len());
+                vtable.push(entry);
+            }
+        }
+    }
+
+    // The vtable of an interface already has the methods of its own superinterfaces
+    for interface in interfaces {
+        for entry in &interface.vtable {
+            let key = method_key(&entry.name, &entry.descriptor);
+            match indices.get(&key) {
+                Some(&index) => if is_more_specific(entry, &vtable[index]) {
+                    vtable[index] = entry.clone();
+                },
+                None => {
+                    indices.insert(key, vtable.len());
+                    vtable.push(entry.clone());
+                }
+            }
+        }
+    }
+
+    (vtable, indices)
+}
+
+/// Builds the itables of a class from its vtable indices. Overriding keeps vtable indices,
+/// so the itables of the superclass stay valid and only new interfaces are added
+pub fn itables(vtable_indices: &HashMap<String, usize>, super_class: Option<&Rc<Class>>, interfaces: &[Rc<Class>]) -> HashMap<String, Vec<Option<usize>>> {
+    fn add(itables: &mut HashMap<String, Vec<Option<usize>>>, vtable_indices: &HashMap<String, usize>, interface: &Rc<Class>) {
+        if itables.contains_key(&interface.name) {
+            return;
+        }
+        let itable = interface.vtable.iter()
+            .map(|entry| vtable_indices.get(&method_key(&entry.name, &entry.descriptor)).copied())
+            .collect();
+        itables.insert(interface.name.clone(), itable);
+        for super_interface in &interface.interfaces {
+            add(itables, vtable_indices, super_interface);
+        }
+    }
+
+    let mut itables = super_class.map(|super_class| super_class.itables.clone()).unwrap_or_default();
+    for interface in interfaces {
+        add(&mut itables, vtable_indices, interface);
+    }
+    itables
+}
+
+impl Class {
+    pub fn is_interface(&self) -> bool {
+        self.class_file.has_flag(ClassAccessFlags::INTERFACE)
+    }
+
+    /// Sets the static fields that have a ConstantValue attribute, the first step of initialization
+    pub fn apply_constant_values(&self) -> JvmResult<()> {
+        let class_file = &self.class_file;
+        for field in class_file.fields.iter().filter(|field| is_static(field)) {
+            let index = match field.attributes.iter().find_map(|attribute| match attribute {
+                Attribute::ConstantValue(constant_value) => Some(constant_value.constant_value_index),
+                _ => None
+            }) {
+                Some(index) => index,
+                None => continue
+            };
+
+            let name = class_file.get_utf8(field.name_index).unwrap_or_default();
+            let field_type = FieldType::parse(class_file.get_utf8(field.descriptor_index).unwrap_or_default())?;
+            // Offsets into the class file are gone by now, so a mismatch is reported against the field
+            let invalid = || JvmError::InvalidClass {
+                name: self.name.clone(),
+                reason: format!("ConstantValue #{} of field {} does not match its type {}", index, name, field_type)
+            };
+            let value = match (&field_type, class_file.get_constant(index)) {
+                (FieldType::Long, Some(Constant::Long(constant))) => Value::Long(constant.value as i64),
+                (FieldType::Float, Some(Constant::Float(constant))) => Value::Float(constant.value),
+                (FieldType::Double, Some(Constant::Double(constant))) => Value::Double(constant.value),
+                (FieldType::Object(class_name), Some(Constant::String(constant))) if class_name == "java/lang/String" => {
+                    let string = class_file.get_utf8(constant.string_index).ok_or_else(invalid)?;
+                    Value::Reference(Reference::String(Rc::from(string)))
+                }
+                (FieldType::Int | FieldType::Short | FieldType::Char | FieldType::Byte | FieldType::Boolean, Some(Constant::Integer(constant))) =>
+                    Value::Int(constant.value as i32).narrow_to(&field_type),
+                _ => return Err(invalid())
+            };
+            self.static_values.borrow_mut().insert(name.to_string(), value);
+        }
+        Ok(())
+    }
+
+    /// Whether the interface declares methods with a body, which makes initializing a class
+    /// that implements it initialize the interface as well
+    pub fn declares_default_methods(&self) -> bool {
+        self.class_file.methods.iter().any(|method| {
+            !method.has_flag(MethodsAccessFlags::ABSTRACT) && !method.has_flag(MethodsAccessFlags::STATIC)
+        })
+    }
+
+    pub fn declares_static_field(&self, name: &str) -> bool {
+        self.static_values.borrow().contains_key(name)
+    }
+
+    pub fn declares_field(&self, name: &str, descriptor: &str) -> bool {
+        self.class_file.fields.iter().any(|field| {
+            self.class_file.get_utf8(field.name_index) == Some(name) && self.class_file.get_utf8(field.descriptor_index) == Some(descriptor)
+        })
+    }
+
+    /// Slot of the instance field declared by the named class
+    pub fn field_index(&self, declaring_class: &str, name: &str) -> Option<usize> {
+        self.instance_fields.iter().position(|field| field.declaring_class == declaring_class && field.name == name)
+    }
+
+    /// Finds a method declared by this class or inherited from a superclass, returns the
+    /// class that declares it along with it
+    pub fn lookup_method(self: &Rc<Class>, name: &str, descriptor: &str) -> Option<(Rc<Class>, &MethodInfo)> {
+        let mut current = Some(self);
+        while let Some(class) = current {
+            if let Some(method) = class.class_file.find_method(name, descriptor) {
+                return Some((class.clone(), method));
+            }
+            current = class.super_class.as_ref();
+        }
+        None
+    }
+
+    /// Index into the vtable of the method selected for the name and descriptor
+    pub fn vtable_index(&self, name: &str, descriptor: &str) -> Option<usize> {
+        self.vtable_indices.get(&method_key(name, descriptor)).copied()
+    }
+
+    /// Whether this class is the named class or extends or implements it, directly or not
+    pub fn is_subtype_of(&self, name: &str) -> bool {
+        if self.name == name {
+            return true;
+        }
+        if self.super_class.as_ref().is_some_and(|super_class| super_class.is_subtype_of(name)) {
+            return true;
+        }
+        self.interfaces.iter().any(|interface| interface.is_subtype_of(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class_loader::ClassLoader;
+    use crate::code::execute_method;
+
+    // Classes compiled from the .java files next to them
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/etc/tests");
+
+    fn load(loader: &ClassLoader, name: &str) -> Rc<Class> {
+        loader.load_class(&format!("Dispatch${}", name)).unwrap()
+    }
+
+    // Name of the class declaring the method selected for name()I
+    fn selected(class: &Class, name: &str) -> String {
+        let index = class.vtable_index(name, "()I").unwrap_or_else(|| panic!("{} is not in the vtable of {}", name, class.name));
+        class.vtable[index].method().unwrap().0.name.clone()
+    }
+
+    fn call_static(loader: &ClassLoader, name: &str) -> i32 {
+        let class = loader.load_class("Dispatch").unwrap();
+        let method = class.class_file.find_method(name, "()I").unwrap();
+        match execute_method(loader, &class, method, Vec::new()) {
+            Ok(Some(Value::Int(value))) => value,
+            result => panic!("{} returned {:?}", name, result)
+        }
+    }
+
+    #[test]
+    fn overrides_reuse_the_superclass_slot() {
+        let loader = ClassLoader::new(ClassLoader::parse_class_path(FIXTURES));
+        let base = load(&loader, "Base");
+        let derived = load(&loader, "Derived");
+
+        assert_eq!(derived.vtable_index("m", "()I"), base.vtable_index("m", "()I"));
+        assert_eq!(derived.vtable_index("n", "()I"), base.vtable_index("n", "()I"));
+        assert_eq!(selected(&derived, "m"), "Dispatch$Derived");
+        assert_eq!(selected(&derived, "n"), "Dispatch$Base");
+        // New methods go after the inherited ones: extra, abstractK and the default k
+        assert!(derived.vtable_index("extra", "()I").unwrap() >= base.vtable.len());
+        assert_eq!(derived.vtable.len(), base.vtable.len() + 3);
+
+        // lookup_method only follows superclasses, so it finds no default methods
+        assert_eq!(derived.lookup_method("n", "()I").unwrap().0.name, "Dispatch$Base");
+        assert_eq!(derived.lookup_method("m", "()I").unwrap().0.name, "Dispatch$Derived");
+        assert!(derived.lookup_method("k", "()I").is_none());
+
+        assert_eq!(call_static(&loader, "mOfDerived"), 210);
+    }
+
+    #[test]
+    fn the_most_specific_default_method_is_selected() {
+        let loader = ClassLoader::new(ClassLoader::parse_class_path(FIXTURES));
+        let k = load(&loader, "K");
+        let k2 = load(&loader, "K2");
+        let derived = load(&loader, "Derived");
+
+        assert_eq!(selected(&k2, "k"), "Dispatch$K2");
+        assert_eq!(selected(&derived, "k"), "Dispatch$K2");
+        assert_eq!(selected(&load(&loader, "Both"), "k"), "Dispatch$K2");
+
+        // The itable of K maps its k slot to the class's slot holding K2.k
+        let slot = k.vtable_index("k", "()I").unwrap();
+        assert_eq!(derived.itables["Dispatch$K"][slot], derived.vtable_index("k", "()I"));
+        let slot = k.vtable_index("abstractK", "()I").unwrap();
+        assert_eq!(derived.itables["Dispatch$K"][slot], derived.vtable_index("abstractK", "()I"));
+        assert_eq!(selected(&derived, "abstractK"), "Dispatch$Derived");
+
+        assert_eq!(call_static(&loader, "kOfDerived"), 2);
+    }
+
+    #[test]
+    fn private_and_static_interface_methods_are_not_selected() {
+        let loader = ClassLoader::new(ClassLoader::parse_class_path(FIXTURES));
+        let with_private = load(&loader, "WithPrivate");
+        let uses_private = load(&loader, "UsesPrivate");
+
+        assert!(with_private.vtable_index("hidden", "()I").is_none());
+        assert!(with_private.vtable_index("helper", "()I").is_none());
+        assert_eq!(with_private.vtable.len(), 1);
+        assert_eq!(uses_private.itables["Dispatch$WithPrivate"], [uses_private.vtable_index("visible", "()I")]);
+        assert!(uses_private.vtable_index("hidden", "()I").is_none());
+
+        assert_eq!(call_static(&loader, "visibleOfUsesPrivate"), 5);
+    }
+}
@@ -1,6 +1,16 @@
-use std::io;
 use std::io::{Cursor};
-use crate::utils::{read_attributes, ReadFromCursor};
+use std::ops::RangeInclusive;
+use crate::error::{JvmError, JvmResult, Location};
+use crate::attributes::{read_attributes, Attribute};
+use crate::code::CodeAttribute;
+use crate::signature::{ClassSignature, MethodSignature, TypeSignature};
+use crate::utils::ReadFromCursor;
+
+pub const CLASS_FILE_MAGIC: u32 = 0xCAFEBABE;
+
+// Java 1.0.2 up to Java 21
+pub const SUPPORTED_MAJOR_VERSIONS: RangeInclusive<u16> = 45..=65;
+pub const PREVIEW_MINOR_VERSION: u16 = 0xFFFF;
 
 pub const CONSTANT_UTF8: u8                 = 1;
 pub const CONSTANT_INTEGER: u8              = 3;
@@ -15,23 +25,74 @@ pub const CONSTANT_INTERFACE_METHOD_REF: u8 = 11;
 pub const CONSTANT_NAME_AND_TYPE: u8        = 12;
 pub const CONSTANT_METHOD_HANDLE: u8        = 15;
 pub const CONSTANT_METHOD_TYPE: u8          = 16;
+pub const CONSTANT_DYNAMIC: u8              = 17;
 pub const CONSTANT_INVOKE_DYNAMIC: u8       = 18;
+pub const CONSTANT_MODULE: u8               = 19;
+pub const CONSTANT_PACKAGE: u8              = 20;
+
+pub const REF_GET_FIELD: u8             = 1;
+pub const REF_GET_STATIC: u8            = 2;
+pub const REF_PUT_FIELD: u8             = 3;
+pub const REF_PUT_STATIC: u8            = 4;
+pub const REF_INVOKE_VIRTUAL: u8        = 5;
+pub const REF_INVOKE_STATIC: u8         = 6;
+pub const REF_INVOKE_SPECIAL: u8        = 7;
+pub const REF_NEW_INVOKE_SPECIAL: u8    = 8;
+pub const REF_INVOKE_INTERFACE: u8      = 9;
 
 pub enum Constant {
     Utf8(ConstantUft8),
     Integer(ConstantInteger),
     Float(ConstantFloat),
+    Long(ConstantLong),
+    Double(ConstantDouble),
     Class(ConstantClass),
     String(ConstantString),
     FieldRef(ConstantFieldRef),
     MethodRef(ConstantMethodRef),
     InterfaceMethodRef(ConstantInterfaceMethodRef),
-    NameAndType(ConstantNameAndType)
+    NameAndType(ConstantNameAndType),
+    MethodHandle(ConstantMethodHandle),
+    MethodType(ConstantMethodType),
+    Dynamic(ConstantDynamic),
+    InvokeDynamic(ConstantInvokeDynamic),
+    Module(ConstantModule),
+    Package(ConstantPackage),
+    // Long and Double take up two entries in the pool, the second one is never valid
+    Unusable
+}
+
+impl Constant {
+    /// Name of the tag the constant was read with, e.g. CONSTANT_CLASS
+    pub fn tag(&self) -> &str {
+        match self {
+            Constant::Utf8(constant) => &constant.tag,
+            Constant::Integer(constant) => &constant.tag,
+            Constant::Float(constant) => &constant.tag,
+            Constant::Long(constant) => &constant.tag,
+            Constant::Double(constant) => &constant.tag,
+            Constant::Class(constant) => &constant.tag,
+            Constant::String(constant) => &constant.tag,
+            Constant::FieldRef(constant) => &constant.tag,
+            Constant::MethodRef(constant) => &constant.tag,
+            Constant::InterfaceMethodRef(constant) => &constant.tag,
+            Constant::NameAndType(constant) => &constant.tag,
+            Constant::MethodHandle(constant) => &constant.tag,
+            Constant::MethodType(constant) => &constant.tag,
+            Constant::Dynamic(constant) => &constant.tag,
+            Constant::InvokeDynamic(constant) => &constant.tag,
+            Constant::Module(constant) => &constant.tag,
+            Constant::Package(constant) => &constant.tag,
+            Constant::Unusable => "the second entry of a Long or Double"
+        }
+    }
 }
 
 pub struct ConstantUft8 {
     pub tag: String,
-    pub data: String
+    pub data: String,
+    // The exact UTF-16 code units, data can't hold unpaired surrogates
+    pub utf16: Vec<u16>
 }
 
 pub struct ConstantInteger {
@@ -44,6 +105,16 @@ pub struct ConstantFloat {
     pub value: f32
 }
 
+pub struct ConstantLong {
+    pub tag: String,
+    pub value: u64
+}
+
+pub struct ConstantDouble {
+    pub tag: String,
+    pub value: f64
+}
+
 pub struct ConstantClass {
     pub tag: String,
     pub name_index: u16
@@ -78,6 +149,41 @@ pub struct ConstantNameAndType {
     pub descriptor_index: u16
 }
 
+pub struct ConstantMethodHandle {
+    pub tag: String,
+    pub reference_kind: u8,
+    pub reference_index: u16
+}
+
+pub struct ConstantMethodType {
+    pub tag: String,
+    pub descriptor_index: u16
+}
+
+pub struct ConstantDynamic {
+    pub tag: String,
+    pub bootstrap_method_attr_index: u16,
+    pub name_and_type_index: u16
+}
+
+pub struct ConstantInvokeDynamic {
+    pub tag: String,
+    pub bootstrap_method_attr_index: u16,
+    pub name_and_type_index: u16
+}
+
+pub struct ConstantModule {
+    pub tag: String,
+    pub name_index: u16
+}
+
+pub struct ConstantPackage {
+    pub tag: String,
+    pub name_index: u16
+}
+
+// The access flags keep the names of the ACC_ constants in the JVMS
+#[allow(clippy::upper_case_acronyms)]
 pub enum ClassAccessFlags {
     PUBLIC =	    0x0001,
     FINAL =	        0x0010,
@@ -87,8 +193,10 @@ pub enum ClassAccessFlags {
     SYNTHETIC =	    0x1000,
     ANNOTATION =    0x2000,
     ENUM =	        0x4000,
+    MODULE =        0x8000,
 }
 
+#[allow(clippy::upper_case_acronyms)]
 pub enum FieldsAccessFlags {
     PUBLIC =	0x0001,
     PRIVATE =	0x0002,
@@ -101,6 +209,7 @@ pub enum FieldsAccessFlags {
     ENUM =	    0x4000,
 }
 
+#[allow(clippy::upper_case_acronyms)]
 pub enum MethodsAccessFlags {
     PUBLIC =	    0x0001,
     PRIVATE =	    0x0002,
@@ -116,19 +225,69 @@ pub enum MethodsAccessFlags {
     SYNTHETIC =	    0x1000,
 }
 
+/// Features whose legality depends on the class file version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassFileFeature {
+    // jsr, jsr_w and ret
+    Subroutines,
+    InvokeDynamic,
+    MethodHandleConstants,
+    InterfaceDefaultAndStaticMethods,
+    Modules,
+    NestMates,
+    DynamicConstants,
+    PreviewClasses,
+    Records,
+    SealedClasses
+}
+
+impl ClassFileFeature {
+    pub fn is_legal_in(&self, major: u16) -> bool {
+        match self {
+            ClassFileFeature::Subroutines => major < 51,
+            ClassFileFeature::InvokeDynamic => major >= 51,
+            ClassFileFeature::MethodHandleConstants => major >= 51,
+            ClassFileFeature::InterfaceDefaultAndStaticMethods => major >= 52,
+            ClassFileFeature::Modules => major >= 53,
+            ClassFileFeature::NestMates => major >= 55,
+            ClassFileFeature::DynamicConstants => major >= 55,
+            ClassFileFeature::PreviewClasses => major >= 56,
+            ClassFileFeature::Records => major >= 60,
+            ClassFileFeature::SealedClasses => major >= 61
+        }
+    }
+}
+
+pub struct FieldInfo {
+    pub access_flags: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub attributes: Vec<Attribute>
+}
+
 pub struct MethodInfo {
     pub access_flags: u16,
     pub name_index: u16,
     pub descriptor_index: u16,
-    pub attributes: Vec<AttributeInfo>
+    pub attributes: Vec<Attribute>
 }
 
-pub struct AttributeInfo {
-    pub attribute_name_index: u16,
-    pub info: Vec<u8>
+impl MethodInfo {
+    pub fn code(&self) -> Option<&CodeAttribute> {
+        self.attributes.iter().find_map(|att| match att {
+            Attribute::Code(code) => Some(code),
+            _ => None
+        })
+    }
+
+    pub fn has_flag(&self, flag: MethodsAccessFlags) -> bool {
+        self.access_flags & flag as u16 != 0
+    }
 }
 
 pub struct ClassFile {
+    // Always CLASS_FILE_MAGIC, kept with the rest of the header
+    #[allow(dead_code)]
     pub magic: u32,
     pub minor: u16,
     pub major: u16,
@@ -136,57 +295,37 @@ pub struct ClassFile {
     pub access_flags: u16,
     pub this_class: u16,
     pub super_class: u16,
-    // interfaces
-    // fields
+    pub interfaces: Vec<u16>,
+    pub fields: Vec<FieldInfo>,
     pub methods: Vec<MethodInfo>,
-    pub attributes: Vec<AttributeInfo>
+    pub attributes: Vec<Attribute>
 }
 
 impl ClassFile {
-    pub fn new(cursor: &mut Cursor<Vec<u8>>) -> io::Result<Box<ClassFile>> {
+    pub fn new(cursor: &mut Cursor<Vec<u8>>) -> JvmResult<Box<ClassFile>> {
         let magic = cursor.read_u32()?;
+        if magic != CLASS_FILE_MAGIC {
+            return Err(JvmError::InvalidMagic { magic });
+        }
+
         let minor = cursor.read_u16()?;
         let major = cursor.read_u16()?;
+        ClassFile::check_version(major, minor)?;
 
-        let constants_pool: Vec<Constant>;
-        match ClassFile::read_constants_pool(cursor) {
-            Err(err) => {
-                eprintln!("Error reading constants pool: {}", err);
-                return Err(err)
-            }
-            Ok(pool) => constants_pool = pool
-        }
+        let constants_pool = ClassFile::read_constants_pool(cursor, major)?;
 
         let access_flags = cursor.read_u16()?;
         let this_class = cursor.read_u16()?;
         let super_class = cursor.read_u16()?;
 
-        let interfaces_count = cursor.read_u16()?;
-        assert_eq!(interfaces_count, 0, "Interfaces are not supported");
-
-        let fields_count = cursor.read_u16()?;
-        assert_eq!(fields_count, 0, "Fields are not supported");
-
-        let methods: Vec<MethodInfo>;
-        match ClassFile::read_methods(cursor) {
-            Err(err) => {
-                eprintln!("Error reading methods: {}", err);
-                return Err(err)
-            }
-            Ok(mds) => methods = mds
-        }
+        let interfaces = ClassFile::read_interfaces(cursor)?;
+        let fields = ClassFile::read_fields(cursor, &constants_pool, major)?;
+        let methods = ClassFile::read_methods(cursor, &constants_pool, major)?;
 
         let attributes_count = cursor.read_u16()?;
-        let attributes: Vec<AttributeInfo>;
-        match read_attributes(cursor, attributes_count as usize) {
-            Err(err) => {
-                eprintln!("Error reading class attributes: {}", err);
-                return Err(err)
-            }
-            Ok(ats) => attributes = ats
-        }
+        let attributes = read_attributes(cursor, &constants_pool, attributes_count as usize, major)?;
 
-        return Ok(Box::new(ClassFile {
+        Ok(Box::new(ClassFile {
             magic,
             minor,
             major,
@@ -194,42 +333,133 @@ impl ClassFile {
             access_flags,
             this_class,
             super_class,
+            interfaces,
+            fields,
             methods,
             attributes
-        }));
+        }))
+    }
+
+    pub fn supports(&self, feature: ClassFileFeature) -> bool {
+        feature.is_legal_in(self.major)
+    }
+
+    fn check_version(major: u16, minor: u16) -> JvmResult<()> {
+        if !SUPPORTED_MAJOR_VERSIONS.contains(&major) {
+            return Err(JvmError::UnsupportedVersion { major, minor });
+        }
+
+        // From Java 12 the minor version only marks preview classes, which only run on the
+        // release they were compiled for
+        if ClassFileFeature::PreviewClasses.is_legal_in(major) {
+            let valid = minor == 0 || (minor == PREVIEW_MINOR_VERSION && major == *SUPPORTED_MAJOR_VERSIONS.end());
+            if !valid {
+                return Err(JvmError::UnsupportedVersion { major, minor });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the constant at a 1-based pool index, None if the index doesn't point to a usable entry
+    pub fn get_constant(&self, index: u16) -> Option<&Constant> {
+        match self.constants_pool.get((index as usize).wrapping_sub(1)) {
+            Some(Constant::Unusable) => None,
+            constant => constant
+        }
+    }
+
+    pub fn get_utf8(&self, index: u16) -> Option<&str> {
+        match self.get_constant(index) {
+            Some(Constant::Utf8(utf8)) => Some(utf8.data.as_str()),
+            _ => None
+        }
+    }
+
+    /// Name of the class referenced by a Class constant
+    pub fn get_class_name(&self, index: u16) -> Option<&str> {
+        match self.get_constant(index)? {
+            Constant::Class(class) => self.get_utf8(class.name_index),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.get_class_name(self.this_class)
+    }
+
+    /// None for java/lang/Object, the only class without a superclass
+    pub fn super_class_name(&self) -> Option<&str> {
+        if self.super_class == 0 {
+            return None;
+        }
+        self.get_class_name(self.super_class)
+    }
+
+    pub fn has_flag(&self, flag: ClassAccessFlags) -> bool {
+        self.access_flags & flag as u16 != 0
     }
 
     pub fn find_method_by_name(&self, name: &str) -> Option<&MethodInfo> {
+        self.methods.iter().find(|method| self.get_utf8(method.name_index) == Some(name))
+    }
+
+    pub fn find_method(&self, name: &str, descriptor: &str) -> Option<&MethodInfo> {
         self.methods.iter().find(|method| {
-            if let Some(Constant::Utf8(method_name)) = self.constants_pool.get((method.name_index - 1) as usize) {
-                return method_name.data == name
-            }
-            return false
+            self.get_utf8(method.name_index) == Some(name) && self.get_utf8(method.descriptor_index) == Some(descriptor)
         })
     }
 
-    pub fn find_attribute_by_name<'a>(&'a self, attributes: &'a Vec<AttributeInfo>, name: &str) -> Option<&'a AttributeInfo> {
-        attributes.iter().find(|&att| {
-            if let Some(Constant::Utf8(att_name)) = self.constants_pool.get((att.attribute_name_index - 1) as usize) {
-                return att_name.data == name;
-            }
-            false
+    pub fn find_field_by_name(&self, name: &str) -> Option<&FieldInfo> {
+        self.fields.iter().find(|field| self.get_utf8(field.name_index) == Some(name))
+    }
+
+    fn find_signature(&self, attributes: &[Attribute]) -> Option<&str> {
+        attributes.iter().find_map(|att| match att {
+            Attribute::Signature(signature) => self.get_utf8(signature.signature_index),
+            _ => None
         })
     }
 
-    fn read_constants_pool(cursor: &mut Cursor<Vec<u8>>) -> io::Result<Vec<Constant>> {
+    pub fn class_signature(&self) -> JvmResult<Option<ClassSignature>> {
+        self.find_signature(&self.attributes).map(ClassSignature::parse).transpose()
+    }
+
+    pub fn method_signature(&self, method: &MethodInfo) -> JvmResult<Option<MethodSignature>> {
+        self.find_signature(&method.attributes).map(MethodSignature::parse).transpose()
+    }
+
+    pub fn field_signature(&self, field: &FieldInfo) -> JvmResult<Option<TypeSignature>> {
+        self.find_signature(&field.attributes).map(TypeSignature::parse_field).transpose()
+    }
+
+    fn read_constants_pool(cursor: &mut Cursor<Vec<u8>>, major: u16) -> JvmResult<Vec<Constant>> {
         let pool_count = cursor.read_u16()?;
         let mut pool: Vec<Constant> = Vec::with_capacity(pool_count as usize);
 
-        for _ in 0..pool_count - 1 {
+        // The pool is indexed from 1 to pool_count - 1
+        while pool.len() < (pool_count as usize).saturating_sub(1) {
+            let offset = cursor.position();
             let tag: u8  = cursor.read_u8()?;
+            let required_feature = match tag {
+                CONSTANT_METHOD_HANDLE | CONSTANT_METHOD_TYPE => Some(ClassFileFeature::MethodHandleConstants),
+                CONSTANT_INVOKE_DYNAMIC => Some(ClassFileFeature::InvokeDynamic),
+                CONSTANT_DYNAMIC => Some(ClassFileFeature::DynamicConstants),
+                CONSTANT_MODULE | CONSTANT_PACKAGE => Some(ClassFileFeature::Modules),
+                _ => None
+            };
+            if required_feature.is_some_and(|feature| !feature.is_legal_in(major)) {
+                return Err(JvmError::IllegalConstantTag { tag, major, offset });
+            }
+
             let constant: Constant = match tag {
                 CONSTANT_UTF8 => {
                     let length: u16 = cursor.read_u16()?;
-                    let data: String = cursor.read_string(length as usize)?;
+                    let utf16: Vec<u16> = cursor.read_utf16(length as usize)?;
                     Constant::Utf8(ConstantUft8 {
                         tag: String::from("CONSTANT_UTF8"),
-                        data
+                        data: String::from_utf16_lossy(&utf16),
+                        utf16
                     })
                 },
                 CONSTANT_INTEGER => Constant::Integer(ConstantInteger {
@@ -240,6 +470,14 @@ impl ClassFile {
                     tag: String::from("CONSTANT_FLOAT"),
                     value: cursor.read_f32()?
                 }),
+                CONSTANT_LONG => Constant::Long(ConstantLong {
+                    tag: String::from("CONSTANT_LONG"),
+                    value: cursor.read_u64()?
+                }),
+                CONSTANT_DOUBLE => Constant::Double(ConstantDouble {
+                    tag: String::from("CONSTANT_DOUBLE"),
+                    value: cursor.read_f64()?
+                }),
                 CONSTANT_STRING => Constant::String(ConstantString {
                     tag: String::from("CONSTANT_STRING"),
                     string_index: cursor.read_u16()?
@@ -268,18 +506,83 @@ impl ClassFile {
                     name_index: cursor.read_u16()?,
                     descriptor_index: cursor.read_u16()?
                 }),
-                _ => {
-                    unimplemented!("Unsupported constant pool tag: {}", tag);
-                }
+                CONSTANT_METHOD_HANDLE => Constant::MethodHandle(ConstantMethodHandle {
+                    tag: String::from("CONSTANT_METHOD_HANDLE"),
+                    reference_kind: cursor.read_u8()?,
+                    reference_index: cursor.read_u16()?
+                }),
+                CONSTANT_METHOD_TYPE => Constant::MethodType(ConstantMethodType {
+                    tag: String::from("CONSTANT_METHOD_TYPE"),
+                    descriptor_index: cursor.read_u16()?
+                }),
+                CONSTANT_DYNAMIC => Constant::Dynamic(ConstantDynamic {
+                    tag: String::from("CONSTANT_DYNAMIC"),
+                    bootstrap_method_attr_index: cursor.read_u16()?,
+                    name_and_type_index: cursor.read_u16()?
+                }),
+                CONSTANT_INVOKE_DYNAMIC => Constant::InvokeDynamic(ConstantInvokeDynamic {
+                    tag: String::from("CONSTANT_INVOKE_DYNAMIC"),
+                    bootstrap_method_attr_index: cursor.read_u16()?,
+                    name_and_type_index: cursor.read_u16()?
+                }),
+                CONSTANT_MODULE => Constant::Module(ConstantModule {
+                    tag: String::from("CONSTANT_MODULE"),
+                    name_index: cursor.read_u16()?
+                }),
+                CONSTANT_PACKAGE => Constant::Package(ConstantPackage {
+                    tag: String::from("CONSTANT_PACKAGE"),
+                    name_index: cursor.read_u16()?
+                }),
+                _ => return Err(JvmError::UnknownConstantTag { tag, offset })
             };
 
+            let takes_two_entries = matches!(constant, Constant::Long(_) | Constant::Double(_));
             pool.push(constant);
+            if takes_two_entries {
+                // The unusable entry needs an index of its own below pool_count
+                if pool.len() + 1 >= pool_count as usize {
+                    return Err(JvmError::InvalidConstantIndex { index: pool_count, location: Location::Offset(offset) });
+                }
+                pool.push(Constant::Unusable);
+            }
+        }
+
+        Ok(pool)
+    }
+
+    fn read_interfaces(cursor: &mut Cursor<Vec<u8>>) -> JvmResult<Vec<u16>> {
+        let interfaces_count = cursor.read_u16()?;
+        let mut interfaces: Vec<u16> = Vec::with_capacity(interfaces_count as usize);
+
+        for _ in 0..interfaces_count {
+            interfaces.push(cursor.read_u16()?);
         }
 
-        return Ok(pool)
+        Ok(interfaces)
     }
 
-    fn read_methods(cursor: &mut Cursor<Vec<u8>>) -> io::Result<Vec<MethodInfo>> {
+    fn read_fields(cursor: &mut Cursor<Vec<u8>>, constants_pool: &[Constant], major: u16) -> JvmResult<Vec<FieldInfo>> {
+        let fields_count = cursor.read_u16()?;
+        let mut fields: Vec<FieldInfo> = Vec::with_capacity(fields_count as usize);
+
+        for _ in 0..fields_count {
+            let access_flags: u16 = cursor.read_u16()?;
+            let name_index: u16 = cursor.read_u16()?;
+            let descriptor_index: u16 = cursor.read_u16()?;
+            let attributes_count: u16 = cursor.read_u16()?;
+            let attributes: Vec<Attribute> = read_attributes(cursor, constants_pool, attributes_count as usize, major)?;
+            fields.push(FieldInfo {
+                access_flags,
+                name_index,
+                descriptor_index,
+                attributes
+            });
+        }
+
+        Ok(fields)
+    }
+
+    fn read_methods(cursor: &mut Cursor<Vec<u8>>, constants_pool: &[Constant], major: u16) -> JvmResult<Vec<MethodInfo>> {
         let methods_count = cursor.read_u16()?;
         let mut methods: Vec<MethodInfo> = Vec::with_capacity(methods_count as usize);
 
@@ -288,7 +591,7 @@ impl ClassFile {
             let name_index: u16 = cursor.read_u16()?;
             let descriptor_index: u16 = cursor.read_u16()?;
             let attributes_count: u16 = cursor.read_u16()?;
-            let attributes: Vec<AttributeInfo> = read_attributes(cursor, attributes_count as usize)?;
+            let attributes: Vec<Attribute> = read_attributes(cursor, constants_pool, attributes_count as usize, major)?;
             methods.push(MethodInfo {
                 access_flags,
                 name_index,
@@ -297,6 +600,140 @@ impl ClassFile {
             });
         }
 
-        return Ok(methods)
+        Ok(methods)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf8(string: &str) -> Vec<u8> {
+        let mut bytes = vec![CONSTANT_UTF8];
+        bytes.extend_from_slice(&(string.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(string.as_bytes());
+        bytes
+    }
+
+    fn class(name_index: u16) -> Vec<u8> {
+        let mut bytes = vec![CONSTANT_CLASS];
+        bytes.extend_from_slice(&name_index.to_be_bytes());
+        bytes
+    }
+
+    /// A class file holding the constants, with this_class at #2 and fields given as
+    /// (access flags, name index, descriptor index)
+    fn class_bytes(major: u16, minor: u16, constants: &[Vec<u8>], fields: &[(u16, u16, u16)]) -> Vec<u8> {
+        let pool_count = 1 + constants.iter()
+            .map(|constant| if constant[0] == CONSTANT_LONG || constant[0] == CONSTANT_DOUBLE { 2 } else { 1 })
+            .sum::<u16>();
+        let mut bytes: Vec<u8> = Vec::new();
+        bytes.extend_from_slice(&CLASS_FILE_MAGIC.to_be_bytes());
+        bytes.extend_from_slice(&minor.to_be_bytes());
+        bytes.extend_from_slice(&major.to_be_bytes());
+        bytes.extend_from_slice(&pool_count.to_be_bytes());
+        for constant in constants {
+            bytes.extend_from_slice(constant);
+        }
+        bytes.extend_from_slice(&[0x00, 0x21, 0x00, 0x02, 0x00, 0x00]); // Access flags, this_class, super_class
+        bytes.extend_from_slice(&[0x00, 0x00]); // Interfaces
+        bytes.extend_from_slice(&(fields.len() as u16).to_be_bytes());
+        for (access_flags, name_index, descriptor_index) in fields {
+            for value in [access_flags, name_index, descriptor_index, &0] {
+                bytes.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]); // Methods and attributes
+        bytes
+    }
+
+    fn long(value: u64) -> Vec<u8> {
+        let mut bytes = vec![CONSTANT_LONG];
+        bytes.extend_from_slice(&value.to_be_bytes());
+        bytes
+    }
+
+    fn double(value: f64) -> Vec<u8> {
+        let mut bytes = vec![CONSTANT_DOUBLE];
+        bytes.extend_from_slice(&value.to_bits().to_be_bytes());
+        bytes
+    }
+
+    fn parse(bytes: Vec<u8>) -> JvmResult<Box<ClassFile>> {
+        ClassFile::new(&mut Cursor::new(bytes))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn find_field_by_name() {
+        let constants = [utf8("Test"), class(1), utf8("count"), utf8("I"), utf8("name"), utf8("Ljava/lang/String;")];
+        let class_file = parse(class_bytes(52, 0, &constants, &[(0x0008, 3, 4), (0x0001, 5, 6)])).unwrap();
+
+        let field = class_file.find_field_by_name("name").unwrap();
+        assert_eq!(class_file.get_utf8(field.descriptor_index), Some("Ljava/lang/String;"));
+        assert_eq!(class_file.find_field_by_name("count").unwrap().access_flags, FieldsAccessFlags::STATIC as u16);
+        assert!(class_file.find_field_by_name("missing").is_none());
+        // Methods and fields are looked up separately
+        assert!(class_file.find_method_by_name("count").is_none());
+    }
+
+    #[test]
+    fn long_and_double_take_two_pool_entries() {
+        let constants = [utf8("Test"), class(1), long(0x0123456789ABCDEF), double(-2.5), utf8("after")];
+        let class_file = parse(class_bytes(52, 0, &constants, &[])).unwrap();
+
+        assert!(matches!(class_file.get_constant(3), Some(Constant::Long(constant)) if constant.value == 0x0123456789ABCDEF));
+        assert!(class_file.get_constant(4).is_none());
+        assert!(matches!(class_file.get_constant(5), Some(Constant::Double(constant)) if constant.value == -2.5));
+        assert!(class_file.get_constant(6).is_none());
+        assert_eq!(class_file.get_utf8(7), Some("after"));
+        assert!(class_file.get_constant(8).is_none());
+        assert_eq!(class_file.name(), Some("Test"));
+    }
+
+    #[test]
+    fn long_in_the_last_pool_entry_is_rejected() {
+        for last in [long(1), double(1.0)] {
+            let mut bytes = class_bytes(52, 0, &[utf8("Test"), class(1), last], &[]);
+            // One entry short of what the last constant needs
+            bytes[8..10].copy_from_slice(&4u16.to_be_bytes());
+            match parse(bytes) {
+                Err(JvmError::InvalidConstantIndex { index: 4, location: Location::Offset(20) }) => {}
+                Err(other) => panic!("expected InvalidConstantIndex, got {:?}", other),
+                Ok(_) => panic!("a Long or Double in the last entry was accepted")
+            }
+        }
+    }
+
+    fn version_check(major: u16, minor: u16) -> JvmResult<Box<ClassFile>> {
+        parse(class_bytes(major, minor, &[utf8("Test"), class(1)], &[]))
+    }
+
+    #[test]
+    fn bad_magic_is_rejected() {
+        let mut bytes = class_bytes(61, 0, &[utf8("Test"), class(1)], &[]);
+        bytes[3] = 0xBF;
+        assert!(matches!(parse(bytes), Err(JvmError::InvalidMagic { magic: 0xCAFEBABF })));
+        assert!(matches!(parse(vec![0xCA, 0xFE]), Err(JvmError::Truncated { offset: 0 })));
+    }
+
+    #[test]
+    fn major_versions_from_45_to_65() {
+        for major in [45, 52, 61, 65] {
+            assert!(version_check(major, 0).is_ok(), "major {}", major);
+        }
+        for major in [0, 44, 66, u16::MAX] {
+            assert!(matches!(version_check(major, 0), Err(JvmError::UnsupportedVersion { major: found, minor: 0 }) if found == major));
+        }
+    }
+
+    #[test]
+    fn preview_minor_version_only_on_the_latest_major() {
+        assert_eq!(version_check(65, PREVIEW_MINOR_VERSION).unwrap().minor, PREVIEW_MINOR_VERSION);
+        for (major, minor) in [(61, PREVIEW_MINOR_VERSION), (56, PREVIEW_MINOR_VERSION), (65, 1), (56, 3)] {
+            assert!(matches!(version_check(major, minor), Err(JvmError::UnsupportedVersion { .. })), "{}.{}", major, minor);
+        }
+        // Before Java 12 any minor version is accepted
+        for (major, minor) in [(45, 3), (55, 7), (55, PREVIEW_MINOR_VERSION)] {
+            assert!(version_check(major, minor).is_ok(), "{}.{}", major, minor);
+        }
+    }
+}
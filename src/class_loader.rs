@@ -0,0 +1,407 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::class::{instance_fields, itables, static_values, vtable, Class, InitializationState};
+use crate::class_file::{ClassAccessFlags, ClassFile, ClassFileFeature, Constant, MethodsAccessFlags};
+use crate::error::{JvmError, JvmResult, Location};
+use crate::jar::JarFile;
+
+pub enum ClassPathEntry {
+    // Root of a package hierarchy, a/b/C is read from <root>/a/b/C.class
+    Directory(PathBuf),
+    // a/b/C is read from the a/b/C.class entry
+    Jar(JarFile)
+}
+
+impl ClassPathEntry {
+    /// Paths ending in .jar are JAR files, anything else is a directory
+    pub fn open(path: PathBuf) -> JvmResult<ClassPathEntry> {
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("jar")) {
+            return Ok(ClassPathEntry::Jar(JarFile::open(&path)?));
+        }
+        Ok(ClassPathEntry::Directory(path))
+    }
+}
+
+/// Classes of the Java platform are provided by the interpreter, they are never looked up
+/// on the class path
+pub fn is_platform_class(name: &str) -> bool {
+    ["java/", "javax/", "jdk/", "sun/"].iter().any(|prefix| name.starts_with(prefix))
+}
+
+/// What the constant at index is, for errors about constants of the wrong kind
+fn constant_kind(class_file: &ClassFile, index: u16) -> &str {
+    class_file.constants_pool.get((index as usize).wrapping_sub(1)).map_or("missing", Constant::tag)
+}
+
+/// Before version 52 interfaces can't have default or static methods, all their methods
+/// except <clinit> have to be public and abstract (JVMS 4.6)
+fn check_interface_methods(name: &str, class_file: &ClassFile) -> JvmResult<()> {
+    if !class_file.has_flag(ClassAccessFlags::INTERFACE) || class_file.supports(ClassFileFeature::InterfaceDefaultAndStaticMethods) {
+        return Ok(());
+    }
+    for method in &class_file.methods {
+        let method_name = class_file.get_utf8(method.name_index).unwrap_or_default();
+        if method_name != "<clinit>" && !(method.has_flag(MethodsAccessFlags::PUBLIC) && method.has_flag(MethodsAccessFlags::ABSTRACT)) {
+            return Err(JvmError::InvalidClass {
+                name: name.to_string(),
+                reason: format!("interface method {} must be public and abstract in version {} class files", method_name, class_file.major)
+            });
+        }
+    }
+    Ok(())
+}
+
+// Superclass and superinterfaces of a class being defined
+type Supertypes = (Option<Rc<Class>>, Vec<Rc<Class>>);
+
+pub struct ClassLoader {
+    class_path: Vec<ClassPathEntry>,
+    // Every class loaded so far, by binary name
+    classes: RefCell<HashMap<String, Rc<Class>>>,
+    // Classes whose superclass and superinterfaces are being loaded
+    loading: RefCell<Vec<String>>
+}
+
+impl ClassLoader {
+    pub fn new(class_path: Vec<ClassPathEntry>) -> ClassLoader {
+        ClassLoader {
+            class_path,
+            classes: RefCell::new(HashMap::new()),
+            loading: RefCell::new(Vec::new())
+        }
+    }
+
+    /// Parses a class path in the platform's format, entries separated by ':' or ';' on Windows
+    pub fn parse_class_path(class_path: &str) -> Vec<ClassPathEntry> {
+        let mut entries: Vec<ClassPathEntry> = Vec::new();
+        for path in std::env::split_paths(class_path) {
+            if path.as_os_str().is_empty() {
+                continue;
+            }
+            match ClassPathEntry::open(path) {
+                Ok(entry) => entries.push(entry),
+                // Like java, entries that cannot be opened are skipped
+                Err(err) => eprintln!("Warning: ignoring class path entry, {}", err)
+            }
+        }
+        entries
+    }
+
+    /// Loader for java -jar, the class path is the JAR followed by its manifest's Class-Path.
+    /// Returns the binary name of the Main-Class with it
+    pub fn for_jar(path: &Path) -> JvmResult<(ClassLoader, String)> {
+        let jar = JarFile::open(path)?;
+        let manifest = jar.manifest()?;
+        let main_class = match manifest.as_ref().and_then(|manifest| manifest.main_class.as_ref()) {
+            Some(main_class) => main_class.replace('.', "/"),
+            None => return Err(JvmError::InvalidJar { path: path.display().to_string(), reason: String::from("no Main-Class in the manifest") })
+        };
+
+        let mut class_path = vec![ClassPathEntry::Jar(jar)];
+        let base = path.parent().unwrap_or(Path::new(""));
+        for relative in manifest.map(|manifest| manifest.class_path).unwrap_or_default() {
+            match ClassPathEntry::open(base.join(relative)) {
+                Ok(entry) => class_path.push(entry),
+                Err(err) => eprintln!("Warning: ignoring class path entry, {}", err)
+            }
+        }
+
+        Ok((ClassLoader::new(class_path), main_class))
+    }
+
+    fn find_class_bytes(&self, name: &str) -> JvmResult<Option<Vec<u8>>> {
+        for entry in &self.class_path {
+            match entry {
+                ClassPathEntry::Directory(root) => {
+                    let path = root.join(format!("{}.class", name));
+                    if path.is_file() {
+                        return Ok(Some(std::fs::read(path)?));
+                    }
+                }
+                ClassPathEntry::Jar(jar) => {
+                    if let Some(bytes) = jar.read(&format!("{}.class", name))? {
+                        return Ok(Some(bytes));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the class with the given binary name, loading it from the class path the first time
+    pub fn load_class(&self, name: &str) -> JvmResult<Rc<Class>> {
+        if let Some(class) = self.classes.borrow().get(name) {
+            return Ok(class.clone());
+        }
+        if is_platform_class(name) {
+            return Err(JvmError::ClassNotFound { name: name.to_string() });
+        }
+
+        let bytes = self.find_class_bytes(name)?.ok_or_else(|| JvmError::ClassNotFound { name: name.to_string() })?;
+        let invalid = |reason: String| JvmError::InvalidClass { name: name.to_string(), reason };
+        let class_file = ClassFile::new(&mut Cursor::new(bytes)).map_err(|err| invalid(err.to_string()))?;
+        match class_file.name() {
+            Some(actual) if actual == name => self.define_class(class_file),
+            Some(actual) => Err(invalid(format!("wrong name {}", actual))),
+            None => Err(invalid(format!("this_class is {}, not a Class constant", constant_kind(&class_file, class_file.this_class))))
+        }
+    }
+
+    /// Creates a class from a parsed class file, loading its superclass and superinterfaces
+    pub fn define_class(&self, class_file: Box<ClassFile>) -> JvmResult<Rc<Class>> {
+        let name = match class_file.name() {
+            Some(name) => name.to_string(),
+            None => return Err(JvmError::InvalidClass {
+                name: String::from("<unknown>"),
+                reason: format!("this_class is {}, not a Class constant", constant_kind(&class_file, class_file.this_class))
+            })
+        };
+        let name = name.as_str();
+        check_interface_methods(name, &class_file)?;
+
+        // A class that is still loading its supertypes is being reached through its own supertypes
+        if self.loading.borrow().iter().any(|loading| loading == name) {
+            return Err(JvmError::ClassCircularity { name: name.to_string() });
+        }
+        self.loading.borrow_mut().push(name.to_string());
+        let supertypes = self.load_supertypes(name, &class_file);
+        self.loading.borrow_mut().pop();
+        let (super_class, interfaces) = supertypes?;
+        let instance_fields = instance_fields(&class_file, super_class.as_ref())?;
+        let static_values = static_values(&class_file)?;
+
+        // The vtable refers back to the class for the methods it declares
+        let class = Rc::new_cyclic(|this| {
+            let (vtable, vtable_indices) = vtable(this, &class_file, super_class.as_ref(), &interfaces);
+            let itables = itables(&vtable_indices, super_class.as_ref(), &interfaces);
+            Class {
+                name: name.to_string(),
+                class_file,
+                super_class,
+                interfaces,
+                instance_fields,
+                static_values: RefCell::new(static_values),
+                initialization: Cell::new(InitializationState::Uninitialized),
+                resolved_classes: RefCell::new(HashMap::new()),
+                resolved_fields: RefCell::new(HashMap::new()),
+                resolved_static_fields: RefCell::new(HashMap::new()),
+                vtable,
+                vtable_indices,
+                itables,
+                resolved_methods: RefCell::new(HashMap::new()),
+                method_descriptors: RefCell::new(HashMap::new())
+            }
+        });
+        self.classes.borrow_mut().insert(name.to_string(), class.clone());
+        Ok(class)
+    }
+
+    fn load_supertypes(&self, name: &str, class_file: &ClassFile) -> JvmResult<Supertypes> {
+        let invalid = |reason: String| JvmError::InvalidClass { name: name.to_string(), reason };
+
+        let mut super_class: Option<Rc<Class>> = None;
+        if class_file.super_class != 0 {
+            let super_name = class_file.super_class_name().ok_or_else(|| {
+                invalid(format!("super_class is {}, not a Class constant", constant_kind(class_file, class_file.super_class)))
+            })?;
+            if !is_platform_class(super_name) {
+                let loaded = self.load_class(super_name)?;
+                if loaded.is_interface() {
+                    return Err(invalid(format!("superclass {} is an interface", super_name)));
+                }
+                if loaded.class_file.has_flag(ClassAccessFlags::FINAL) {
+                    return Err(invalid(format!("cannot inherit from final class {}", super_name)));
+                }
+                super_class = Some(loaded);
+            }
+        }
+
+        let mut interfaces: Vec<Rc<Class>> = Vec::with_capacity(class_file.interfaces.len());
+        for &index in &class_file.interfaces {
+            let interface_name = class_file.get_class_name(index).ok_or_else(|| {
+                invalid(format!("interface #{} is {}, not a Class constant", index, constant_kind(class_file, index)))
+            })?;
+            if is_platform_class(interface_name) {
+                continue;
+            }
+            let loaded = self.load_class(interface_name)?;
+            if !loaded.is_interface() {
+                return Err(invalid(format!("{} is not an interface", interface_name)));
+            }
+            interfaces.push(loaded);
+        }
+
+        Ok((super_class, interfaces))
+    }
+
+    /// Resolves the Class constant at index in the class's constant pool, loading the class
+    /// the first time it is referenced
+    pub fn resolve_class(&self, class: &Class, index: u16, pc: u32) -> JvmResult<Rc<Class>> {
+        if let Some(resolved) = class.resolved_classes.borrow().get(&index) {
+            return Ok(resolved.clone());
+        }
+
+        let name = match class.class_file.get_constant(index) {
+            Some(Constant::Class(_)) => class.class_file.get_class_name(index)
+                .ok_or(JvmError::InvalidConstantIndex { index, location: Location::Pc(pc) })?,
+            _ => return Err(JvmError::UnexpectedConstant { index, expected: "Class", location: Location::Pc(pc) })
+        };
+        let resolved = self.load_class(name)?;
+        class.resolved_classes.borrow_mut().insert(index, resolved.clone());
+        Ok(resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class_file::{CLASS_FILE_MAGIC, CONSTANT_CLASS, CONSTANT_UTF8};
+    use crate::jar::tests::archive;
+
+    const PUBLIC_CLASS: u16 = ClassAccessFlags::PUBLIC as u16 | ClassAccessFlags::SUPER as u16;
+    const FINAL_CLASS: u16 = PUBLIC_CLASS | ClassAccessFlags::FINAL as u16;
+    const INTERFACE: u16 = ClassAccessFlags::PUBLIC as u16 | ClassAccessFlags::INTERFACE as u16 | ClassAccessFlags::ABSTRACT as u16;
+
+    /// A directory under the system temp directory, removed with everything in it when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = std::env::temp_dir().join(format!("jvm-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn write(&self, relative: &str, bytes: &[u8]) {
+            let path = self.0.join(relative);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, bytes).unwrap();
+        }
+
+        fn path(&self, relative: &str) -> String {
+            self.0.join(relative).to_str().unwrap().to_string()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A version 52 class file without members, each class name is a Utf8 followed by its
+    /// Class constant
+    fn class_bytes(name: &str, access_flags: u16, super_name: &str, interfaces: &[&str]) -> Vec<u8> {
+        let names: Vec<&str> = [name, super_name].iter().chain(interfaces).copied().collect();
+        let mut bytes: Vec<u8> = Vec::new();
+        bytes.extend_from_slice(&CLASS_FILE_MAGIC.to_be_bytes());
+        bytes.extend_from_slice(&[0, 0, 0, 52]);
+        bytes.extend_from_slice(&(names.len() as u16 * 2 + 1).to_be_bytes());
+        for (i, class_name) in names.iter().enumerate() {
+            bytes.push(CONSTANT_UTF8);
+            bytes.extend_from_slice(&(class_name.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(class_name.as_bytes());
+            bytes.push(CONSTANT_CLASS);
+            bytes.extend_from_slice(&(i as u16 * 2 + 1).to_be_bytes());
+        }
+        bytes.extend_from_slice(&access_flags.to_be_bytes());
+        bytes.extend_from_slice(&[0, 2, 0, 4]); // this_class, super_class
+        bytes.extend_from_slice(&(interfaces.len() as u16).to_be_bytes());
+        for i in 0..interfaces.len() {
+            bytes.extend_from_slice(&(i as u16 * 2 + 6).to_be_bytes());
+        }
+        bytes.extend_from_slice(&[0; 6]); // Fields, methods and attributes
+        bytes
+    }
+
+    fn class(name: &str, super_name: &str) -> Vec<u8> {
+        class_bytes(name, PUBLIC_CLASS, super_name, &[])
+    }
+
+    fn loader(directory: &TempDir) -> ClassLoader {
+        ClassLoader::new(ClassLoader::parse_class_path(&directory.path("")))
+    }
+
+    fn invalid_reason(result: JvmResult<Rc<Class>>) -> String {
+        match result {
+            Err(JvmError::InvalidClass { reason, .. }) => reason,
+            Err(other) => panic!("expected InvalidClass, got {:?}", other),
+            Ok(class) => panic!("expected InvalidClass, loaded {}", class.name)
+        }
+    }
+
+    #[test]
+    fn circular_superclasses_are_rejected() {
+        let directory = TempDir::new("circularity");
+        directory.write("A.class", &class("A", "B"));
+        directory.write("B.class", &class("B", "A"));
+        directory.write("Self.class", &class("Self", "Self"));
+        directory.write("I.class", &class_bytes("I", INTERFACE, "java/lang/Object", &["J"]));
+        directory.write("J.class", &class_bytes("J", INTERFACE, "java/lang/Object", &["I"]));
+        directory.write("C.class", &class("C", "java/lang/Object"));
+        let loader = loader(&directory);
+
+        for name in ["A", "Self", "I"] {
+            assert!(matches!(loader.load_class(name), Err(JvmError::ClassCircularity { name: found }) if found == name));
+        }
+        // Nothing is left on the loading stack, so loading again fails the same way
+        assert!(matches!(loader.load_class("B"), Err(JvmError::ClassCircularity { name }) if name == "B"));
+        assert!(matches!(loader.load_class("A"), Err(JvmError::ClassCircularity { .. })));
+        assert_eq!(loader.load_class("C").unwrap().name, "C");
+    }
+
+    #[test]
+    fn class_files_have_to_hold_the_class_they_are_named_after() {
+        let directory = TempDir::new("wrong-name");
+        directory.write("Foo.class", &class("Bar", "java/lang/Object"));
+        directory.write("p/Baz.class", &class("Baz", "java/lang/Object"));
+        let loader = loader(&directory);
+
+        assert_eq!(invalid_reason(loader.load_class("Foo")), "wrong name Bar");
+        assert_eq!(invalid_reason(loader.load_class("p/Baz")), "wrong name Baz");
+        assert!(matches!(loader.load_class("Bar"), Err(JvmError::ClassNotFound { name }) if name == "Bar"));
+    }
+
+    #[test]
+    fn superclasses_have_to_be_extendable_classes() {
+        let directory = TempDir::new("supertypes");
+        directory.write("Final.class", &class_bytes("Final", FINAL_CLASS, "java/lang/Object", &[]));
+        directory.write("Interface.class", &class_bytes("Interface", INTERFACE, "java/lang/Object", &[]));
+        directory.write("Plain.class", &class("Plain", "java/lang/Object"));
+        directory.write("ExtendsFinal.class", &class("ExtendsFinal", "Final"));
+        directory.write("ExtendsInterface.class", &class("ExtendsInterface", "Interface"));
+        directory.write("ImplementsClass.class", &class_bytes("ImplementsClass", PUBLIC_CLASS, "java/lang/Object", &["Plain"]));
+        directory.write("Fine.class", &class_bytes("Fine", PUBLIC_CLASS, "Plain", &["Interface"]));
+        let loader = loader(&directory);
+
+        assert_eq!(invalid_reason(loader.load_class("ExtendsFinal")), "cannot inherit from final class Final");
+        assert_eq!(invalid_reason(loader.load_class("ExtendsInterface")), "superclass Interface is an interface");
+        assert_eq!(invalid_reason(loader.load_class("ImplementsClass")), "Plain is not an interface");
+        let fine = loader.load_class("Fine").unwrap();
+        assert_eq!(fine.super_class.as_ref().unwrap().name, "Plain");
+        assert_eq!(fine.interfaces[0].name, "Interface");
+    }
+
+    #[test]
+    fn class_path_entries_are_searched_in_order() {
+        // The same classes in a directory, a JAR and another directory, told apart by ACC_FINAL
+        let directory = TempDir::new("class-path");
+        directory.write("first/InFirstAndJar.class", &class_bytes("InFirstAndJar", FINAL_CLASS, "java/lang/Object", &[]));
+        directory.write("library.jar", &archive(&[
+            ("InFirstAndJar.class", &class("InFirstAndJar", "java/lang/Object")),
+            ("p/InJarAndLast.class", &class_bytes("p/InJarAndLast", FINAL_CLASS, "java/lang/Object", &[]))
+        ]));
+        directory.write("last/p/InJarAndLast.class", &class("p/InJarAndLast", "java/lang/Object"));
+        directory.write("last/OnlyInLast.class", &class("OnlyInLast", "java/lang/Object"));
+
+        let class_path = std::env::join_paths([directory.path("first"), directory.path("library.jar"), directory.path("last")]).unwrap();
+        let loader = ClassLoader::new(ClassLoader::parse_class_path(class_path.to_str().unwrap()));
+        assert!(loader.load_class("InFirstAndJar").unwrap().class_file.has_flag(ClassAccessFlags::FINAL));
+        assert!(loader.load_class("p/InJarAndLast").unwrap().class_file.has_flag(ClassAccessFlags::FINAL));
+        assert_eq!(loader.load_class("OnlyInLast").unwrap().name, "OnlyInLast");
+        assert!(matches!(loader.load_class("Missing"), Err(JvmError::ClassNotFound { .. })));
+    }
+}
@@ -1,200 +1,1801 @@
-use std::collections::VecDeque;
-use std::io;
-use std::io::{Cursor, Read};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::io::Cursor;
+use crate::attributes::{read_attributes, Attribute};
+use crate::bytecode::{decode, index_of_pc, Instruction, Op};
+use crate::class::{Class, InitializationState, ResolvedField, ResolvedMethod, ResolvedStaticField};
 use crate::class_file::*;
-use crate::utils::{read_attributes, ReadFromCursor};
+use crate::class_loader::{is_platform_class, ClassLoader};
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::error::{JvmError, JvmResult, Location};
+use crate::frame::Frame;
+use crate::heap::{new_object, Object};
+use crate::utils::ReadFromCursor;
+use crate::value::{double_to_string, float_to_string, Array, Reference, Throwable, Value};
 
-pub const OP_CODE_GET_STATIC: u8 = 0xB2;
-pub const OP_CODE_LDC: u8 = 0x12;
-pub const OP_CODE_INVOKE_VIRTUAL: u8 = 0xB6;
-pub const OP_CODE_BI_PUSH: u8 = 0x10;
-pub const OP_CODE_SI_PUSH: u8 = 0x11;
-pub const OP_CODE_RETURN: u8 = 0xB1;
+pub struct ExceptionTableEntry {
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    // 0 catches everything, used for finally blocks
+    pub catch_type: u16
+}
+
+impl ExceptionTableEntry {
+    /// Whether the entry covers an instruction at pc, end_pc is exclusive
+    pub fn covers(&self, pc: u32) -> bool {
+        self.start_pc as u32 <= pc && pc < self.end_pc as u32
+    }
+}
 
 pub struct CodeAttribute {
     pub max_stack: u16,
     pub max_locals: u16,
     pub code: Vec<u8>,
-    pub exception_table: Vec<u8>, // NOT PARSED
-    pub attributes: Vec<AttributeInfo>
+    // The code decoded once when the class file is read. A method whose code does not decode
+    // keeps the opcode and pc of the bad instruction, and only fails when it is run
+    instructions: Result<Vec<Instruction>, (u8, u32)>,
+    pub exception_table: Vec<ExceptionTableEntry>,
+    pub attributes: Vec<Attribute>
 }
 
 impl CodeAttribute {
-    pub fn new(attribute: &AttributeInfo) -> io::Result<Box<CodeAttribute>> {
-        let mut cursor: Cursor<Vec<u8>> = Cursor::new(attribute.info.clone());
+    pub fn new(cursor: &mut Cursor<Vec<u8>>, constants_pool: &[Constant], major: u16) -> JvmResult<CodeAttribute> {
         let max_stack = cursor.read_u16()?;
         let max_locals = cursor.read_u16()?;
         let code_length = cursor.read_u32()?;
         let code: Vec<u8> = cursor.read_bytes(code_length as usize)?;
+        let instructions = decode(&code).map_err(|error| match error {
+            JvmError::InvalidInstruction { opcode, pc } => (opcode, pc),
+            error => unreachable!("decode only reports invalid instructions, not {}", error)
+        });
         let ex_table_length = cursor.read_u16()?;
-        let exception_table = cursor.read_bytes(ex_table_length as usize)?;
+        let mut exception_table: Vec<ExceptionTableEntry> = Vec::with_capacity(ex_table_length as usize);
+        for _ in 0..ex_table_length {
+            exception_table.push(ExceptionTableEntry {
+                start_pc: cursor.read_u16()?,
+                end_pc: cursor.read_u16()?,
+                handler_pc: cursor.read_u16()?,
+                catch_type: cursor.read_u16()?
+            });
+        }
         let att_count = cursor.read_u16()?;
-        let attributes: Vec<AttributeInfo> = read_attributes(cursor.by_ref(), att_count as usize)?;
+        let attributes: Vec<Attribute> = read_attributes(cursor, constants_pool, att_count as usize, major)?;
 
-        return Ok(Box::new(CodeAttribute {
+        Ok(CodeAttribute {
             max_stack,
             max_locals,
             code,
+            instructions,
             exception_table,
             attributes
-        }))
+        })
+    }
+
+    /// The handler for an exception thrown at pc, the first entry of the exception table that
+    /// covers pc and catches everything or a class catches accepts (JVMS 2.10)
+    pub fn find_handler(&self, pc: u32, mut catches: impl FnMut(u16) -> JvmResult<bool>) -> JvmResult<Option<&ExceptionTableEntry>> {
+        for entry in self.exception_table.iter().filter(|entry| entry.covers(pc)) {
+            if entry.catch_type == 0 || catches(entry.catch_type)? {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn instructions(&self) -> JvmResult<&[Instruction]> {
+        match &self.instructions {
+            Ok(instructions) => Ok(instructions),
+            &Err((opcode, pc)) => Err(JvmError::InvalidInstruction { opcode, pc })
+        }
+    }
+}
+
+fn is_int(value: &Value) -> bool {
+    matches!(value, Value::Int(_))
+}
+
+fn is_long(value: &Value) -> bool {
+    matches!(value, Value::Long(_))
+}
+
+fn is_float(value: &Value) -> bool {
+    matches!(value, Value::Float(_))
+}
+
+fn is_double(value: &Value) -> bool {
+    matches!(value, Value::Double(_))
+}
+
+fn is_reference(value: &Value) -> bool {
+    matches!(value, Value::Reference(_))
+}
+
+fn get_constant(class_file: &ClassFile, index: u16, pc: u32) -> JvmResult<&Constant> {
+    class_file.get_constant(index).ok_or(JvmError::InvalidConstantIndex { index, location: Location::Pc(pc) })
+}
+
+fn get_utf8(class_file: &ClassFile, index: u16, pc: u32) -> JvmResult<&str> {
+    match get_constant(class_file, index, pc)? {
+        Constant::Utf8(utf8) => Ok(utf8.data.as_str()),
+        _ => Err(JvmError::UnexpectedConstant { index, expected: "Utf8", location: Location::Pc(pc) })
     }
 }
 
-enum Instruction {
-    Type(String),
-    String(String),
-    SByte(i8),
-    Short(i16),
-    Integer(i32),
-    Float(f32)
+fn get_name_of_class(class_file: &ClassFile, class_index: u16, pc: u32) -> JvmResult<&str> {
+    match get_constant(class_file, class_index, pc)? {
+        Constant::Class(class) => get_utf8(class_file, class.name_index, pc),
+        _ => Err(JvmError::UnexpectedConstant { index: class_index, expected: "Class", location: Location::Pc(pc) })
+    }
 }
 
-fn get_name_of_class(class_file: &ClassFile, class_index: u16) -> &str {
-    if let Some(Constant::Class(class)) = class_file.constants_pool.get((class_index - 1) as usize) {
-         if let Some(Constant::Utf8(class_name)) = class_file.constants_pool.get((class.name_index - 1) as usize) {
-             return class_name.data.as_str()
-         }
+fn get_name_of_member(class_file: &ClassFile, name_and_type_index: u16, pc: u32) -> JvmResult<&str> {
+    match get_constant(class_file, name_and_type_index, pc)? {
+        Constant::NameAndType(name_and_type) => get_utf8(class_file, name_and_type.name_index, pc),
+        _ => Err(JvmError::UnexpectedConstant { index: name_and_type_index, expected: "NameAndType", location: Location::Pc(pc) })
     }
+}
 
-    ""
+fn get_descriptor_of_member(class_file: &ClassFile, name_and_type_index: u16, pc: u32) -> JvmResult<&str> {
+    match get_constant(class_file, name_and_type_index, pc)? {
+        Constant::NameAndType(name_and_type) => get_utf8(class_file, name_and_type.descriptor_index, pc),
+        _ => Err(JvmError::UnexpectedConstant { index: name_and_type_index, expected: "NameAndType", location: Location::Pc(pc) })
+    }
 }
 
-fn get_name_of_member(class_file: &ClassFile, name_and_type_index: u16) -> &str {
-    if let Some(Constant::NameAndType(name_and_type)) = class_file.constants_pool.get((name_and_type_index - 1) as usize) {
-        if let Some(Constant::Utf8(member_name)) = class_file.constants_pool.get((name_and_type.name_index - 1) as usize) {
-            return member_name.data.as_str()
+/// Resolves a FieldRef to a static field, JVMS 5.4.3.2. The field is looked up in the class,
+/// then its superinterfaces, then its superclass
+fn resolve_static_field(loader: &ClassLoader, class: &Rc<Class>, index: u16, pc: u32) -> JvmResult<ResolvedStaticField> {
+    if let Some(resolved) = class.resolved_static_fields.borrow().get(&index) {
+        return Ok(resolved.clone());
+    }
+
+    let class_file = &class.class_file;
+    let field_ref = match get_constant(class_file, index, pc)? {
+        Constant::FieldRef(field_ref) => field_ref,
+        _ => return Err(JvmError::UnexpectedConstant { index, expected: "FieldRef", location: Location::Pc(pc) })
+    };
+    let class_name = get_name_of_class(class_file, field_ref.class_index, pc)?;
+    let name = get_name_of_member(class_file, field_ref.name_and_type_index, pc)?;
+    let field_type = FieldType::parse(get_descriptor_of_member(class_file, field_ref.name_and_type_index, pc)?)?;
+
+    // The first class declaring a field with the name, static or not
+    fn find_declaring_class(class: &Rc<Class>, name: &str) -> Option<Rc<Class>> {
+        if class.class_file.find_field_by_name(name).is_some() {
+            return Some(class.clone());
+        }
+        if let Some(found) = class.interfaces.iter().find_map(|interface| find_declaring_class(interface, name)) {
+            return Some(found);
         }
+        class.super_class.as_ref().and_then(|super_class| find_declaring_class(super_class, name))
     }
 
-    ""
+    let target = loader.resolve_class(class, field_ref.class_index, pc)?;
+    let declaring_class = find_declaring_class(&target, name).ok_or_else(|| {
+        uncaught("java/lang/NoSuchFieldError", format!("{}.{}", class_name, name), pc)
+    })?;
+    // An instance field with that name is a different error than no field at all
+    if !declaring_class.declares_static_field(name) {
+        return Err(uncaught("java/lang/IncompatibleClassChangeError", format!("Expected static field {}.{}", class_name, name), pc));
+    }
+
+    let resolved = ResolvedStaticField { class: declaring_class, name: name.to_string(), field_type };
+    class.resolved_static_fields.borrow_mut().insert(index, resolved.clone());
+    Ok(resolved)
 }
 
-fn get_static(class_file: &ClassFile, cursor: &mut Cursor<Vec<u8>>, stack: &mut VecDeque<Instruction>) -> io::Result<()> {
-    let index: u16 = cursor.read_u16()?;
-    let field_ref: &ConstantFieldRef;
-    match class_file.constants_pool.get((index - 1) as usize) {
-        Some(Constant::FieldRef(fr)) => field_ref = fr,
-        _ => panic!("GetStatic - Expected FieldRef!!")
-    }
+fn get_static(loader: &ClassLoader, class: &Rc<Class>, index: u16, frame: &mut Frame, depth: usize) -> JvmResult<()> {
+    let pc = frame.pc;
+    let class_file = &class.class_file;
+    let field_ref = match get_constant(class_file, index, pc)? {
+        Constant::FieldRef(field_ref) => field_ref,
+        _ => return Err(JvmError::UnexpectedConstant { index, expected: "FieldRef", location: Location::Pc(pc) })
+    };
+    let class_name = get_name_of_class(class_file, field_ref.class_index, pc)?;
+    let member_name = get_name_of_member(class_file, field_ref.name_and_type_index, pc)?;
 
-    let class_name = get_name_of_class(class_file, field_ref.class_index);
-    let member_name = get_name_of_member(class_file, field_ref.name_and_type_index);
+    // System.out is the only static field of the platform classes
+    if is_platform_class(class_name) {
+        if class_name != "java/lang/System" || member_name != "out" {
+            return Err(JvmError::Runtime {
+                message: format!("GetStatic - Unsupported class member {}.{}", class_name, member_name),
+                pc
+            });
+        }
+        return frame.push(Value::Reference(Reference::PrintStream));
+    }
 
-    if class_name.is_empty() || member_name.is_empty() {
-        panic!("GetStatic - ClassName or MemberName not found!!")
+    let field = resolve_static_field(loader, class, index, pc)?;
+    initialize_class(loader, &field.class, pc, depth)?;
+    let value = field.class.static_values.borrow().get(&field.name).cloned();
+    match value {
+        Some(value) => frame.push(value),
+        None => Err(JvmError::Runtime { message: format!("GetStatic - Missing static field {}", field.name), pc })
     }
+}
 
-    // Only supports this type for now
-    if class_name != "java/lang/System" && member_name != "out" {
-        panic!("GetStatic - Unsupported class member {}.{}", class_name, member_name);
+fn put_static(loader: &ClassLoader, class: &Rc<Class>, index: u16, frame: &mut Frame, depth: usize) -> JvmResult<()> {
+    let pc = frame.pc;
+    let field_ref = match get_constant(&class.class_file, index, pc)? {
+        Constant::FieldRef(field_ref) => field_ref,
+        _ => return Err(JvmError::UnexpectedConstant { index, expected: "FieldRef", location: Location::Pc(pc) })
+    };
+    let class_name = get_name_of_class(&class.class_file, field_ref.class_index, pc)?;
+    if is_platform_class(class_name) {
+        let member_name = get_name_of_member(&class.class_file, field_ref.name_and_type_index, pc)?;
+        return Err(JvmError::Runtime {
+            message: format!("PutStatic - Unsupported class member {}.{}", class_name, member_name),
+            pc
+        });
     }
 
-    stack.push_back(Instruction::Type(String::from("FakePrintStream")));
-    return Ok(())
+    let field = resolve_static_field(loader, class, index, pc)?;
+    initialize_class(loader, &field.class, pc, depth)?;
+    let value = frame.pop()?;
+    if !value.matches(&field.field_type) {
+        return Err(JvmError::Runtime {
+            message: format!("PutStatic - Expected {} but found {}", field.field_type.java_name(), value.type_name()),
+            pc
+        });
+    }
+    field.class.static_values.borrow_mut().insert(field.name.clone(), value.narrow_to(&field.field_type));
+    Ok(())
 }
 
-fn ldc(class_file: &ClassFile, cursor: &mut Cursor<Vec<u8>>, stack: &mut VecDeque<Instruction>) -> io::Result<()> {
-    let index: u8 = cursor.read_u8()?;
-    let constant = class_file.constants_pool.get((index - 1) as usize);
+/// Initializes the class if that wasn't done yet, JVMS 5.5. Static fields with a ConstantValue
+/// are set first, then the superclass and the superinterfaces with default methods are
+/// initialized, then <clinit> runs
+fn initialize_class(loader: &ClassLoader, class: &Rc<Class>, pc: u32, depth: usize) -> JvmResult<()> {
+    match class.initialization.get() {
+        // A class being initialized is only reached again through its own <clinit>
+        InitializationState::Initialized | InitializationState::InProgress => return Ok(()),
+        InitializationState::Erroneous =>
+            return Err(uncaught("java/lang/NoClassDefFoundError", format!("Could not initialize class {}", class.name.replace('/', ".")), pc)),
+        InitializationState::Uninitialized => {}
+    }
+    class.initialization.set(InitializationState::InProgress);
+
+    let mut result = class.apply_constant_values();
+    // Interfaces don't initialize their superinterfaces
+    if result.is_ok() && !class.is_interface() {
+        if let Some(super_class) = &class.super_class {
+            result = initialize_class(loader, super_class, pc, depth);
+        }
+        for interface in class.interfaces.iter().filter(|interface| interface.declares_default_methods()) {
+            if result.is_err() {
+                break;
+            }
+            result = initialize_class(loader, interface, pc, depth);
+        }
+    }
 
-    match constant {
-        Some(Constant::String(string_constant)) => {
-            if let Some(Constant::Utf8(string)) = class_file.constants_pool.get((string_constant.string_index - 1) as usize) {
-                stack.push_back(Instruction::String(string.data.clone()));
-                return Ok(())
+    if result.is_ok() {
+        if let Some(clinit) = class.class_file.find_method("<clinit>", "()V") {
+            let code = clinit.code().ok_or(JvmError::Runtime { message: format!("{}.<clinit> has no Code attribute", class.name), pc })?;
+            if depth + 1 >= MAX_CALL_DEPTH {
+                result = Err(JvmError::UncaughtException { class_name: String::from("java/lang/StackOverflowError"), message: None, pc });
+            } else {
+                let descriptor = MethodDescriptor { parameters: Vec::new(), return_type: None };
+                result = execute_code(loader, class, code, &descriptor, Vec::new(), depth + 1).map(|_| ()).map_err(|err| match err {
+                    // Exceptions that are not Errors get wrapped
+                    JvmError::UncaughtException { ref class_name, .. } if !is_error(loader, class_name) =>
+                        JvmError::ExceptionInInitializer { class_name: class.name.clone(), cause: Box::new(err) },
+                    err => err
+                });
             }
         }
-        Some(Constant::Integer(int_constant)) => {
-            stack.push_back(Instruction::Integer(int_constant.value as i32));
+    }
+
+    match result {
+        Ok(()) => class.initialization.set(InitializationState::Initialized),
+        Err(_) => class.initialization.set(InitializationState::Erroneous)
+    }
+    result
+}
+
+fn ldc(class_file: &ClassFile, index: u16, frame: &mut Frame) -> JvmResult<()> {
+    let pc = frame.pc;
+    match get_constant(class_file, index, pc)? {
+        Constant::String(string_constant) => {
+            let string = get_utf8(class_file, string_constant.string_index, pc)?;
+            frame.push(Value::Reference(Reference::String(Rc::from(string))))?;
+        }
+        Constant::Integer(int_constant) => {
+            frame.push(Value::Int(int_constant.value as i32))?;
+        }
+        Constant::Float(float_constant) => {
+            frame.push(Value::Float(float_constant.value))?;
+        }
+        _ => return Err(JvmError::UnexpectedConstant { index, expected: "String, Integer or Float", location: Location::Pc(pc) })
+    }
+
+    Ok(())
+}
+
+fn ldc2_w(class_file: &ClassFile, index: u16, frame: &mut Frame) -> JvmResult<()> {
+    let pc = frame.pc;
+    match get_constant(class_file, index, pc)? {
+        Constant::Long(long_constant) => {
+            frame.push(Value::Long(long_constant.value as i64))?;
+        }
+        Constant::Double(double_constant) => {
+            frame.push(Value::Double(double_constant.value))?;
+        }
+        _ => return Err(JvmError::UnexpectedConstant { index, expected: "Long or Double", location: Location::Pc(pc) })
+    }
+
+    Ok(())
+}
+
+/// Object.hashCode, the address stands in for the identity hash code. Strings hash their
+/// UTF-16 code units like String.hashCode
+fn hash_code(reference: &Reference) -> i32 {
+    match reference {
+        Reference::Null | Reference::PrintStream => 0,
+        Reference::String(string) => string.encode_utf16().fold(0i32, |hash, unit| hash.wrapping_mul(31).wrapping_add(unit as i32)),
+        Reference::Array(array) => Rc::as_ptr(array) as usize as i32,
+        Reference::Object(object) => Rc::as_ptr(object) as usize as i32,
+        Reference::Throwable(throwable) => Rc::as_ptr(throwable) as usize as i32
+    }
+}
+
+/// Object.toString without calling an override, which is the class name and the hash code
+fn default_to_string(reference: &Reference) -> String {
+    match reference {
+        Reference::Null => String::from("null"),
+        Reference::String(string) => string.to_string(),
+        Reference::Array(array) => format!("{}@{:x}", array.borrow().class_name(), hash_code(reference)),
+        Reference::Object(object) => format!("{}@{:x}", object.borrow().class.name.replace('/', "."), hash_code(reference)),
+        Reference::PrintStream => format!("java.io.PrintStream@{:x}", hash_code(reference)),
+        // Throwable.toString, the class name and the message if there is one
+        Reference::Throwable(throwable) => match &throwable.message {
+            Some(message) => format!("{}: {}", throwable.class_name.replace('/', "."), message),
+            None => throwable.class_name.replace('/', ".")
+        }
+    }
+}
+
+const TO_STRING: (&str, &str) = ("toString", "()Ljava/lang/String;");
+
+/// String.valueOf, calls toString on objects whose class overrides it
+fn to_string(loader: &ClassLoader, reference: Reference, pc: u32, depth: usize) -> JvmResult<String> {
+    let receiver_class = match &reference {
+        Reference::Object(object) => object.borrow().class.clone(),
+        _ => return Ok(default_to_string(&reference))
+    };
+    let (declaring_class, method_index) = match receiver_class.vtable_index(TO_STRING.0, TO_STRING.1).and_then(|index| receiver_class.vtable[index].method()) {
+        Some(selected) => selected,
+        None => return Ok(default_to_string(&reference))
+    };
+    let method = &declaring_class.class_file.methods[method_index];
+    let descriptor = MethodDescriptor::parse(TO_STRING.1)?;
+    match call_method(loader, &declaring_class, method, &descriptor, vec![Value::Reference(reference)], pc, depth)? {
+        Some(Value::Reference(Reference::String(string))) => Ok(string.to_string()),
+        Some(Value::Reference(Reference::Null)) => Ok(String::from("null")),
+        _ => Err(JvmError::Runtime { message: String::from("toString did not return a String"), pc })
+    }
+}
+
+fn format_println_argument(parameter: &FieldType, value: Value, pc: u32) -> JvmResult<String> {
+    match (parameter, value) {
+        (FieldType::Boolean, Value::Int(value)) => Ok((value != 0).to_string()),
+        (FieldType::Char, Value::Int(value)) => Ok(char::decode_utf16([value as u16]).map(|c| c.unwrap_or('?')).collect()),
+        (FieldType::Int, Value::Int(value)) => Ok(value.to_string()),
+        (FieldType::Long, Value::Long(value)) => Ok(value.to_string()),
+        (FieldType::Float, Value::Float(value)) => Ok(float_to_string(value)),
+        (FieldType::Double, Value::Double(value)) => Ok(double_to_string(value)),
+        (FieldType::Array(component), Value::Reference(Reference::Array(array))) if **component == FieldType::Char => {
+            let units: Vec<u16> = array.borrow().elements.iter().map(|unit| match unit {
+                Value::Int(unit) => *unit as u16,
+                _ => 0
+            }).collect();
+            Ok(String::from_utf16_lossy(&units))
         }
-        Some(Constant::Float(float_constant)) => {
-            stack.push_back(Instruction::Float(float_constant.value));
+        (FieldType::Object(_), Value::Reference(reference)) if !matches!(reference, Reference::Object(_)) => Ok(default_to_string(&reference)),
+        (parameter, value) => Err(JvmError::Runtime {
+            message: format!("InvokeVirtual - Cannot print {} as {}", value.type_name(), parameter.java_name()),
+            pc
+        })
+    }
+}
+
+fn println(loader: &ClassLoader, descriptor: &MethodDescriptor, frame: &mut Frame, depth: usize) -> JvmResult<()> {
+    let pc = frame.pc;
+    // Arguments are on top of the receiver, last argument on top
+    let value = if descriptor.parameters.is_empty() { None } else { Some(frame.pop()?) };
+    let argument = match (descriptor.parameters.first(), value) {
+        (Some(FieldType::Object(_)), Some(Value::Reference(reference @ Reference::Object(_)))) => Some(to_string(loader, reference, pc, depth)?),
+        (Some(parameter), Some(value)) => Some(format_println_argument(parameter, value, pc)?),
+        _ => None
+    };
+
+    match frame.pop_reference()? {
+        Reference::PrintStream => {}
+        Reference::Null => return Err(null_pointer(pc)),
+        _ => return Err(JvmError::Runtime { message: String::from("InvokeVirtual - Receiver is not a PrintStream"), pc })
+    }
+
+    match argument {
+        Some(argument) => println!("{}", argument),
+        None => println!()
+    }
+    Ok(())
+}
+
+/// The methods of java/lang/Object, which every class inherits
+fn is_object_method(name: &str, descriptor: &str) -> bool {
+    matches!((name, descriptor), ("toString", "()Ljava/lang/String;") | ("hashCode", "()I") | ("equals", "(Ljava/lang/Object;)Z"))
+}
+
+/// Resolves a method declared by or inherited into a loaded class or interface. Private methods
+/// are called directly, the others are selected through the vtable, or the itable when the
+/// reference is to an interface
+fn resolve_method(target: &Rc<Class>, class_name: &str, name: &str, descriptor: &str, pc: u32) -> JvmResult<ResolvedMethod> {
+    if let Some((declaring_class, method)) = target.lookup_method(name, descriptor) {
+        if method.has_flag(MethodsAccessFlags::STATIC) {
+            return Err(uncaught("java/lang/IncompatibleClassChangeError", format!("Expected non-static method {}.{}", class_name, name), pc));
         }
-        _ => {
-            panic!("LDC - Invalid constant type!!")
+        if method.has_flag(MethodsAccessFlags::PRIVATE) {
+            let method_index = declaring_class.class_file.methods.iter().position(|candidate| std::ptr::eq(candidate, method)).unwrap_or_default();
+            return Ok(ResolvedMethod::Direct { class: declaring_class, method_index });
         }
     }
 
-    unreachable!("LDC - Invalid constant type!!")
+    match target.vtable_index(name, descriptor) {
+        Some(slot) if target.is_interface() => Ok(ResolvedMethod::Interface { interface: target.clone(), slot }),
+        Some(index) => Ok(ResolvedMethod::Virtual { index }),
+        None if is_object_method(name, descriptor) => Ok(ResolvedMethod::ByName { name: name.to_string(), descriptor: descriptor.to_string() }),
+        None => Err(uncaught("java/lang/NoSuchMethodError", format!("{}.{}{}", class_name, name, descriptor), pc))
+    }
+}
+
+/// Resolves a MethodRef for invokevirtual, JVMS 5.4.3.3, or an InterfaceMethodRef for
+/// invokeinterface, JVMS 5.4.3.4. Methods of platform classes are selected by name
+fn resolve_invoked_method(loader: &ClassLoader, class: &Rc<Class>, index: u16, interface: bool, pc: u32) -> JvmResult<ResolvedMethod> {
+    if let Some(resolved) = class.resolved_methods.borrow().get(&index) {
+        return Ok(resolved.clone());
+    }
+
+    let (class_index, name, descriptor) = get_method_ref(&class.class_file, index, pc)?;
+    let class_name = get_name_of_class(&class.class_file, class_index, pc)?;
+    let resolved = if is_platform_class(class_name) {
+        ResolvedMethod::ByName { name: name.to_string(), descriptor: descriptor.to_string() }
+    } else {
+        let target = loader.resolve_class(class, class_index, pc)?;
+        if target.is_interface() != interface {
+            let (found, expected) = if interface { ("class", "interface") } else { ("interface", "class") };
+            return Err(uncaught("java/lang/IncompatibleClassChangeError", format!("Found {} {}, but {} was expected", found, class_name.replace('/', "."), expected), pc));
+        }
+        resolve_method(&target, class_name, name, descriptor, pc)?
+    };
+
+    class.resolved_methods.borrow_mut().insert(index, resolved.clone());
+    Ok(resolved)
+}
+
+/// Selects the method to run on an object of the receiver class, JVMS 5.4.6. None means the
+/// class doesn't override the method of java/lang/Object
+fn select_method(resolved: &ResolvedMethod, receiver_class: &Rc<Class>, pc: u32) -> JvmResult<Option<(Rc<Class>, usize)>> {
+    let entry = match resolved {
+        ResolvedMethod::Direct { class, method_index } => return Ok(Some((class.clone(), *method_index))),
+        ResolvedMethod::Virtual { index } => receiver_class.vtable.get(*index),
+        ResolvedMethod::Interface { interface, slot } => {
+            let itable = receiver_class.itables.get(&interface.name).ok_or_else(|| uncaught(
+                "java/lang/IncompatibleClassChangeError",
+                format!("Class {} does not implement the requested interface {}", receiver_class.name.replace('/', "."), interface.name.replace('/', ".")),
+                pc
+            ))?;
+            match itable.get(*slot).copied().flatten() {
+                Some(index) => receiver_class.vtable.get(index),
+                None => {
+                    let name = interface.vtable.get(*slot).map(|entry| entry.name.as_str()).unwrap_or_default();
+                    return Err(uncaught("java/lang/AbstractMethodError", format!("{}.{}", receiver_class.name, name), pc));
+                }
+            }
+        }
+        ResolvedMethod::ByName { name, descriptor } => match receiver_class.vtable_index(name, descriptor) {
+            Some(index) => receiver_class.vtable.get(index),
+            None if is_object_method(name, descriptor) => return Ok(None),
+            None => return Err(uncaught("java/lang/AbstractMethodError", format!("{}.{}{}", receiver_class.name, name, descriptor), pc))
+        }
+    };
+    match entry.and_then(|entry| entry.method()) {
+        Some(selected) => Ok(Some(selected)),
+        None => Err(JvmError::Runtime { message: format!("Invoke - Broken vtable of {}", receiver_class.name), pc })
+    }
 }
 
-fn invoke_virtual(class_file: &ClassFile, cursor: &mut Cursor<Vec<u8>>, stack: &mut VecDeque<Instruction>) -> io::Result<()> {
-    let index: u16 = cursor.read_u16()?;
-    let method_ref: &ConstantMethodRef;
-    match class_file.constants_pool.get((index - 1) as usize) {
-        Some(Constant::MethodRef(mr)) => method_ref = mr,
-        _ => panic!("InvokeVirtual - Expected MethodRef!!")
+/// Runs a method of java/lang/Object on a receiver whose class doesn't override it
+fn invoke_object_method(name: &str, receiver: Reference, arguments: Vec<Value>, frame: &mut Frame) -> JvmResult<()> {
+    match (name, arguments.first()) {
+        ("toString", _) => frame.push(Value::Reference(Reference::String(Rc::from(default_to_string(&receiver))))),
+        ("hashCode", _) => frame.push(Value::Int(hash_code(&receiver))),
+        // Strings are the only platform objects compared by value
+        ("equals", Some(Value::Reference(other))) => {
+            let equal = match (&receiver, other) {
+                (Reference::String(left), Reference::String(right)) => left == right,
+                _ => receiver.is_same(other)
+            };
+            frame.push(Value::Int(equal as i32))
+        }
+        _ => Err(JvmError::Runtime { message: format!("Invoke - Unsupported method java.lang.Object.{}", name), pc: frame.pc })
     }
+}
 
-    let class_name = get_name_of_class(class_file, method_ref.class_index);
-    let member_name = get_name_of_member(class_file, method_ref.name_and_type_index);
+/// Pops the arguments and the receiver and runs the method selected for the receiver
+fn invoke_resolved(loader: &ClassLoader, resolved: &ResolvedMethod, descriptor: &MethodDescriptor, frame: &mut Frame, depth: usize) -> JvmResult<()> {
+    let pc = frame.pc;
+    let mut arguments = pop_arguments(frame, descriptor)?;
+    let receiver = frame.pop_reference()?;
+    let selected = match &receiver {
+        Reference::Null => return Err(null_pointer(pc)),
+        Reference::Object(object) => {
+            let receiver_class = object.borrow().class.clone();
+            select_method(resolved, &receiver_class, pc)?
+        }
+        // Strings, arrays and print streams only have the methods of their platform class
+        _ => match resolved {
+            ResolvedMethod::ByName { .. } => None,
+            _ => return Err(JvmError::UnexpectedValue { expected: "object", found: "reference", pc })
+        }
+    };
 
-    if class_name.is_empty() || member_name.is_empty() {
-        panic!("InvokeVirtual - ClassName or MemberName not found!!")
+    match (selected, resolved) {
+        (Some((declaring_class, method_index)), _) => {
+            arguments.insert(0, Value::Reference(receiver));
+            run_method(loader, &declaring_class, &declaring_class.class_file.methods[method_index], descriptor, arguments, frame, depth)
+        }
+        (None, ResolvedMethod::ByName { name, descriptor }) if is_object_method(name, descriptor) =>
+            invoke_object_method(name, receiver, arguments, frame),
+        (None, ResolvedMethod::ByName { name, .. }) =>
+            Err(JvmError::Runtime { message: format!("Invoke - Unsupported method {}", name), pc }),
+        (None, _) => Err(JvmError::Runtime { message: String::from("Invoke - No method selected"), pc })
     }
+}
+
+fn invoke_virtual(loader: &ClassLoader, class: &Rc<Class>, index: u16, frame: &mut Frame, depth: usize) -> JvmResult<()> {
+    let pc = frame.pc;
+    let (class_index, member_name, _) = get_method_ref(&class.class_file, index, pc)?;
+    let class_name = get_name_of_class(&class.class_file, class_index, pc)?;
+    let descriptor = method_descriptor(class, index, pc)?;
 
     if class_name == "java/io/PrintStream" && member_name == "println" {
-        if stack.len() < 2 {
-            panic!("InvokeVirtual - {}.{} expects two arguments", class_name, member_name);
+        return println(loader, &descriptor, frame, depth);
+    }
+
+    let resolved = resolve_invoked_method(loader, class, index, false, pc)?;
+    invoke_resolved(loader, &resolved, &descriptor, frame, depth)
+}
+
+fn invoke_interface(loader: &ClassLoader, class: &Rc<Class>, index: u16, frame: &mut Frame, depth: usize) -> JvmResult<()> {
+    let pc = frame.pc;
+    if !matches!(get_constant(&class.class_file, index, pc)?, Constant::InterfaceMethodRef(_)) {
+        return Err(JvmError::UnexpectedConstant { index, expected: "InterfaceMethodRef", location: Location::Pc(pc) });
+    }
+
+    let descriptor = method_descriptor(class, index, pc)?;
+    let resolved = resolve_invoked_method(loader, class, index, true, pc)?;
+    invoke_resolved(loader, &resolved, &descriptor, frame, depth)
+}
+
+fn arithmetic_exception(pc: u32) -> JvmError {
+    JvmError::UncaughtException {
+        class_name: String::from("java/lang/ArithmeticException"),
+        message: Some(String::from("/ by zero")),
+        pc
+    }
+}
+
+fn int_binary(frame: &mut Frame, operation: fn(i32, i32) -> i32) -> JvmResult<()> {
+    let right = frame.pop_int()?;
+    let left = frame.pop_int()?;
+    frame.push(Value::Int(operation(left, right)))
+}
+
+fn long_binary(frame: &mut Frame, operation: fn(i64, i64) -> i64) -> JvmResult<()> {
+    let right = frame.pop_long()?;
+    let left = frame.pop_long()?;
+    frame.push(Value::Long(operation(left, right)))
+}
+
+// wrapping_div and wrapping_rem give MIN_VALUE / -1 == MIN_VALUE and MIN_VALUE % -1 == 0 like Java
+fn int_division(frame: &mut Frame, operation: fn(i32, i32) -> i32) -> JvmResult<()> {
+    let right = frame.pop_int()?;
+    let left = frame.pop_int()?;
+    if right == 0 {
+        return Err(arithmetic_exception(frame.pc));
+    }
+    frame.push(Value::Int(operation(left, right)))
+}
+
+fn long_division(frame: &mut Frame, operation: fn(i64, i64) -> i64) -> JvmResult<()> {
+    let right = frame.pop_long()?;
+    let left = frame.pop_long()?;
+    if right == 0 {
+        return Err(arithmetic_exception(frame.pc));
+    }
+    frame.push(Value::Long(operation(left, right)))
+}
+
+// The shift distance of a long shift is an int, wrapping_shl and wrapping_shr mask it to 5 or 6 bits
+fn long_shift(frame: &mut Frame, operation: fn(i64, u32) -> i64) -> JvmResult<()> {
+    let distance = frame.pop_int()?;
+    let value = frame.pop_long()?;
+    frame.push(Value::Long(operation(value, distance as u32)))
+}
+
+fn float_binary(frame: &mut Frame, operation: fn(f32, f32) -> f32) -> JvmResult<()> {
+    let right = frame.pop_float()?;
+    let left = frame.pop_float()?;
+    frame.push(Value::Float(operation(left, right)))
+}
+
+fn double_binary(frame: &mut Frame, operation: fn(f64, f64) -> f64) -> JvmResult<()> {
+    let right = frame.pop_double()?;
+    let left = frame.pop_double()?;
+    frame.push(Value::Double(operation(left, right)))
+}
+
+// The l and g variants only differ in the result pushed when either value is NaN
+fn floating_compare(left: f64, right: f64, nan_result: i32) -> Value {
+    match left.partial_cmp(&right) {
+        Some(std::cmp::Ordering::Less) => Value::Int(-1),
+        Some(std::cmp::Ordering::Equal) => Value::Int(0),
+        Some(std::cmp::Ordering::Greater) => Value::Int(1),
+        None => Value::Int(nan_result)
+    }
+}
+
+fn float_compare(frame: &mut Frame, nan_result: i32) -> JvmResult<()> {
+    let right = frame.pop_float()?;
+    let left = frame.pop_float()?;
+    frame.push(floating_compare(left as f64, right as f64, nan_result))
+}
+
+fn double_compare(frame: &mut Frame, nan_result: i32) -> JvmResult<()> {
+    let right = frame.pop_double()?;
+    let left = frame.pop_double()?;
+    frame.push(floating_compare(left, right, nan_result))
+}
+
+/// Every Throwable in java.lang of JDK 17 with its superclass, taken from the java.base module.
+/// It is how is_error and exception handlers see the hierarchy of the exceptions the
+/// interpreter raises and of the platform classes user exceptions extend. Throwables of
+/// other packages are not listed, they are only caught by their own class and are never
+/// treated as Errors.
+const PLATFORM_THROWABLES: [(&str, &str); 53] = [
+    ("java/lang/Throwable", "java/lang/Object"),
+    ("java/lang/Exception", "java/lang/Throwable"),
+    ("java/lang/Error", "java/lang/Throwable"),
+
+    ("java/lang/CloneNotSupportedException", "java/lang/Exception"),
+    ("java/lang/InterruptedException", "java/lang/Exception"),
+    ("java/lang/ReflectiveOperationException", "java/lang/Exception"),
+    ("java/lang/RuntimeException", "java/lang/Exception"),
+    ("java/lang/ClassNotFoundException", "java/lang/ReflectiveOperationException"),
+    ("java/lang/IllegalAccessException", "java/lang/ReflectiveOperationException"),
+    ("java/lang/InstantiationException", "java/lang/ReflectiveOperationException"),
+    ("java/lang/NoSuchFieldException", "java/lang/ReflectiveOperationException"),
+    ("java/lang/NoSuchMethodException", "java/lang/ReflectiveOperationException"),
+
+    ("java/lang/ArithmeticException", "java/lang/RuntimeException"),
+    ("java/lang/ArrayStoreException", "java/lang/RuntimeException"),
+    ("java/lang/ClassCastException", "java/lang/RuntimeException"),
+    ("java/lang/EnumConstantNotPresentException", "java/lang/RuntimeException"),
+    ("java/lang/IllegalArgumentException", "java/lang/RuntimeException"),
+    ("java/lang/IllegalCallerException", "java/lang/RuntimeException"),
+    ("java/lang/IllegalMonitorStateException", "java/lang/RuntimeException"),
+    ("java/lang/IllegalStateException", "java/lang/RuntimeException"),
+    ("java/lang/IndexOutOfBoundsException", "java/lang/RuntimeException"),
+    ("java/lang/LayerInstantiationException", "java/lang/RuntimeException"),
+    ("java/lang/NegativeArraySizeException", "java/lang/RuntimeException"),
+    ("java/lang/NullPointerException", "java/lang/RuntimeException"),
+    ("java/lang/SecurityException", "java/lang/RuntimeException"),
+    ("java/lang/TypeNotPresentException", "java/lang/RuntimeException"),
+    ("java/lang/UnsupportedOperationException", "java/lang/RuntimeException"),
+    ("java/lang/ArrayIndexOutOfBoundsException", "java/lang/IndexOutOfBoundsException"),
+    ("java/lang/StringIndexOutOfBoundsException", "java/lang/IndexOutOfBoundsException"),
+    ("java/lang/IllegalThreadStateException", "java/lang/IllegalArgumentException"),
+    ("java/lang/NumberFormatException", "java/lang/IllegalArgumentException"),
+
+    ("java/lang/AssertionError", "java/lang/Error"),
+    ("java/lang/LinkageError", "java/lang/Error"),
+    ("java/lang/ThreadDeath", "java/lang/Error"),
+    ("java/lang/VirtualMachineError", "java/lang/Error"),
+    ("java/lang/BootstrapMethodError", "java/lang/LinkageError"),
+    ("java/lang/ClassCircularityError", "java/lang/LinkageError"),
+    ("java/lang/ClassFormatError", "java/lang/LinkageError"),
+    ("java/lang/ExceptionInInitializerError", "java/lang/LinkageError"),
+    ("java/lang/IncompatibleClassChangeError", "java/lang/LinkageError"),
+    ("java/lang/NoClassDefFoundError", "java/lang/LinkageError"),
+    ("java/lang/UnsatisfiedLinkError", "java/lang/LinkageError"),
+    ("java/lang/VerifyError", "java/lang/LinkageError"),
+    ("java/lang/UnsupportedClassVersionError", "java/lang/ClassFormatError"),
+    ("java/lang/AbstractMethodError", "java/lang/IncompatibleClassChangeError"),
+    ("java/lang/IllegalAccessError", "java/lang/IncompatibleClassChangeError"),
+    ("java/lang/InstantiationError", "java/lang/IncompatibleClassChangeError"),
+    ("java/lang/NoSuchFieldError", "java/lang/IncompatibleClassChangeError"),
+    ("java/lang/NoSuchMethodError", "java/lang/IncompatibleClassChangeError"),
+    ("java/lang/InternalError", "java/lang/VirtualMachineError"),
+    ("java/lang/OutOfMemoryError", "java/lang/VirtualMachineError"),
+    ("java/lang/StackOverflowError", "java/lang/VirtualMachineError"),
+    ("java/lang/UnknownError", "java/lang/VirtualMachineError")
+];
+
+fn platform_super_class(class_name: &str) -> Option<&'static str> {
+    PLATFORM_THROWABLES.iter().find(|(name, _)| *name == class_name).map(|&(_, super_class)| super_class)
+}
+
+/// Whether class_name is ancestor or one of its subclasses. User classes are followed through
+/// the loader, platform classes through PLATFORM_THROWABLES, any other platform class ends
+/// the search
+fn is_subclass_of(loader: &ClassLoader, class_name: &str, ancestor: &str) -> JvmResult<bool> {
+    let mut current = class_name.to_string();
+    loop {
+        if current == ancestor {
+            return Ok(true);
+        }
+        let super_class = if is_platform_class(&current) {
+            platform_super_class(&current).map(String::from)
+        } else {
+            loader.load_class(&current)?.class_file.super_class_name().map(String::from)
+        };
+        match super_class {
+            Some(super_class) => current = super_class,
+            None => return Ok(false)
+        }
+    }
+}
+
+/// Whether the exception is an Error rather than an Exception
+fn is_error(loader: &ClassLoader, class_name: &str) -> bool {
+    is_subclass_of(loader, class_name, "java/lang/Error").unwrap_or(false)
+}
+
+fn uncaught(class_name: &str, message: String, pc: u32) -> JvmError {
+    JvmError::UncaughtException { class_name: String::from(class_name), message: Some(message), pc }
+}
+
+/// Pops the arguments for a call off the stack, the last argument is on top
+fn pop_arguments(frame: &mut Frame, descriptor: &MethodDescriptor) -> JvmResult<Vec<Value>> {
+    let mut arguments: Vec<Value> = Vec::with_capacity(descriptor.parameters.len());
+    for parameter in descriptor.parameters.iter().rev() {
+        let value = frame.pop()?;
+        if !value.matches(parameter) {
+            return Err(JvmError::Runtime {
+                message: format!("Invoke - Expected {} argument but found {}", parameter.java_name(), value.type_name()),
+                pc: frame.pc
+            });
+        }
+        arguments.push(value);
+    }
+    arguments.reverse();
+    Ok(arguments)
+}
+
+/// Class index, name and descriptor of a MethodRef or InterfaceMethodRef
+fn get_method_ref(class_file: &ClassFile, index: u16, pc: u32) -> JvmResult<(u16, &str, &str)> {
+    let (class_index, name_and_type_index) = match get_constant(class_file, index, pc)? {
+        Constant::MethodRef(method_ref) => (method_ref.class_index, method_ref.name_and_type_index),
+        // Static and private interface methods are referenced with an InterfaceMethodRef
+        Constant::InterfaceMethodRef(method_ref) => (method_ref.class_index, method_ref.name_and_type_index),
+        _ => return Err(JvmError::UnexpectedConstant { index, expected: "MethodRef or InterfaceMethodRef", location: Location::Pc(pc) })
+    };
+    let name = get_name_of_member(class_file, name_and_type_index, pc)?;
+    let descriptor = get_descriptor_of_member(class_file, name_and_type_index, pc)?;
+    Ok((class_index, name, descriptor))
+}
+
+/// The descriptor of the MethodRef or InterfaceMethodRef at index, parsed the first time the
+/// reference is used
+fn method_descriptor(class: &Class, index: u16, pc: u32) -> JvmResult<Rc<MethodDescriptor>> {
+    if let Some(descriptor) = class.method_descriptors.borrow().get(&index) {
+        return Ok(descriptor.clone());
+    }
+
+    let (_, _, descriptor) = get_method_ref(&class.class_file, index, pc)?;
+    let descriptor = Rc::new(MethodDescriptor::parse(descriptor)?);
+    class.method_descriptors.borrow_mut().insert(index, descriptor.clone());
+    Ok(descriptor)
+}
+
+/// Calls a method of the class with the arguments already popped, returns its return value if any
+fn call_method(loader: &ClassLoader, class: &Rc<Class>, method: &MethodInfo, descriptor: &MethodDescriptor, arguments: Vec<Value>, pc: u32, depth: usize) -> JvmResult<Option<Value>> {
+    let code = match method.code() {
+        Some(code) => code,
+        None => {
+            let name = class.class_file.get_utf8(method.name_index).unwrap_or_default();
+            let error = if method.has_flag(MethodsAccessFlags::ABSTRACT) { "java/lang/AbstractMethodError" } else { "java/lang/UnsatisfiedLinkError" };
+            return Err(uncaught(error, format!("{}.{}", class.name, name), pc));
+        }
+    };
+    if depth + 1 >= MAX_CALL_DEPTH {
+        return Err(JvmError::UncaughtException { class_name: String::from("java/lang/StackOverflowError"), message: None, pc });
+    }
+
+    let result = execute_code(loader, class, code, descriptor, arguments, depth + 1)?;
+    // A return instruction of the wrong kind would leave the caller's stack unbalanced
+    if result.as_ref().map_or(0, Value::slots) != descriptor.return_slots() {
+        let name = class.class_file.get_utf8(method.name_index).unwrap_or_default();
+        return Err(JvmError::Runtime { message: format!("Invoke - {}.{} returned a value that doesn't match {}", class.name, name, descriptor), pc });
+    }
+    Ok(result)
+}
+
+/// Calls a method of the class with the arguments already popped, pushing its return value if any
+fn run_method(loader: &ClassLoader, class: &Rc<Class>, method: &MethodInfo, descriptor: &MethodDescriptor, arguments: Vec<Value>, frame: &mut Frame, depth: usize) -> JvmResult<()> {
+    if let Some(value) = call_method(loader, class, method, descriptor, arguments, frame.pc, depth)? {
+        frame.push(value)?;
+    }
+    Ok(())
+}
+
+fn invoke_static(loader: &ClassLoader, class: &Rc<Class>, index: u16, frame: &mut Frame, depth: usize) -> JvmResult<()> {
+    let pc = frame.pc;
+    let class_file = &class.class_file;
+    let (class_index, member_name, descriptor_string) = get_method_ref(class_file, index, pc)?;
+    let class_name = get_name_of_class(class_file, class_index, pc)?;
+
+    if is_platform_class(class_name) {
+        return Err(JvmError::Runtime {
+            message: format!("InvokeStatic - Unsupported class method {}.{}", class_name, member_name),
+            pc
+        });
+    }
+
+    let target = loader.resolve_class(class, class_index, pc)?;
+    let (declaring_class, method) = target.lookup_method(member_name, descriptor_string).ok_or_else(|| {
+        uncaught("java/lang/NoSuchMethodError", format!("{}.{}{}", class_name, member_name, descriptor_string), pc)
+    })?;
+    if !method.has_flag(MethodsAccessFlags::STATIC) {
+        return Err(uncaught("java/lang/IncompatibleClassChangeError", format!("Expected static method {}.{}", class_name, member_name), pc));
+    }
+
+    initialize_class(loader, &declaring_class, pc, depth)?;
+    let descriptor = method_descriptor(class, index, pc)?;
+    let arguments = pop_arguments(frame, &descriptor)?;
+    run_method(loader, &declaring_class, method, &descriptor, arguments, frame, depth)
+}
+
+/// Calls constructors, private methods and superclass methods, without dynamic dispatch
+fn invoke_special(loader: &ClassLoader, class: &Rc<Class>, index: u16, frame: &mut Frame, depth: usize) -> JvmResult<()> {
+    let pc = frame.pc;
+    let class_file = &class.class_file;
+    let (class_index, member_name, descriptor_string) = get_method_ref(class_file, index, pc)?;
+    let class_name = get_name_of_class(class_file, class_index, pc)?;
+    let descriptor = method_descriptor(class, index, pc)?;
+
+    if is_platform_class(class_name) {
+        // The constructor of Object does nothing
+        if class_name == "java/lang/Object" && member_name == "<init>" && descriptor.parameters.is_empty() {
+            frame.pop_reference()?;
+            return Ok(());
+        }
+        // super.toString() and the like in classes that extend Object
+        if class_name == "java/lang/Object" && is_object_method(member_name, descriptor_string) {
+            let arguments = pop_arguments(frame, &descriptor)?;
+            return match frame.pop_reference()? {
+                Reference::Null => Err(null_pointer(pc)),
+                receiver => invoke_object_method(member_name, receiver, arguments, frame)
+            };
         }
+        // The constructors of Throwable, called by user classes that extend a platform exception
+        if member_name == "<init>" && platform_super_class(class_name).is_some() && matches!(descriptor_string, "()V" | "(Ljava/lang/String;)V") {
+            let message = match pop_arguments(frame, &descriptor)?.first() {
+                Some(Value::Reference(Reference::String(message))) => Some(message.clone()),
+                _ => None
+            };
+            pop_object(frame)?.borrow_mut().message = message;
+            return Ok(());
+        }
+        return Err(JvmError::Runtime {
+            message: format!("InvokeSpecial - Unsupported class method {}.{}", class_name, member_name),
+            pc
+        });
+    }
 
-        let type_name: String;
-        match stack.pop_front() {
-            Some(Instruction::Type(tn)) => type_name = tn,
-            _ => panic!("InvokeVirtual - Expected Instruction-Type!!")
+    let target = loader.resolve_class(class, class_index, pc)?;
+    // Constructors are not inherited
+    let found = if member_name == "<init>" {
+        target.class_file.find_method(member_name, descriptor_string).map(|method| (target.clone(), method))
+    } else {
+        target.lookup_method(member_name, descriptor_string)
+    };
+    // Default methods are only inherited from superinterfaces, which the vtable has already picked from
+    if found.is_none() && member_name != "<init>" {
+        if let Some((declaring_class, method_index)) = target.vtable_index(member_name, descriptor_string).and_then(|index| target.vtable[index].method()) {
+            return invoke_special_method(loader, &declaring_class, &declaring_class.class_file.methods[method_index], &descriptor, frame, depth);
         }
+    }
+    let (declaring_class, method) = found.ok_or_else(|| {
+        uncaught("java/lang/NoSuchMethodError", format!("{}.{}{}", class_name, member_name, descriptor_string), pc)
+    })?;
+    if method.has_flag(MethodsAccessFlags::STATIC) {
+        return Err(uncaught("java/lang/IncompatibleClassChangeError", format!("Expected non-static method {}.{}", class_name, member_name), pc));
+    }
+    invoke_special_method(loader, &declaring_class, method, &descriptor, frame, depth)
+}
+
+fn invoke_special_method(loader: &ClassLoader, class: &Rc<Class>, method: &MethodInfo, descriptor: &MethodDescriptor, frame: &mut Frame, depth: usize) -> JvmResult<()> {
+    let mut arguments = pop_arguments(frame, descriptor)?;
+    let receiver = frame.pop_reference()?;
+    if matches!(receiver, Reference::Null) {
+        return Err(null_pointer(frame.pc));
+    }
+    arguments.insert(0, Value::Reference(receiver));
+    run_method(loader, class, method, descriptor, arguments, frame, depth)
+}
+
+/// Resolves a FieldRef to an instance field, JVMS 5.4.3.2. Instance fields can only be
+/// declared by the class or one of its superclasses
+fn resolve_field(loader: &ClassLoader, class: &Rc<Class>, index: u16, pc: u32) -> JvmResult<ResolvedField> {
+    if let Some(resolved) = class.resolved_fields.borrow().get(&index) {
+        return Ok(resolved.clone());
+    }
+
+    let class_file = &class.class_file;
+    let field_ref = match get_constant(class_file, index, pc)? {
+        Constant::FieldRef(field_ref) => field_ref,
+        _ => return Err(JvmError::UnexpectedConstant { index, expected: "FieldRef", location: Location::Pc(pc) })
+    };
+    let class_name = get_name_of_class(class_file, field_ref.class_index, pc)?;
+    let name = get_name_of_member(class_file, field_ref.name_and_type_index, pc)?;
+    let descriptor = get_descriptor_of_member(class_file, field_ref.name_and_type_index, pc)?;
+    if is_platform_class(class_name) {
+        return Err(JvmError::Runtime { message: format!("Unsupported field {}.{}", class_name, name), pc });
+    }
+
+    let target = loader.resolve_class(class, field_ref.class_index, pc)?;
+    let mut current = Some(&target);
+    let mut declaring_class: Option<&str> = None;
+    while let Some(candidate) = current {
+        if candidate.declares_field(name, descriptor) {
+            declaring_class = Some(&candidate.name);
+            break;
+        }
+        current = candidate.super_class.as_ref();
+    }
+    let declaring_class = declaring_class.ok_or_else(|| {
+        uncaught("java/lang/NoSuchFieldError", format!("{}.{}", class_name, name), pc)
+    })?;
+    // Static fields are not part of the layout
+    let field_index = target.field_index(declaring_class, name).ok_or_else(|| {
+        uncaught("java/lang/IncompatibleClassChangeError", format!("Expected non-static field {}.{}", class_name, name), pc)
+    })?;
+
+    let resolved = ResolvedField { index: field_index, field_type: FieldType::parse(descriptor)? };
+    class.resolved_fields.borrow_mut().insert(index, resolved.clone());
+    Ok(resolved)
+}
+
+fn pop_object(frame: &mut Frame) -> JvmResult<Rc<RefCell<Object>>> {
+    match frame.pop_reference()? {
+        Reference::Object(object) => Ok(object),
+        Reference::Null => Err(null_pointer(frame.pc)),
+        _ => Err(JvmError::UnexpectedValue { expected: "object", found: "reference", pc: frame.pc })
+    }
+}
+
+fn get_field(loader: &ClassLoader, class: &Rc<Class>, index: u16, frame: &mut Frame) -> JvmResult<()> {
+    let field = resolve_field(loader, class, index, frame.pc)?;
+    let object = pop_object(frame)?;
+    let value = object.borrow().fields[field.index].clone();
+    frame.push(value)
+}
+
+fn put_field(loader: &ClassLoader, class: &Rc<Class>, index: u16, frame: &mut Frame) -> JvmResult<()> {
+    let field = resolve_field(loader, class, index, frame.pc)?;
+    let value = frame.pop()?;
+    if !value.matches(&field.field_type) {
+        return Err(JvmError::Runtime {
+            message: format!("PutField - Expected {} but found {}", field.field_type.java_name(), value.type_name()),
+            pc: frame.pc
+        });
+    }
+    let object = pop_object(frame)?;
+    object.borrow_mut().fields[field.index] = value.narrow_to(&field.field_type);
+    Ok(())
+}
+
+fn new_instance(loader: &ClassLoader, class: &Rc<Class>, index: u16, frame: &mut Frame, depth: usize) -> JvmResult<()> {
+    let pc = frame.pc;
+    let class_name = get_name_of_class(&class.class_file, index, pc)?;
+    if is_platform_class(class_name) {
+        return Err(JvmError::Runtime { message: format!("New - Unsupported class {}", class_name), pc });
+    }
+
+    let target = loader.resolve_class(class, index, pc)?;
+    if target.is_interface() || target.class_file.has_flag(ClassAccessFlags::ABSTRACT) {
+        return Err(uncaught("java/lang/InstantiationError", target.name.replace('/', "."), pc));
+    }
+    initialize_class(loader, &target, pc, depth)?;
+    frame.push(Value::Reference(new_object(&target)))
+}
+
+fn null_pointer(pc: u32) -> JvmError {
+    JvmError::UncaughtException { class_name: String::from("java/lang/NullPointerException"), message: None, pc }
+}
 
-        if type_name != "FakePrintStream" {
-            panic!("Unsupported stream type {}", type_name)
+/// Throws the exception on top of the stack, null throws a NullPointerException instead
+fn athrow(loader: &ClassLoader, frame: &mut Frame) -> JvmResult<()> {
+    let pc = frame.pc;
+    let (class_name, message) = match frame.pop_reference()? {
+        Reference::Null => return Err(null_pointer(pc)),
+        Reference::Throwable(throwable) => (throwable.class_name.clone(), throwable.message.clone()),
+        Reference::Object(object) => {
+            let object = object.borrow();
+            (object.class.name.clone(), object.message.as_deref().map(String::from))
         }
+        _ => return Err(JvmError::UnexpectedValue { expected: "Throwable", found: "reference", pc })
+    };
+    if !is_subclass_of(loader, &class_name, "java/lang/Throwable")? {
+        return Err(JvmError::Runtime { message: format!("Athrow - {} is not a Throwable", class_name), pc });
+    }
+    Err(JvmError::UncaughtException { class_name, message, pc })
+}
+
+fn pop_array(frame: &mut Frame) -> JvmResult<Rc<RefCell<Array>>> {
+    match frame.pop_reference()? {
+        Reference::Array(array) => Ok(array),
+        Reference::Null => Err(null_pointer(frame.pc)),
+        _ => Err(JvmError::UnexpectedValue { expected: "array", found: "reference", pc: frame.pc })
+    }
+}
+
+/// Pops an array length, negative lengths throw NegativeArraySizeException
+fn pop_length(frame: &mut Frame) -> JvmResult<usize> {
+    let length = frame.pop_int()?;
+    if length < 0 {
+        return Err(uncaught("java/lang/NegativeArraySizeException", length.to_string(), frame.pc));
+    }
+    Ok(length as usize)
+}
+
+fn check_index(array: &Array, index: i32, pc: u32) -> JvmResult<usize> {
+    if index < 0 || index as usize >= array.elements.len() {
+        return Err(uncaught(
+            "java/lang/ArrayIndexOutOfBoundsException",
+            format!("Index {} out of bounds for length {}", index, array.elements.len()),
+            pc
+        ));
+    }
+    Ok(index as usize)
+}
+
+/// The type named by a Class constant used as an array component, e.g. java/lang/String or [I
+fn get_class_as_field_type(class_file: &ClassFile, class_index: u16, pc: u32) -> JvmResult<FieldType> {
+    let name = get_name_of_class(class_file, class_index, pc)?;
+    if name.starts_with('[') {
+        return FieldType::parse(name);
+    }
+    Ok(FieldType::Object(name.to_string()))
+}
 
-        match stack.pop_front() {
-            Some(Instruction::String(val)) => println!("{}", val),
-            Some(Instruction::SByte(val)) => println!("{}", val),
-            Some(Instruction::Short(val)) => println!("{}", val),
-            Some(Instruction::Integer(val)) => println!("{}", val),
-            Some(Instruction::Float(val)) => println!("{}", val),
-            _ => panic!("InvokeVirtual - Expected Instruction-Constant!!")
+fn new_array(frame: &mut Frame, component: FieldType) -> JvmResult<()> {
+    let length = pop_length(frame)?;
+    frame.push(Value::Reference(Reference::Array(Rc::new(RefCell::new(Array::new(component, length))))))
+}
+
+/// The caller checks that component has at least lengths.len() - 1 array dimensions
+fn new_multi_array(component: &FieldType, lengths: &[usize]) -> Reference {
+    let mut array = Array::new(component.clone(), lengths[0]);
+    // Dimensions without a length are left as null
+    if lengths.len() > 1 {
+        if let FieldType::Array(inner) = component {
+            for element in array.elements.iter_mut() {
+                *element = Value::Reference(new_multi_array(inner, &lengths[1..]));
+            }
         }
+    }
+    Reference::Array(Rc::new(RefCell::new(array)))
+}
+
+fn multi_anew_array(class_file: &ClassFile, index: u16, dimensions: u8, frame: &mut Frame) -> JvmResult<()> {
+    let array_type = get_class_as_field_type(class_file, index, frame.pc)?;
+    let mut depth = 0;
+    let mut innermost = &array_type;
+    while let FieldType::Array(inner) = innermost {
+        depth += 1;
+        innermost = inner;
+    }
+    let component = match &array_type {
+        FieldType::Array(component) if dimensions != 0 && dimensions as usize <= depth => component,
+        _ => return Err(JvmError::Runtime {
+            message: format!("MultiANewArray - {} dimensions for array type {}", dimensions, array_type),
+            pc: frame.pc
+        })
+    };
+
+    // The outermost length was pushed first
+    let mut lengths: Vec<usize> = Vec::with_capacity(dimensions as usize);
+    for _ in 0..dimensions {
+        lengths.push(pop_length(frame)?);
+    }
+    lengths.reverse();
 
-        return Ok(())
+    frame.push(Value::Reference(new_multi_array(component, &lengths)))
+}
+
+fn array_load(frame: &mut Frame, expected: &'static str, is_component: fn(&FieldType) -> bool) -> JvmResult<()> {
+    let index = frame.pop_int()?;
+    let array = pop_array(frame)?;
+    let array = array.borrow();
+    if !is_component(&array.component) {
+        return Err(JvmError::UnexpectedValue { expected, found: "array of another type", pc: frame.pc });
     }
+    let index = check_index(&array, index, frame.pc)?;
+    frame.push(array.elements[index].clone())
+}
+
+fn array_store(frame: &mut Frame, expected: &'static str, is_component: fn(&FieldType) -> bool) -> JvmResult<()> {
+    let value = frame.pop()?;
+    let index = frame.pop_int()?;
+    let array = pop_array(frame)?;
+    let mut array = array.borrow_mut();
+    if !is_component(&array.component) || !value.matches(&array.component) {
+        return Err(JvmError::UnexpectedValue { expected, found: value.type_name(), pc: frame.pc });
+    }
+    let index = check_index(&array, index, frame.pc)?;
+
+    array.elements[index] = value.narrow_to(&array.component);
+    Ok(())
+}
+
+// The branch helpers return the offset to jump by, None to fall through to the next instruction
+
+fn branch_if(condition: bool, offset: i32) -> Option<i32> {
+    if condition { Some(offset) } else { None }
+}
+
+fn int_branch(frame: &mut Frame, offset: i32, condition: fn(i32) -> bool) -> JvmResult<Option<i32>> {
+    let value = frame.pop_int()?;
+    Ok(branch_if(condition(value), offset))
+}
+
+fn int_compare_branch(frame: &mut Frame, offset: i32, condition: fn(i32, i32) -> bool) -> JvmResult<Option<i32>> {
+    let right = frame.pop_int()?;
+    let left = frame.pop_int()?;
+    Ok(branch_if(condition(left, right), offset))
+}
 
-    panic!("InvokeVirtual - Unsupported class method {}.{}", class_name, member_name);
+fn reference_compare_branch(frame: &mut Frame, offset: i32, same: bool) -> JvmResult<Option<i32>> {
+    let right = frame.pop_reference()?;
+    let left = frame.pop_reference()?;
+    Ok(branch_if(left.is_same(&right) == same, offset))
 }
 
-pub fn execute_code(class_file: &ClassFile, code: Vec<u8>) -> io::Result<()> {
-    let mut cursor = Cursor::new(code.clone());
-    let mut stack: VecDeque<Instruction> = VecDeque::new();
+fn null_branch(frame: &mut Frame, offset: i32, null: bool) -> JvmResult<Option<i32>> {
+    let value = frame.pop_reference()?;
+    Ok(branch_if(matches!(value, Reference::Null) == null, offset))
+}
+
+// Every call recurses into execute_code, so the call depth is limited well before the native stack runs out
+const MAX_CALL_DEPTH: usize = 4096;
+
+pub fn execute_method(loader: &ClassLoader, class: &Rc<Class>, method: &MethodInfo, arguments: Vec<Value>) -> JvmResult<Option<Value>> {
+    // Running main is an active use of its class
+    initialize_class(loader, class, 0, 0)?;
+    let descriptor = MethodDescriptor::parse(class.class_file.get_utf8(method.descriptor_index).unwrap_or_default())?;
+    match method.code() {
+        Some(code) => execute_code(loader, class, code, &descriptor, arguments, 0),
+        None => Err(JvmError::Runtime { message: String::from("Method has no Code attribute"), pc: 0 })
+    }
+}
+
+/// Runs a method's code with the arguments in its first locals, returns the value returned by it if any
+fn execute_code(loader: &ClassLoader, class: &Rc<Class>, code: &CodeAttribute, descriptor: &MethodDescriptor, arguments: Vec<Value>, depth: usize) -> JvmResult<Option<Value>> {
+    let mut frame = Frame::new(code.max_locals, code.max_stack);
 
-    while cursor.position() < code.len() as u64 {
-        let opcode = cursor.read_u8()?;
-        match opcode {
-            OP_CODE_GET_STATIC =>  {
-                get_static(class_file, &mut cursor, &mut stack)?;
+    // The receiver of an instance method goes in local 0 and the parameters follow it, with
+    // the slots the descriptor gives them
+    let receiver_slots = arguments.len().saturating_sub(descriptor.parameters.len());
+    if receiver_slots + descriptor.parameter_slots() > code.max_locals as usize {
+        return Err(JvmError::Runtime {
+            message: format!("Invoke - max_locals {} is too small for the arguments of {}", code.max_locals, descriptor),
+            pc: 0
+        });
+    }
+    let mut local_index = 0;
+    for (position, argument) in arguments.into_iter().enumerate() {
+        let slots = match position.checked_sub(receiver_slots) {
+            Some(parameter) => descriptor.parameters[parameter].slots(),
+            None => 1
+        };
+        frame.set_local(local_index as u16, argument)?;
+        local_index += slots;
+    }
+
+    let mut index = 0;
+    loop {
+        match run_instructions(loader, class, code, &mut frame, &mut index, depth) {
+            Err(JvmError::UncaughtException { class_name, message, pc }) => {
+                let handler_pc = code.find_handler(frame.pc, |catch_type| {
+                    let catch_name = get_name_of_class(&class.class_file, catch_type, frame.pc)?;
+                    is_subclass_of(loader, &class_name, catch_name)
+                })?.map(|handler| handler.handler_pc as u32);
+                let handler_pc = match handler_pc {
+                    Some(handler_pc) => handler_pc,
+                    None => return Err(JvmError::UncaughtException { class_name, message, pc })
+                };
+
+                // The handler starts with only the exception on the operand stack
+                frame.clear_stack();
+                frame.push(Value::Reference(Reference::Throwable(Rc::new(Throwable { class_name, message }))))?;
+                index = index_of_pc(code.instructions()?, handler_pc).ok_or(JvmError::Runtime {
+                    message: format!("Exception handler {} is not the start of an instruction", handler_pc),
+                    pc: frame.pc
+                })?;
             }
-            OP_CODE_LDC => {
-                ldc(class_file, &mut cursor, &mut stack)?;
+            result => return result
+        }
+    }
+}
+
+/// Runs the code from the instruction at index until the method returns or an instruction
+/// fails, index is left at the failed instruction so a handler can be looked up for it
+fn run_instructions(loader: &ClassLoader, class: &Rc<Class>, code: &CodeAttribute, frame: &mut Frame, index: &mut usize, depth: usize) -> JvmResult<Option<Value>> {
+    let class_file = &class.class_file;
+    let instructions = code.instructions()?;
+    while let Some(instruction) = instructions.get(*index) {
+        frame.pc = instruction.pc;
+        let mut jump: Option<i32> = None;
+        match instruction.op {
+            Op::GetStatic(index) =>  {
+                get_static(loader, class, index, frame, depth)?;
+            }
+            Op::PutStatic(index) => put_static(loader, class, index, frame, depth)?,
+            Op::Ldc(index) | Op::LdcW(index) => {
+                ldc(class_file, index, frame)?;
             },
-            OP_CODE_INVOKE_VIRTUAL => {
-                invoke_virtual(class_file, &mut cursor, &mut stack)?;
+            Op::Ldc2W(index) => {
+                ldc2_w(class_file, index, frame)?;
             },
-            OP_CODE_BI_PUSH => {
-                stack.push_back(Instruction::SByte(cursor.read_u8()? as i8));
+            Op::InvokeVirtual(index) => {
+                invoke_virtual(loader, class, index, frame, depth)?;
             },
-            OP_CODE_SI_PUSH => {
-                stack.push_back(Instruction::Short(cursor.read_u16()? as i16));
+            Op::InvokeInterface { index, .. } => invoke_interface(loader, class, index, frame, depth)?,
+            Op::InvokeStatic(index) => {
+                invoke_static(loader, class, index, frame, depth)?;
+            },
+            Op::InvokeSpecial(index) => {
+                invoke_special(loader, class, index, frame, depth)?;
+            },
+            Op::New(index) => new_instance(loader, class, index, frame, depth)?,
+            Op::GetField(index) => get_field(loader, class, index, frame)?,
+            Op::PutField(index) => put_field(loader, class, index, frame)?,
+            Op::BiPush(value) => frame.push(Value::Int(value as i32))?,
+            Op::SiPush(value) => frame.push(Value::Int(value as i32))?,
+            Op::AconstNull => frame.push(Value::Reference(Reference::Null))?,
+            Op::IconstM1 => frame.push(Value::Int(-1))?,
+            Op::Iconst0 => frame.push(Value::Int(0))?,
+            Op::Iconst1 => frame.push(Value::Int(1))?,
+            Op::Iconst2 => frame.push(Value::Int(2))?,
+            Op::Iconst3 => frame.push(Value::Int(3))?,
+            Op::Iconst4 => frame.push(Value::Int(4))?,
+            Op::Iconst5 => frame.push(Value::Int(5))?,
+            Op::Lconst0 => frame.push(Value::Long(0))?,
+            Op::Lconst1 => frame.push(Value::Long(1))?,
+            Op::Fconst0 => frame.push(Value::Float(0.0))?,
+            Op::Fconst1 => frame.push(Value::Float(1.0))?,
+            Op::Fconst2 => frame.push(Value::Float(2.0))?,
+            Op::Dconst0 => frame.push(Value::Double(0.0))?,
+            Op::Dconst1 => frame.push(Value::Double(1.0))?,
+            Op::Iload(index) => frame.load(index, "int", is_int)?,
+            Op::Iload0 => frame.load(0, "int", is_int)?,
+            Op::Iload1 => frame.load(1, "int", is_int)?,
+            Op::Iload2 => frame.load(2, "int", is_int)?,
+            Op::Iload3 => frame.load(3, "int", is_int)?,
+            Op::Lload(index) => frame.load(index, "long", is_long)?,
+            Op::Lload0 => frame.load(0, "long", is_long)?,
+            Op::Lload1 => frame.load(1, "long", is_long)?,
+            Op::Lload2 => frame.load(2, "long", is_long)?,
+            Op::Lload3 => frame.load(3, "long", is_long)?,
+            Op::Fload(index) => frame.load(index, "float", is_float)?,
+            Op::Fload0 => frame.load(0, "float", is_float)?,
+            Op::Fload1 => frame.load(1, "float", is_float)?,
+            Op::Fload2 => frame.load(2, "float", is_float)?,
+            Op::Fload3 => frame.load(3, "float", is_float)?,
+            Op::Dload(index) => frame.load(index, "double", is_double)?,
+            Op::Dload0 => frame.load(0, "double", is_double)?,
+            Op::Dload1 => frame.load(1, "double", is_double)?,
+            Op::Dload2 => frame.load(2, "double", is_double)?,
+            Op::Dload3 => frame.load(3, "double", is_double)?,
+            Op::Aload(index) => frame.load(index, "reference", is_reference)?,
+            Op::Aload0 => frame.load(0, "reference", is_reference)?,
+            Op::Aload1 => frame.load(1, "reference", is_reference)?,
+            Op::Aload2 => frame.load(2, "reference", is_reference)?,
+            Op::Aload3 => frame.load(3, "reference", is_reference)?,
+            Op::Istore(index) => frame.store(index, "int", is_int)?,
+            Op::Istore0 => frame.store(0, "int", is_int)?,
+            Op::Istore1 => frame.store(1, "int", is_int)?,
+            Op::Istore2 => frame.store(2, "int", is_int)?,
+            Op::Istore3 => frame.store(3, "int", is_int)?,
+            Op::Lstore(index) => frame.store(index, "long", is_long)?,
+            Op::Lstore0 => frame.store(0, "long", is_long)?,
+            Op::Lstore1 => frame.store(1, "long", is_long)?,
+            Op::Lstore2 => frame.store(2, "long", is_long)?,
+            Op::Lstore3 => frame.store(3, "long", is_long)?,
+            Op::Fstore(index) => frame.store(index, "float", is_float)?,
+            Op::Fstore0 => frame.store(0, "float", is_float)?,
+            Op::Fstore1 => frame.store(1, "float", is_float)?,
+            Op::Fstore2 => frame.store(2, "float", is_float)?,
+            Op::Fstore3 => frame.store(3, "float", is_float)?,
+            Op::Dstore(index) => frame.store(index, "double", is_double)?,
+            Op::Dstore0 => frame.store(0, "double", is_double)?,
+            Op::Dstore1 => frame.store(1, "double", is_double)?,
+            Op::Dstore2 => frame.store(2, "double", is_double)?,
+            Op::Dstore3 => frame.store(3, "double", is_double)?,
+            Op::Astore(index) => frame.store(index, "reference", is_reference)?,
+            Op::Astore0 => frame.store(0, "reference", is_reference)?,
+            Op::Astore1 => frame.store(1, "reference", is_reference)?,
+            Op::Astore2 => frame.store(2, "reference", is_reference)?,
+            Op::Astore3 => frame.store(3, "reference", is_reference)?,
+            Op::Iinc { index, constant } => frame.iinc(index, constant)?,
+            Op::Iadd => int_binary(frame, i32::wrapping_add)?,
+            Op::Ladd => long_binary(frame, i64::wrapping_add)?,
+            Op::Isub => int_binary(frame, i32::wrapping_sub)?,
+            Op::Lsub => long_binary(frame, i64::wrapping_sub)?,
+            Op::Imul => int_binary(frame, i32::wrapping_mul)?,
+            Op::Lmul => long_binary(frame, i64::wrapping_mul)?,
+            Op::Idiv => int_division(frame, i32::wrapping_div)?,
+            Op::Ldiv => long_division(frame, i64::wrapping_div)?,
+            Op::Irem => int_division(frame, i32::wrapping_rem)?,
+            Op::Lrem => long_division(frame, i64::wrapping_rem)?,
+            Op::Fadd => float_binary(frame, |left, right| left + right)?,
+            Op::Dadd => double_binary(frame, |left, right| left + right)?,
+            Op::Fsub => float_binary(frame, |left, right| left - right)?,
+            Op::Dsub => double_binary(frame, |left, right| left - right)?,
+            Op::Fmul => float_binary(frame, |left, right| left * right)?,
+            Op::Dmul => double_binary(frame, |left, right| left * right)?,
+            Op::Fdiv => float_binary(frame, |left, right| left / right)?,
+            Op::Ddiv => double_binary(frame, |left, right| left / right)?,
+            // Rust's % on floats truncates like C's fmod, which is what Java specifies, not IEEE remainder
+            Op::Frem => float_binary(frame, |left, right| left % right)?,
+            Op::Drem => double_binary(frame, |left, right| left % right)?,
+            Op::Ineg => {
+                let value = frame.pop_int()?;
+                frame.push(Value::Int(value.wrapping_neg()))?;
+            }
+            Op::Lneg => {
+                let value = frame.pop_long()?;
+                frame.push(Value::Long(value.wrapping_neg()))?;
+            }
+            Op::Fneg => {
+                let value = frame.pop_float()?;
+                frame.push(Value::Float(-value))?;
+            }
+            Op::Dneg => {
+                let value = frame.pop_double()?;
+                frame.push(Value::Double(-value))?;
+            }
+            // Float to integer casts in Rust saturate and turn NaN into 0 the same way JLS 5.1.3 does,
+            // and int to float casts round to nearest
+            Op::I2l => {
+                let value = frame.pop_int()?;
+                frame.push(Value::Long(value as i64))?;
+            }
+            Op::I2f => {
+                let value = frame.pop_int()?;
+                frame.push(Value::Float(value as f32))?;
+            }
+            Op::I2d => {
+                let value = frame.pop_int()?;
+                frame.push(Value::Double(value as f64))?;
             }
-            OP_CODE_RETURN => {
-                assert_eq!(stack.len(), 0, "Return - Stack was not empty!");
-                return Ok(())
+            Op::L2i => {
+                let value = frame.pop_long()?;
+                frame.push(Value::Int(value as i32))?;
             }
-            _ => {
-                unimplemented!("OP CODE 0x{:x} NOT IMPLEMENTED", opcode);
+            Op::L2f => {
+                let value = frame.pop_long()?;
+                frame.push(Value::Float(value as f32))?;
             }
+            Op::L2d => {
+                let value = frame.pop_long()?;
+                frame.push(Value::Double(value as f64))?;
+            }
+            Op::F2i => {
+                let value = frame.pop_float()?;
+                frame.push(Value::Int(value as i32))?;
+            }
+            Op::F2l => {
+                let value = frame.pop_float()?;
+                frame.push(Value::Long(value as i64))?;
+            }
+            Op::F2d => {
+                let value = frame.pop_float()?;
+                frame.push(Value::Double(value as f64))?;
+            }
+            Op::D2i => {
+                let value = frame.pop_double()?;
+                frame.push(Value::Int(value as i32))?;
+            }
+            Op::D2l => {
+                let value = frame.pop_double()?;
+                frame.push(Value::Long(value as i64))?;
+            }
+            Op::D2f => {
+                let value = frame.pop_double()?;
+                frame.push(Value::Float(value as f32))?;
+            }
+            Op::I2b => {
+                let value = frame.pop_int()?;
+                frame.push(Value::Int(value as i8 as i32))?;
+            }
+            Op::I2c => {
+                let value = frame.pop_int()?;
+                frame.push(Value::Int(value as u16 as i32))?;
+            }
+            Op::I2s => {
+                let value = frame.pop_int()?;
+                frame.push(Value::Int(value as i16 as i32))?;
+            }
+            Op::Lcmp => {
+                let right = frame.pop_long()?;
+                let left = frame.pop_long()?;
+                frame.push(Value::Int(left.cmp(&right) as i32))?;
+            }
+            Op::Fcmpl => float_compare(frame, -1)?,
+            Op::Fcmpg => float_compare(frame, 1)?,
+            Op::Dcmpl => double_compare(frame, -1)?,
+            Op::Dcmpg => double_compare(frame, 1)?,
+            Op::Ishl => int_binary(frame, |value, distance| value.wrapping_shl(distance as u32))?,
+            Op::Lshl => long_shift(frame, i64::wrapping_shl)?,
+            Op::Ishr => int_binary(frame, |value, distance| value.wrapping_shr(distance as u32))?,
+            Op::Lshr => long_shift(frame, i64::wrapping_shr)?,
+            Op::Iushr => int_binary(frame, |value, distance| (value as u32).wrapping_shr(distance as u32) as i32)?,
+            Op::Lushr => long_shift(frame, |value, distance| (value as u64).wrapping_shr(distance) as i64)?,
+            Op::Iand => int_binary(frame, |left, right| left & right)?,
+            Op::Land => long_binary(frame, |left, right| left & right)?,
+            Op::Ior => int_binary(frame, |left, right| left | right)?,
+            Op::Lor => long_binary(frame, |left, right| left | right)?,
+            Op::Ixor => int_binary(frame, |left, right| left ^ right)?,
+            Op::Lxor => long_binary(frame, |left, right| left ^ right)?,
+            Op::NewArray(atype) => {
+                let component = FieldType::from_array_type(atype).ok_or(JvmError::InvalidInstruction { opcode: instruction.opcode, pc: frame.pc })?;
+                new_array(frame, component)?;
+            }
+            Op::AnewArray(index) => {
+                let component = get_class_as_field_type(class_file, index, frame.pc)?;
+                if let FieldType::Object(name) = &component {
+                    if !is_platform_class(name) {
+                        loader.resolve_class(class, index, frame.pc)?;
+                    }
+                }
+                new_array(frame, component)?;
+            }
+            Op::MultiAnewArray { index, dimensions } => multi_anew_array(class_file, index, dimensions, frame)?,
+            Op::ArrayLength => {
+                let array = pop_array(frame)?;
+                let length = array.borrow().elements.len() as i32;
+                frame.push(Value::Int(length))?;
+            }
+            Op::Iaload => array_load(frame, "int[]", |component| *component == FieldType::Int)?,
+            Op::Laload => array_load(frame, "long[]", |component| *component == FieldType::Long)?,
+            Op::Faload => array_load(frame, "float[]", |component| *component == FieldType::Float)?,
+            Op::Daload => array_load(frame, "double[]", |component| *component == FieldType::Double)?,
+            Op::Aaload => array_load(frame, "reference array", FieldType::is_reference)?,
+            Op::Baload => array_load(frame, "byte[] or boolean[]", |component| matches!(component, FieldType::Byte | FieldType::Boolean))?,
+            Op::Caload => array_load(frame, "char[]", |component| *component == FieldType::Char)?,
+            Op::Saload => array_load(frame, "short[]", |component| *component == FieldType::Short)?,
+            Op::Iastore => array_store(frame, "int[]", |component| *component == FieldType::Int)?,
+            Op::Lastore => array_store(frame, "long[]", |component| *component == FieldType::Long)?,
+            Op::Fastore => array_store(frame, "float[]", |component| *component == FieldType::Float)?,
+            Op::Dastore => array_store(frame, "double[]", |component| *component == FieldType::Double)?,
+            Op::Aastore => array_store(frame, "reference array", FieldType::is_reference)?,
+            Op::Bastore => array_store(frame, "byte[] or boolean[]", |component| matches!(component, FieldType::Byte | FieldType::Boolean))?,
+            Op::Castore => array_store(frame, "char[]", |component| *component == FieldType::Char)?,
+            Op::Sastore => array_store(frame, "short[]", |component| *component == FieldType::Short)?,
+            Op::Pop => frame.pop_discard()?,
+            Op::Pop2 => frame.pop2()?,
+            Op::Dup => frame.dup()?,
+            Op::DupX1 => frame.dup_x1()?,
+            Op::DupX2 => frame.dup_x2()?,
+            Op::Dup2 => frame.dup2()?,
+            Op::Dup2X1 => frame.dup2_x1()?,
+            Op::Dup2X2 => frame.dup2_x2()?,
+            Op::Swap => frame.swap()?,
+            Op::IfEq(offset) => jump = int_branch(frame, offset, |value| value == 0)?,
+            Op::IfNe(offset) => jump = int_branch(frame, offset, |value| value != 0)?,
+            Op::IfLt(offset) => jump = int_branch(frame, offset, |value| value < 0)?,
+            Op::IfGe(offset) => jump = int_branch(frame, offset, |value| value >= 0)?,
+            Op::IfGt(offset) => jump = int_branch(frame, offset, |value| value > 0)?,
+            Op::IfLe(offset) => jump = int_branch(frame, offset, |value| value <= 0)?,
+            Op::IfIcmpEq(offset) => jump = int_compare_branch(frame, offset, |left, right| left == right)?,
+            Op::IfIcmpNe(offset) => jump = int_compare_branch(frame, offset, |left, right| left != right)?,
+            Op::IfIcmpLt(offset) => jump = int_compare_branch(frame, offset, |left, right| left < right)?,
+            Op::IfIcmpGe(offset) => jump = int_compare_branch(frame, offset, |left, right| left >= right)?,
+            Op::IfIcmpGt(offset) => jump = int_compare_branch(frame, offset, |left, right| left > right)?,
+            Op::IfIcmpLe(offset) => jump = int_compare_branch(frame, offset, |left, right| left <= right)?,
+            Op::IfAcmpEq(offset) => jump = reference_compare_branch(frame, offset, true)?,
+            Op::IfAcmpNe(offset) => jump = reference_compare_branch(frame, offset, false)?,
+            Op::IfNull(offset) => jump = null_branch(frame, offset, true)?,
+            Op::IfNonNull(offset) => jump = null_branch(frame, offset, false)?,
+            Op::Goto(offset) | Op::GotoW(offset) => jump = Some(offset),
+            Op::TableSwitch(ref table) => jump = Some(table.offset_for(frame.pop_int()?)),
+            Op::LookupSwitch(ref lookup) => jump = Some(lookup.offset_for(frame.pop_int()?)),
+            // Anything left on the operand stack is discarded with the frame
+            Op::Return => return Ok(None),
+            Op::Ireturn => return Ok(Some(Value::Int(frame.pop_int()?))),
+            Op::Lreturn => return Ok(Some(Value::Long(frame.pop_long()?))),
+            Op::Freturn => return Ok(Some(Value::Float(frame.pop_float()?))),
+            Op::Dreturn => return Ok(Some(Value::Double(frame.pop_double()?))),
+            Op::Areturn => return Ok(Some(Value::Reference(frame.pop_reference()?))),
+            Op::Athrow => athrow(loader, frame)?,
+            // Subroutines were removed from the instruction set in version 51 class files
+            Op::Jsr(_) | Op::JsrW(_) | Op::Ret(_) if !class_file.supports(ClassFileFeature::Subroutines) =>
+                return Err(JvmError::InvalidInstruction { opcode: instruction.opcode, pc: frame.pc }),
+            _ => return Err(JvmError::UnsupportedOpcode { opcode: instruction.opcode, pc: frame.pc })
+        }
+
+        *index = match jump {
+            Some(offset) => {
+                let target = instruction.branch_target(offset);
+                index_of_pc(instructions, target).ok_or(JvmError::Runtime {
+                    message: format!("Branch target {} is not the start of an instruction", target),
+                    pc: frame.pc
+                })?
+            }
+            None => *index + 1
+        };
+    }
+
+    Err(JvmError::Runtime {
+        message: String::from("Execution fell off the end of the code"),
+        pc: code.code.len() as u32
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opcodes::*;
+
+    // Classes compiled from the .java files next to them
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/etc/tests");
+
+    fn call(class_name: &str, name: &str, descriptor: &str, arguments: Vec<Value>) -> JvmResult<Option<Value>> {
+        call_in(FIXTURES, class_name, name, descriptor, arguments)
+    }
+
+    fn call_in(class_path: &str, class_name: &str, name: &str, descriptor: &str, arguments: Vec<Value>) -> JvmResult<Option<Value>> {
+        let loader = ClassLoader::new(ClassLoader::parse_class_path(class_path));
+        let class = loader.load_class(class_name)?;
+        let method = class.class_file.find_method(name, descriptor)
+            .unwrap_or_else(|| panic!("{}.{}{} is missing", class_name, name, descriptor));
+        execute_method(&loader, &class, method, arguments)
+    }
+
+    fn call_int(class_name: &str, name: &str, descriptor: &str, arguments: Vec<Value>) -> i32 {
+        match call(class_name, name, descriptor, arguments) {
+            Ok(Some(Value::Int(value))) => value,
+            result => panic!("{}{} returned {:?}", name, descriptor, result)
+        }
+    }
+
+    fn call_long(class_name: &str, name: &str, descriptor: &str, arguments: Vec<Value>) -> i64 {
+        match call(class_name, name, descriptor, arguments) {
+            Ok(Some(Value::Long(value))) => value,
+            result => panic!("{}{} returned {:?}", name, descriptor, result)
+        }
+    }
+
+    fn assert_arithmetic_exception(result: JvmResult<Option<Value>>) {
+        match result {
+            Err(JvmError::UncaughtException { class_name, message, .. }) => {
+                assert_eq!(class_name, "java/lang/ArithmeticException");
+                assert_eq!(message.as_deref(), Some("/ by zero"));
+            }
+            result => panic!("expected an ArithmeticException, got {:?}", result)
+        }
+    }
+
+    fn ints(left: i32, right: i32) -> Vec<Value> {
+        vec![Value::Int(left), Value::Int(right)]
+    }
+
+    fn longs(left: i64, right: i64) -> Vec<Value> {
+        vec![Value::Long(left), Value::Long(right)]
+    }
+
+    fn long_and_int(left: i64, right: i32) -> Vec<Value> {
+        vec![Value::Long(left), Value::Int(right)]
+    }
+
+    #[test]
+    fn min_value_divided_by_minus_one_overflows() {
+        assert_eq!(call_int("Arithmetic", "idiv", "(II)I", ints(i32::MIN, -1)), i32::MIN);
+        assert_eq!(call_int("Arithmetic", "irem", "(II)I", ints(i32::MIN, -1)), 0);
+        assert_eq!(call_long("Arithmetic", "ldiv", "(JJ)J", longs(i64::MIN, -1)), i64::MIN);
+        assert_eq!(call_long("Arithmetic", "lrem", "(JJ)J", longs(i64::MIN, -1)), 0);
+
+        // Division truncates towards zero and the remainder takes the sign of the dividend
+        assert_eq!(call_int("Arithmetic", "idiv", "(II)I", ints(-7, 2)), -3);
+        assert_eq!(call_int("Arithmetic", "irem", "(II)I", ints(-7, 2)), -1);
+        assert_eq!(call_long("Arithmetic", "lrem", "(JJ)J", longs(7, -2)), 1);
+    }
+
+    #[test]
+    fn shift_distances_are_masked() {
+        assert_eq!(call_int("Arithmetic", "ishl", "(II)I", ints(1, 33)), 2);
+        assert_eq!(call_int("Arithmetic", "ishl", "(II)I", ints(1, -1)), i32::MIN);
+        assert_eq!(call_int("Arithmetic", "ishr", "(II)I", ints(-16, 34)), -4);
+        assert_eq!(call_int("Arithmetic", "iushr", "(II)I", ints(-1, 60)), 0xf);
+
+        assert_eq!(call_long("Arithmetic", "lshl", "(JI)J", long_and_int(1, 65)), 2);
+        assert_eq!(call_long("Arithmetic", "lshl", "(JI)J", long_and_int(1, 63)), i64::MIN);
+        assert_eq!(call_long("Arithmetic", "lshr", "(JI)J", long_and_int(-16, 66)), -4);
+        assert_eq!(call_long("Arithmetic", "lushr", "(JI)J", long_and_int(-1, 124)), 0xf);
+    }
+
+    #[test]
+    fn division_by_zero_throws() {
+        assert_arithmetic_exception(call("Arithmetic", "idiv", "(II)I", ints(1, 0)));
+        assert_arithmetic_exception(call("Arithmetic", "irem", "(II)I", ints(1, 0)));
+        assert_arithmetic_exception(call("Arithmetic", "ldiv", "(JJ)J", longs(1, 0)));
+        assert_arithmetic_exception(call("Arithmetic", "lrem", "(JJ)J", longs(1, 0)));
+    }
+
+    #[test]
+    fn floating_to_integral_conversions_saturate() {
+        for (value, expected) in [(f32::NAN, 0), (f32::INFINITY, i32::MAX), (f32::NEG_INFINITY, i32::MIN), (3e10, i32::MAX), (-3e10, i32::MIN), (-2.9, -2)] {
+            assert_eq!(call_int("Conversions", "f2i", "(F)I", vec![Value::Float(value)]), expected, "f2i {}", value);
+        }
+        for (value, expected) in [(f64::NAN, 0), (f64::INFINITY, i32::MAX), (f64::NEG_INFINITY, i32::MIN), (1e10, i32::MAX), (-1e10, i32::MIN), (2.9, 2)] {
+            assert_eq!(call_int("Conversions", "d2i", "(D)I", vec![Value::Double(value)]), expected, "d2i {}", value);
+        }
+        for (value, expected) in [(f32::NAN, 0), (f32::INFINITY, i64::MAX), (f32::NEG_INFINITY, i64::MIN), (1e19, i64::MAX), (-1e19, i64::MIN), (3e10, 30_000_001_024)] {
+            assert_eq!(call_long("Conversions", "f2l", "(F)J", vec![Value::Float(value)]), expected, "f2l {}", value);
+        }
+        for (value, expected) in [(f64::NAN, 0), (f64::INFINITY, i64::MAX), (f64::NEG_INFINITY, i64::MIN), (1e19, i64::MAX), (-1e19, i64::MIN), (-1e10, -10_000_000_000)] {
+            assert_eq!(call_long("Conversions", "d2l", "(D)J", vec![Value::Double(value)]), expected, "d2l {}", value);
+        }
+    }
+
+    #[test]
+    fn narrowing_int_conversions_extend() {
+        // byte and short are sign extended, char is zero extended
+        assert_eq!(call_int("Conversions", "i2b", "(I)I", vec![Value::Int(0x80)]), -128);
+        assert_eq!(call_int("Conversions", "i2b", "(I)I", vec![Value::Int(0x17f)]), 0x7f);
+        assert_eq!(call_int("Conversions", "i2c", "(I)I", vec![Value::Int(-1)]), 0xffff);
+        assert_eq!(call_int("Conversions", "i2c", "(I)I", vec![Value::Int(0x1_0041)]), 0x41);
+        assert_eq!(call_int("Conversions", "i2s", "(I)I", vec![Value::Int(0x8000)]), -32768);
+        assert_eq!(call_int("Conversions", "i2s", "(I)I", vec![Value::Int(0x1_7fff)]), 0x7fff);
+    }
+
+    #[test]
+    fn switches_jump_to_the_matching_case() {
+        for (value, expected) in [(-1, 10), (0, 20), (1, 30), (2, 40), (-2, -1), (3, -1), (i32::MIN, -1), (i32::MAX, -1)] {
+            assert_eq!(call_int("Switches", "table", "(I)I", vec![Value::Int(value)]), expected, "table {}", value);
+        }
+        for (value, expected) in [(-1000, 10), (7, 20), (100000, 30), (0, -1), (8, -1), (i32::MIN, -1), (i32::MAX, -1)] {
+            assert_eq!(call_int("Switches", "lookup", "(I)I", vec![Value::Int(value)]), expected, "lookup {}", value);
+        }
+    }
+
+    #[test]
+    fn invalid_code_fails_only_when_its_method_runs() {
+        // Arithmetic with the idiv in idiv replaced by an unknown opcode
+        let mut bytes = std::fs::read(format!("{}/Arithmetic.class", FIXTURES)).unwrap();
+        let idiv = [OP_CODE_ILOAD_0, OP_CODE_ILOAD_1, OP_CODE_IDIV, OP_CODE_IRETURN];
+        let position = bytes.windows(idiv.len()).position(|window| window == idiv).unwrap();
+        bytes[position + 2] = OP_CODE_IMPDEP2;
+
+        let directory = std::env::temp_dir().join(format!("jvm-invalid-code-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("Arithmetic.class"), bytes).unwrap();
+        let class_path = directory.to_str().unwrap();
+
+        assert!(matches!(call_in(class_path, "Arithmetic", "irem", "(II)I", ints(7, 4)), Ok(Some(Value::Int(3)))));
+        assert!(matches!(call_in(class_path, "Arithmetic", "idiv", "(II)I", ints(7, 4)),
+            Err(JvmError::InvalidInstruction { opcode: OP_CODE_IMPDEP2, pc: 2 })));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    // A Code attribute holding a single return and the given exception table
+    fn code_with_handlers(entries: &[[u16; 4]]) -> CodeAttribute {
+        let mut bytes: Vec<u8> = vec![0, 1, 0, 1, 0, 0, 0, 1, OP_CODE_RETURN];
+        bytes.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+        for value in entries.iter().flatten() {
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+        bytes.extend_from_slice(&[0, 0]);
+        let length = bytes.len() as u64;
+
+        let mut cursor = Cursor::new(bytes);
+        let code = CodeAttribute::new(&mut cursor, &[], 52).unwrap();
+        assert_eq!(cursor.position(), length);
+        code
+    }
+
+    #[test]
+    fn exception_table_entries_are_parsed() {
+        let code = code_with_handlers(&[[0, 10, 20, 3], [2, 5, 30, 0]]);
+        let entries: Vec<_> = code.exception_table.iter()
+            .map(|entry| (entry.start_pc, entry.end_pc, entry.handler_pc, entry.catch_type))
+            .collect();
+        assert_eq!(entries, [(0, 10, 20, 3), (2, 5, 30, 0)]);
+    }
+
+    #[test]
+    fn handlers_are_searched_in_table_order() {
+        // An inner handler for class #2 listed before an outer one for #1, then a finally
+        let code = code_with_handlers(&[[2, 5, 30, 2], [0, 10, 20, 1], [0, 12, 40, 0]]);
+        let handler = |pc: u32, caught: &[u16]| {
+            code.find_handler(pc, |catch_type| Ok(caught.contains(&catch_type))).unwrap().map(|entry| entry.handler_pc)
+        };
+
+        assert_eq!(handler(3, &[1, 2]), Some(30));
+        assert_eq!(handler(3, &[1]), Some(20));
+        assert_eq!(handler(3, &[]), Some(40));
+        // end_pc is not covered
+        assert_eq!(handler(5, &[2]), Some(40));
+        assert_eq!(handler(10, &[1]), Some(40));
+        assert_eq!(handler(12, &[1, 2]), None);
+        assert_eq!(handler(0, &[1]), Some(20));
+
+        let result = code.find_handler(3, |_| Err(JvmError::ClassNotFound { name: String::from("Missing") }));
+        assert!(matches!(result, Err(JvmError::ClassNotFound { .. })));
+    }
+
+    fn assert_uncaught(result: JvmResult<Option<Value>>, expected_class: &str, expected_message: Option<&str>) {
+        match result {
+            Err(JvmError::UncaughtException { class_name, message, .. }) => {
+                assert_eq!(class_name, expected_class);
+                assert_eq!(message.as_deref(), expected_message);
+            }
+            result => panic!("expected an uncaught {}, got {:?}", expected_class, result)
         }
     }
 
-    unreachable!("execute_code should exit in OP_CODE_RETURN")
+    #[test]
+    fn exceptions_are_caught_by_the_innermost_matching_handler() {
+        assert_eq!(call_int("Handlers", "nested", "(II)I", ints(6, 3)), 2);
+        assert_eq!(call_int("Handlers", "nested", "(II)I", ints(1, 0)), -2);
+        assert_eq!(call_int("Handlers", "bySuperclass", "(II)I", ints(1, 0)), -3);
+    }
+
+    #[test]
+    fn finally_runs_before_the_exception_leaves_the_method() {
+        assert_eq!(call_int("Handlers", "finallyThenCatch", "(I)I", vec![Value::Int(1)]), 1);
+        assert_eq!(call_int("Handlers", "finallyThenCatch", "(I)I", vec![Value::Int(0)]), 101);
+        assert_uncaught(call("Handlers", "divideLogged", "(II)I", ints(1, 0)), "java/lang/ArithmeticException", Some("/ by zero"));
+    }
+
+    #[test]
+    fn instructions_after_the_try_block_are_not_covered() {
+        assert_eq!(call_int("Handlers", "afterTry", "(II)I", ints(6, 3)), 2);
+        assert_uncaught(call("Handlers", "afterTry", "(II)I", ints(1, 0)), "java/lang/ArithmeticException", Some("/ by zero"));
+    }
+
+    #[test]
+    fn user_exceptions_are_thrown_and_caught() {
+        assert_eq!(call_int("Handlers", "userException", "(I)I", vec![Value::Int(5)]), 5);
+        assert_eq!(call_int("Handlers", "userException", "(I)I", vec![Value::Int(-5)]), -4);
+
+        let message = Value::Reference(Reference::String(Rc::from("bad")));
+        assert_uncaught(call("Handlers", "fail", "(Ljava/lang/String;)V", vec![message]), "Handlers$Failure", Some("bad"));
+        assert_uncaught(call("Handlers", "rethrow", "(II)I", ints(1, 0)), "java/lang/ArithmeticException", Some("/ by zero"));
+    }
+
+    #[test]
+    fn exceptions_from_static_initializers_are_wrapped_unless_they_are_errors() {
+        let loader = ClassLoader::new(ClassLoader::parse_class_path(FIXTURES));
+        let class = loader.load_class("Initializers").unwrap();
+        let read = |name: &str| execute_method(&loader, &class, class.class_file.find_method(name, "()I").unwrap(), Vec::new());
+
+        match read("readException") {
+            Err(JvmError::ExceptionInInitializer { class_name, cause }) => {
+                assert_eq!(class_name, "Initializers$ThrowsException");
+                assert!(matches!(*cause, JvmError::UncaughtException { ref class_name, message: Some(ref message), .. }
+                    if class_name == "Initializers$Failure" && message == "from <clinit>"));
+            }
+            result => panic!("expected an ExceptionInInitializerError, got {:?}", result)
+        }
+        // The class can't be initialized again
+        assert_uncaught(read("readException"), "java/lang/NoClassDefFoundError", Some("Could not initialize class Initializers$ThrowsException"));
+
+        assert_uncaught(read("readPlatformError"), "java/lang/UnsatisfiedLinkError", Some("Initializers$ThrowsPlatformError.link"));
+        assert_uncaught(read("readUserError"), "Initializers$Broken", None);
+    }
 }
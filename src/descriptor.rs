@@ -0,0 +1,326 @@
+use std::fmt;
+use crate::error::{JvmError, JvmResult};
+
+// Field and method descriptors (JVMS 4.3)
+
+// An array type can have at most 255 dimensions (JVMS 4.3.2)
+pub const MAX_ARRAY_DIMENSIONS: usize = 255;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+    // Binary name in internal form, e.g. java/lang/String
+    Object(String),
+    Array(Box<FieldType>)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodDescriptor {
+    pub parameters: Vec<FieldType>,
+    // None for void
+    pub return_type: Option<FieldType>
+}
+
+struct DescriptorParser<'a> {
+    descriptor: &'a str,
+    position: usize
+}
+
+impl<'a> DescriptorParser<'a> {
+    fn error(&self) -> JvmError {
+        JvmError::InvalidDescriptor { descriptor: self.descriptor.to_string(), position: self.position }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.descriptor.as_bytes().get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> JvmResult<()> {
+        if self.peek() != Some(byte) {
+            return Err(self.error());
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn field_type(&mut self) -> JvmResult<FieldType> {
+        let field_type = match self.peek() {
+            Some(b'B') => FieldType::Byte,
+            Some(b'C') => FieldType::Char,
+            Some(b'D') => FieldType::Double,
+            Some(b'F') => FieldType::Float,
+            Some(b'I') => FieldType::Int,
+            Some(b'J') => FieldType::Long,
+            Some(b'S') => FieldType::Short,
+            Some(b'Z') => FieldType::Boolean,
+            Some(b'L') => {
+                let start = self.position + 1;
+                let length = self.descriptor[start..].find(';').ok_or_else(|| self.error())?;
+                if length == 0 {
+                    return Err(self.error());
+                }
+                self.position = start + length + 1;
+                return Ok(FieldType::Object(self.descriptor[start..start + length].to_string()));
+            }
+            Some(b'[') => {
+                // Counted up front instead of recursing, so a long run of [ can't overflow the stack
+                let start = self.position;
+                while self.peek() == Some(b'[') {
+                    if self.position - start == MAX_ARRAY_DIMENSIONS {
+                        return Err(self.error());
+                    }
+                    self.position += 1;
+                }
+                let dimensions = self.position - start;
+                let mut field_type = self.field_type()?;
+                for _ in 0..dimensions {
+                    field_type = FieldType::Array(Box::new(field_type));
+                }
+                return Ok(field_type);
+            }
+            _ => return Err(self.error())
+        };
+
+        self.position += 1;
+        Ok(field_type)
+    }
+
+    fn finish(&self) -> JvmResult<()> {
+        if self.position != self.descriptor.len() {
+            return Err(self.error());
+        }
+        Ok(())
+    }
+}
+
+impl FieldType {
+    pub fn parse(descriptor: &str) -> JvmResult<FieldType> {
+        let mut parser = DescriptorParser { descriptor, position: 0 };
+        let field_type = parser.field_type()?;
+        parser.finish()?;
+        Ok(field_type)
+    }
+
+    /// Number of local variable or operand stack slots a value of this type takes
+    pub fn slots(&self) -> usize {
+        match self {
+            FieldType::Long | FieldType::Double => 2,
+            _ => 1
+        }
+    }
+
+    /// Component type for the atype operand of newarray
+    pub fn from_array_type(atype: u8) -> Option<FieldType> {
+        match atype {
+            4 => Some(FieldType::Boolean),
+            5 => Some(FieldType::Char),
+            6 => Some(FieldType::Float),
+            7 => Some(FieldType::Double),
+            8 => Some(FieldType::Byte),
+            9 => Some(FieldType::Short),
+            10 => Some(FieldType::Int),
+            11 => Some(FieldType::Long),
+            _ => None
+        }
+    }
+
+    pub fn is_reference(&self) -> bool {
+        matches!(self, FieldType::Object(_) | FieldType::Array(_))
+    }
+
+    /// The type as written in Java source, e.g. java.lang.String[]
+    pub fn java_name(&self) -> String {
+        match self {
+            FieldType::Byte => String::from("byte"),
+            FieldType::Char => String::from("char"),
+            FieldType::Double => String::from("double"),
+            FieldType::Float => String::from("float"),
+            FieldType::Int => String::from("int"),
+            FieldType::Long => String::from("long"),
+            FieldType::Short => String::from("short"),
+            FieldType::Boolean => String::from("boolean"),
+            FieldType::Object(class_name) => class_name.replace('/', "."),
+            FieldType::Array(component) => format!("{}[]", component.java_name())
+        }
+    }
+}
+
+impl MethodDescriptor {
+    pub fn parse(descriptor: &str) -> JvmResult<MethodDescriptor> {
+        let mut parser = DescriptorParser { descriptor, position: 0 };
+        parser.expect(b'(')?;
+
+        let mut parameters: Vec<FieldType> = Vec::new();
+        while parser.peek() != Some(b')') {
+            parameters.push(parser.field_type()?);
+        }
+        parser.expect(b')')?;
+
+        let return_type = if parser.peek() == Some(b'V') {
+            parser.position += 1;
+            None
+        } else {
+            Some(parser.field_type()?)
+        };
+        parser.finish()?;
+
+        Ok(MethodDescriptor { parameters, return_type })
+    }
+
+    /// Number of local variable slots the parameters take, not counting `this`
+    pub fn parameter_slots(&self) -> usize {
+        self.parameters.iter().map(|parameter| parameter.slots()).sum()
+    }
+
+    pub fn return_slots(&self) -> usize {
+        self.return_type.as_ref().map_or(0, |return_type| return_type.slots())
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldType::Byte => write!(f, "B"),
+            FieldType::Char => write!(f, "C"),
+            FieldType::Double => write!(f, "D"),
+            FieldType::Float => write!(f, "F"),
+            FieldType::Int => write!(f, "I"),
+            FieldType::Long => write!(f, "J"),
+            FieldType::Short => write!(f, "S"),
+            FieldType::Boolean => write!(f, "Z"),
+            FieldType::Object(class_name) => write!(f, "L{};", class_name),
+            FieldType::Array(component) => write!(f, "[{}", component)
+        }
+    }
+}
+
+impl fmt::Display for MethodDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for parameter in &self.parameters {
+            write!(f, "{}", parameter)?;
+        }
+        write!(f, ")")?;
+        match &self.return_type {
+            Some(return_type) => write!(f, "{}", return_type),
+            None => write!(f, "V")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(class_name: &str) -> FieldType {
+        FieldType::Object(class_name.to_string())
+    }
+
+    fn array(component: FieldType) -> FieldType {
+        FieldType::Array(Box::new(component))
+    }
+
+    fn error_position(result: JvmResult<impl fmt::Debug>) -> usize {
+        match result {
+            Err(JvmError::InvalidDescriptor { position, .. }) => position,
+            other => panic!("expected InvalidDescriptor, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn field_types() {
+        assert_eq!(FieldType::parse("I").unwrap(), FieldType::Int);
+        assert_eq!(FieldType::parse("Ljava/lang/String;").unwrap(), object("java/lang/String"));
+        assert_eq!(FieldType::parse("[[J").unwrap(), array(array(FieldType::Long)));
+        assert_eq!(FieldType::parse("[Ljava/util/Map$Entry;").unwrap(), array(object("java/util/Map$Entry")));
+    }
+
+    #[test]
+    fn method_descriptors() {
+        let descriptor = MethodDescriptor::parse("(IDLjava/lang/Thread;[Z)Ljava/lang/Object;").unwrap();
+        assert_eq!(descriptor.parameters, vec![FieldType::Int, FieldType::Double, object("java/lang/Thread"), array(FieldType::Boolean)]);
+        assert_eq!(descriptor.return_type, Some(object("java/lang/Object")));
+
+        let descriptor = MethodDescriptor::parse("()V").unwrap();
+        assert!(descriptor.parameters.is_empty());
+        assert_eq!(descriptor.return_type, None);
+    }
+
+    #[test]
+    fn display_gives_back_the_descriptor() {
+        for descriptor in ["B", "[[Ljava/lang/String;", "Ljava/util/Map$Entry;"] {
+            assert_eq!(FieldType::parse(descriptor).unwrap().to_string(), descriptor);
+        }
+        for descriptor in ["()V", "(IJ[D)Ljava/lang/String;", "([[Ljava/lang/Object;C)[S"] {
+            assert_eq!(MethodDescriptor::parse(descriptor).unwrap().to_string(), descriptor);
+        }
+    }
+
+    #[test]
+    fn long_and_double_take_two_slots() {
+        assert_eq!(FieldType::Long.slots(), 2);
+        assert_eq!(FieldType::Double.slots(), 2);
+        assert_eq!(FieldType::Int.slots(), 1);
+        assert_eq!(array(FieldType::Long).slots(), 1);
+
+        let descriptor = MethodDescriptor::parse("(IJLjava/lang/String;D[J)D").unwrap();
+        assert_eq!(descriptor.parameter_slots(), 7);
+        assert_eq!(descriptor.return_slots(), 2);
+
+        let descriptor = MethodDescriptor::parse("()V").unwrap();
+        assert_eq!(descriptor.parameter_slots(), 0);
+        assert_eq!(descriptor.return_slots(), 0);
+        assert_eq!(MethodDescriptor::parse("(Z)Ljava/lang/Object;").unwrap().return_slots(), 1);
+    }
+
+    #[test]
+    fn java_names() {
+        assert_eq!(FieldType::parse("[[I").unwrap().java_name(), "int[][]");
+        assert_eq!(FieldType::parse("[Ljava/lang/String;").unwrap().java_name(), "java.lang.String[]");
+    }
+
+    #[test]
+    fn invalid_field_types() {
+        assert_eq!(error_position(FieldType::parse("")), 0);
+        assert_eq!(error_position(FieldType::parse("V")), 0);
+        assert_eq!(error_position(FieldType::parse("X")), 0);
+        assert_eq!(error_position(FieldType::parse("L;")), 0);
+        assert_eq!(error_position(FieldType::parse("Ljava/lang/String")), 0);
+        assert_eq!(error_position(FieldType::parse("[")), 1);
+        assert_eq!(error_position(FieldType::parse("II")), 1);
+    }
+
+    #[test]
+    fn invalid_method_descriptors() {
+        assert_eq!(error_position(MethodDescriptor::parse("V")), 0);
+        assert_eq!(error_position(MethodDescriptor::parse("(V)V")), 1);
+        assert_eq!(error_position(MethodDescriptor::parse("(I")), 2);
+        assert_eq!(error_position(MethodDescriptor::parse("()")), 2);
+        assert_eq!(error_position(MethodDescriptor::parse("()VV")), 3);
+    }
+
+    #[test]
+    fn array_dimensions_are_limited() {
+        let deepest = format!("{}I", "[".repeat(MAX_ARRAY_DIMENSIONS));
+        let mut field_type = FieldType::parse(&deepest).unwrap();
+        for _ in 0..MAX_ARRAY_DIMENSIONS {
+            field_type = match field_type {
+                FieldType::Array(component) => *component,
+                other => panic!("expected an array, got {:?}", other)
+            };
+        }
+        assert_eq!(field_type, FieldType::Int);
+
+        let too_deep = format!("{}I", "[".repeat(MAX_ARRAY_DIMENSIONS + 1));
+        assert_eq!(error_position(FieldType::parse(&too_deep)), MAX_ARRAY_DIMENSIONS);
+        // Far too deep to parse by recursing
+        assert_eq!(error_position(FieldType::parse(&"[".repeat(1_000_000))), MAX_ARRAY_DIMENSIONS);
+    }
+}
@@ -0,0 +1,780 @@
+use crate::attributes::{Attribute, RecordComponent};
+use crate::bytecode::Op;
+use crate::class_file::*;
+use crate::code::CodeAttribute;
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::error::{JvmError, JvmResult};
+use crate::opcodes::mnemonic;
+use crate::signature::{ClassSignature, ClassTypeSignature, TypeParameter, TypeSignature};
+use crate::value::{double_to_string, float_to_string};
+
+// Prints a class file in roughly the same format as `javap -c -v`
+
+// Appends a line to the output, writing to a String can't fail
+macro_rules! emit {
+    ($out:expr) => { $out.push('\n') };
+    ($out:expr, $($arg:tt)*) => {{ $out.push_str(&format!($($arg)*)); $out.push('\n'); }};
+}
+
+const CLASS_FLAGS: [(u16, &str); 9] = [
+    (ClassAccessFlags::PUBLIC as u16, "ACC_PUBLIC"),
+    (ClassAccessFlags::FINAL as u16, "ACC_FINAL"),
+    (ClassAccessFlags::SUPER as u16, "ACC_SUPER"),
+    (ClassAccessFlags::INTERFACE as u16, "ACC_INTERFACE"),
+    (ClassAccessFlags::ABSTRACT as u16, "ACC_ABSTRACT"),
+    (ClassAccessFlags::SYNTHETIC as u16, "ACC_SYNTHETIC"),
+    (ClassAccessFlags::ANNOTATION as u16, "ACC_ANNOTATION"),
+    (ClassAccessFlags::ENUM as u16, "ACC_ENUM"),
+    (ClassAccessFlags::MODULE as u16, "ACC_MODULE"),
+];
+
+const FIELD_FLAGS: [(u16, &str); 9] = [
+    (FieldsAccessFlags::PUBLIC as u16, "ACC_PUBLIC"),
+    (FieldsAccessFlags::PRIVATE as u16, "ACC_PRIVATE"),
+    (FieldsAccessFlags::PROTECTED as u16, "ACC_PROTECTED"),
+    (FieldsAccessFlags::STATIC as u16, "ACC_STATIC"),
+    (FieldsAccessFlags::FINAL as u16, "ACC_FINAL"),
+    (FieldsAccessFlags::VOLATILE as u16, "ACC_VOLATILE"),
+    (FieldsAccessFlags::TRANSIENT as u16, "ACC_TRANSIENT"),
+    (FieldsAccessFlags::SYNTHETIC as u16, "ACC_SYNTHETIC"),
+    (FieldsAccessFlags::ENUM as u16, "ACC_ENUM"),
+];
+
+const METHOD_FLAGS: [(u16, &str); 12] = [
+    (MethodsAccessFlags::PUBLIC as u16, "ACC_PUBLIC"),
+    (MethodsAccessFlags::PRIVATE as u16, "ACC_PRIVATE"),
+    (MethodsAccessFlags::PROTECTED as u16, "ACC_PROTECTED"),
+    (MethodsAccessFlags::STATIC as u16, "ACC_STATIC"),
+    (MethodsAccessFlags::FINAL as u16, "ACC_FINAL"),
+    (MethodsAccessFlags::SYNCHRONIZED as u16, "ACC_SYNCHRONIZED"),
+    (MethodsAccessFlags::BRIDGE as u16, "ACC_BRIDGE"),
+    (MethodsAccessFlags::VARARGS as u16, "ACC_VARARGS"),
+    (MethodsAccessFlags::NATIVE as u16, "ACC_NATIVE"),
+    (MethodsAccessFlags::ABSTRACT as u16, "ACC_ABSTRACT"),
+    (MethodsAccessFlags::STRICT as u16, "ACC_STRICT"),
+    (MethodsAccessFlags::SYNTHETIC as u16, "ACC_SYNTHETIC"),
+];
+
+// Flags of MethodParameters entries, printed by name like modifiers
+const PARAMETER_FLAGS: [(u16, &str); 3] = [
+    (0x0010, "final"),
+    (0x1000, "synthetic"),
+    (0x8000, "mandated"),
+];
+
+// Source modifiers in the order javap prints them
+const MODIFIERS: [(u16, &str); 10] = [
+    (0x0001, "public"),
+    (0x0002, "private"),
+    (0x0004, "protected"),
+    (0x0008, "static"),
+    (0x0010, "final"),
+    (0x0020, "synchronized"),
+    (0x0040, "volatile"),
+    (0x0080, "transient"),
+    (0x0100, "native"),
+    (0x0400, "abstract"),
+];
+
+fn format_flags(access_flags: u16, table: &[(u16, &str)]) -> String {
+    let names: Vec<&str> = table.iter()
+        .filter(|(flag, _)| access_flags & flag != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    if names.is_empty() {
+        return format!("(0x{:04x})", access_flags);
+    }
+    format!("(0x{:04x}) {}", access_flags, names.join(", "))
+}
+
+// Only some of the modifiers apply to each kind of member, the rest share bits with unrelated flags
+fn format_modifiers(access_flags: u16, allowed: u16) -> String {
+    MODIFIERS.iter()
+        .filter(|(flag, _)| access_flags & allowed & flag != 0)
+        .map(|(_, name)| format!("{} ", name))
+        .collect()
+}
+
+// javap starts trailing comments 40 columns past the indentation of the line
+const COMMENT_COLUMN: usize = 40;
+
+fn with_comment(indent: usize, line: &str, comment: &str) -> String {
+    let column = indent + COMMENT_COLUMN;
+    if line.len() >= column {
+        return format!("{} // {}", line, comment);
+    }
+    format!("{:<column$}// {}", line, comment)
+}
+
+/// Escapes a string like javap, control characters become \uXXXX and unpaired surrogates
+/// can't be printed
+fn escape(units: &[u16]) -> String {
+    let mut escaped = String::with_capacity(units.len());
+    for c in char::decode_utf16(units.iter().copied()).map(|c| c.unwrap_or('?')) {
+        match c {
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\u{8}' => escaped.push_str("\\b"),
+            '\u{c}' => escaped.push_str("\\f"),
+            '"' => escaped.push_str("\\\""),
+            '\'' => escaped.push_str("\\'"),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+fn escaped_utf8(class_file: &ClassFile, index: u16) -> String {
+    match class_file.get_constant(index) {
+        Some(Constant::Utf8(utf8)) => escape(&utf8.utf16),
+        _ => String::from("<invalid>")
+    }
+}
+
+fn utf8(class_file: &ClassFile, index: u16) -> &str {
+    class_file.get_utf8(index).unwrap_or("<invalid>")
+}
+
+fn quote_special_name(name: &str) -> String {
+    if name.starts_with('<') {
+        return format!("\"{}\"", name);
+    }
+    name.to_string()
+}
+
+fn type_parameters(type_parameters: &[TypeParameter]) -> String {
+    if type_parameters.is_empty() {
+        return String::new();
+    }
+    let names: Vec<String> = type_parameters.iter().map(|parameter| parameter.to_string()).collect();
+    format!("<{}>", names.join(", "))
+}
+
+fn class_name(class_file: &ClassFile, index: u16) -> String {
+    match class_file.get_constant(index) {
+        Some(Constant::Class(class)) => {
+            let name = utf8(class_file, class.name_index);
+            // Array classes are quoted by javap
+            if name.starts_with('[') { format!("\"{}\"", name) } else { name.to_string() }
+        }
+        _ => String::from("<invalid>")
+    }
+}
+
+fn name_and_type(class_file: &ClassFile, index: u16) -> String {
+    match class_file.get_constant(index) {
+        Some(Constant::NameAndType(nat)) =>
+            format!("{}:{}", quote_special_name(utf8(class_file, nat.name_index)), utf8(class_file, nat.descriptor_index)),
+        _ => String::from("<invalid>")
+    }
+}
+
+fn member_ref(class_file: &ClassFile, class_index: u16, name_and_type_index: u16) -> String {
+    format!("{}.{}", class_name(class_file, class_index), name_and_type(class_file, name_and_type_index))
+}
+
+fn reference_kind_name(kind: u8) -> &'static str {
+    match kind {
+        REF_GET_FIELD => "REF_getField",
+        REF_GET_STATIC => "REF_getStatic",
+        REF_PUT_FIELD => "REF_putField",
+        REF_PUT_STATIC => "REF_putStatic",
+        REF_INVOKE_VIRTUAL => "REF_invokeVirtual",
+        REF_INVOKE_STATIC => "REF_invokeStatic",
+        REF_INVOKE_SPECIAL => "REF_invokeSpecial",
+        REF_NEW_INVOKE_SPECIAL => "REF_newInvokeSpecial",
+        REF_INVOKE_INTERFACE => "REF_invokeInterface",
+        _ => "REF_unknown"
+    }
+}
+
+fn constant_kind(constant: &Constant) -> &'static str {
+    match constant {
+        Constant::Utf8(_) => "Utf8",
+        Constant::Integer(_) => "Integer",
+        Constant::Float(_) => "Float",
+        Constant::Long(_) => "Long",
+        Constant::Double(_) => "Double",
+        Constant::Class(_) => "Class",
+        Constant::String(_) => "String",
+        Constant::FieldRef(_) => "Fieldref",
+        Constant::MethodRef(_) => "Methodref",
+        Constant::InterfaceMethodRef(_) => "InterfaceMethodref",
+        Constant::NameAndType(_) => "NameAndType",
+        Constant::MethodHandle(_) => "MethodHandle",
+        Constant::MethodType(_) => "MethodType",
+        Constant::Dynamic(_) => "Dynamic",
+        Constant::InvokeDynamic(_) => "InvokeDynamic",
+        Constant::Module(_) => "Module",
+        Constant::Package(_) => "Package",
+        Constant::Unusable => ""
+    }
+}
+
+/// The operands of a constant as they appear in the pool listing
+fn constant_operands(constant: &Constant) -> String {
+    match constant {
+        Constant::Utf8(utf8) => escape(&utf8.utf16),
+        Constant::Integer(integer) => (integer.value as i32).to_string(),
+        Constant::Float(float) => format!("{}f", float_to_string(float.value)),
+        Constant::Long(long) => format!("{}l", long.value as i64),
+        Constant::Double(double) => format!("{}d", double_to_string(double.value)),
+        Constant::Class(class) => format!("#{}", class.name_index),
+        Constant::String(string) => format!("#{}", string.string_index),
+        Constant::FieldRef(r) => format!("#{}.#{}", r.class_index, r.name_and_type_index),
+        Constant::MethodRef(r) => format!("#{}.#{}", r.class_index, r.name_and_type_index),
+        Constant::InterfaceMethodRef(r) => format!("#{}.#{}", r.class_index, r.name_and_type_index),
+        Constant::NameAndType(nat) => format!("#{}:#{}", nat.name_index, nat.descriptor_index),
+        Constant::MethodHandle(handle) => format!("{}:#{}", handle.reference_kind, handle.reference_index),
+        Constant::MethodType(method_type) => format!("#{}", method_type.descriptor_index),
+        Constant::Dynamic(dynamic) => format!("#{}:#{}", dynamic.bootstrap_method_attr_index, dynamic.name_and_type_index),
+        Constant::InvokeDynamic(dynamic) => format!("#{}:#{}", dynamic.bootstrap_method_attr_index, dynamic.name_and_type_index),
+        Constant::Module(module) => format!("#{}", module.name_index),
+        Constant::Package(package) => format!("#{}", package.name_index),
+        Constant::Unusable => String::new()
+    }
+}
+
+/// What a constant resolves to, used for the trailing comments
+fn resolve_constant(class_file: &ClassFile, index: u16) -> String {
+    let constant = match class_file.get_constant(index) {
+        Some(constant) => constant,
+        None => return String::from("<invalid>")
+    };
+
+    match constant {
+        Constant::Utf8(_) | Constant::Integer(_) | Constant::Float(_) | Constant::Long(_) | Constant::Double(_) =>
+            constant_operands(constant),
+        Constant::Class(_) => class_name(class_file, index),
+        Constant::String(string) => escaped_utf8(class_file, string.string_index),
+        Constant::FieldRef(r) => member_ref(class_file, r.class_index, r.name_and_type_index),
+        Constant::MethodRef(r) => member_ref(class_file, r.class_index, r.name_and_type_index),
+        Constant::InterfaceMethodRef(r) => member_ref(class_file, r.class_index, r.name_and_type_index),
+        Constant::NameAndType(_) => name_and_type(class_file, index),
+        // A handle always points at a member reference, which doesn't point at anything else
+        Constant::MethodHandle(handle) => match class_file.get_constant(handle.reference_index) {
+            Some(Constant::FieldRef(_) | Constant::MethodRef(_) | Constant::InterfaceMethodRef(_)) =>
+                format!("{} {}", reference_kind_name(handle.reference_kind), resolve_constant(class_file, handle.reference_index)),
+            _ => String::from("<invalid>")
+        },
+        Constant::MethodType(method_type) => utf8(class_file, method_type.descriptor_index).to_string(),
+        Constant::Dynamic(dynamic) =>
+            format!("#{}:{}", dynamic.bootstrap_method_attr_index, name_and_type(class_file, dynamic.name_and_type_index)),
+        Constant::InvokeDynamic(dynamic) =>
+            format!("#{}:{}", dynamic.bootstrap_method_attr_index, name_and_type(class_file, dynamic.name_and_type_index)),
+        Constant::Module(module) => utf8(class_file, module.name_index).to_string(),
+        Constant::Package(package) => utf8(class_file, package.name_index).to_string(),
+        Constant::Unusable => String::new()
+    }
+}
+
+/// The constant as referenced by an instruction, e.g. `Method java/io/PrintStream.println:(I)V`
+fn describe_constant(class_file: &ClassFile, index: u16) -> String {
+    let kind = match class_file.get_constant(index) {
+        Some(Constant::Integer(_)) => "int",
+        Some(Constant::Float(_)) => "float",
+        Some(Constant::Long(_)) => "long",
+        Some(Constant::Double(_)) => "double",
+        Some(Constant::Class(_)) => "class",
+        Some(Constant::String(_)) => "String",
+        Some(Constant::FieldRef(_)) => "Field",
+        Some(Constant::MethodRef(_)) => "Method",
+        Some(Constant::InterfaceMethodRef(_)) => "InterfaceMethod",
+        Some(constant) => constant_kind(constant),
+        None => ""
+    };
+    // Members of the class being disassembled are shown without the class name
+    let member = match class_file.get_constant(index) {
+        Some(Constant::FieldRef(r)) => Some((r.class_index, r.name_and_type_index)),
+        Some(Constant::MethodRef(r)) => Some((r.class_index, r.name_and_type_index)),
+        Some(Constant::InterfaceMethodRef(r)) => Some((r.class_index, r.name_and_type_index)),
+        _ => None
+    };
+    match member {
+        Some((class_index, name_and_type_index)) if class_index == class_file.this_class =>
+            format!("{} {}", kind, name_and_type(class_file, name_and_type_index)),
+        _ => format!("{} {}", kind, resolve_constant(class_file, index))
+    }
+}
+
+fn array_type_name(atype: u8) -> &'static str {
+    match atype {
+        4 => "boolean",
+        5 => "char",
+        6 => "float",
+        7 => "double",
+        8 => "byte",
+        9 => "short",
+        10 => "int",
+        11 => "long",
+        _ => "<invalid>"
+    }
+}
+
+fn write_constant_pool(out: &mut String, class_file: &ClassFile) {
+    emit!(out, "Constant pool:");
+    // Numbers are right aligned to the width of the largest one, pool_count
+    let width = (class_file.constants_pool.len() + 1).to_string().len() + 1;
+    for (i, constant) in class_file.constants_pool.iter().enumerate() {
+        if let Constant::Unusable = constant {
+            continue;
+        }
+
+        let index = (i + 1) as u16;
+        let line = format!("  {:>width$} = {:<18} {}", format!("#{}", index), constant_kind(constant), constant_operands(constant));
+        match constant {
+            Constant::Utf8(_) | Constant::Integer(_) | Constant::Float(_) | Constant::Long(_) | Constant::Double(_) =>
+                emit!(out, "{}", line),
+            _ => emit!(out, "{}", with_comment(2, &line, &resolve_constant(class_file, index)))
+        }
+    }
+}
+
+/// Code that does not decode is shown as bytes, 16 to a line after the pc of the first one
+fn write_raw_code(out: &mut String, code: &[u8], error: &JvmError) {
+    emit!(out, "      // {}", error);
+    for (line, bytes) in code.chunks(16).enumerate() {
+        let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        emit!(out, "{:>10}: {}", line * 16, bytes.join(" "));
+    }
+}
+
+fn write_code(out: &mut String, class_file: &ClassFile, code: &CodeAttribute, args_size: usize) -> JvmResult<()> {
+    emit!(out, "    Code:");
+    emit!(out, "      stack={}, locals={}, args_size={}", code.max_stack, code.max_locals, args_size);
+
+    let instructions = match code.instructions() {
+        Ok(instructions) => instructions,
+        Err(error) => {
+            write_raw_code(out, &code.code, &error);
+            &[]
+        }
+    };
+    for instruction in instructions {
+        let pc = instruction.pc;
+        let name = mnemonic(instruction.opcode).unwrap_or("<invalid>");
+        let name = if instruction.wide { format!("{}_w", name) } else { name.to_string() };
+        let prefix = format!("{:>10}: {:<13}", pc, name);
+        let with_constant = |operands: String, index: u16| {
+            with_comment(6, &format!("{} {}", prefix, operands), &describe_constant(class_file, index))
+        };
+
+        let line = match &instruction.op {
+            Op::BiPush(value) => format!("{} {}", prefix, value),
+            Op::SiPush(value) => format!("{} {}", prefix, value),
+            Op::Ldc(index) | Op::LdcW(index) | Op::Ldc2W(index) | Op::GetStatic(index) | Op::PutStatic(index) |
+            Op::GetField(index) | Op::PutField(index) | Op::InvokeVirtual(index) | Op::InvokeSpecial(index) |
+            Op::InvokeStatic(index) | Op::New(index) | Op::AnewArray(index) | Op::CheckCast(index) |
+            Op::InstanceOf(index) => with_constant(format!("#{}", index), *index),
+            Op::InvokeInterface { index, count } => with_constant(format!("#{},  {}", index, count), *index),
+            Op::InvokeDynamic(index) => with_constant(format!("#{},  0", index), *index),
+            Op::MultiAnewArray { index, dimensions } => with_constant(format!("#{},  {}", index, dimensions), *index),
+            Op::NewArray(atype) => format!("{}  {}", prefix, array_type_name(*atype)),
+            Op::Iload(index) | Op::Lload(index) | Op::Fload(index) | Op::Dload(index) | Op::Aload(index) |
+            Op::Istore(index) | Op::Lstore(index) | Op::Fstore(index) | Op::Dstore(index) | Op::Astore(index) |
+            Op::Ret(index) => format!("{} {}", prefix, index),
+            Op::Iinc { index, constant } => format!("{} {}, {}", prefix, index, constant),
+            Op::IfEq(offset) | Op::IfNe(offset) | Op::IfLt(offset) | Op::IfGe(offset) | Op::IfGt(offset) |
+            Op::IfLe(offset) | Op::IfIcmpEq(offset) | Op::IfIcmpNe(offset) | Op::IfIcmpLt(offset) |
+            Op::IfIcmpGe(offset) | Op::IfIcmpGt(offset) | Op::IfIcmpLe(offset) | Op::IfAcmpEq(offset) |
+            Op::IfAcmpNe(offset) | Op::Goto(offset) | Op::Jsr(offset) | Op::IfNull(offset) |
+            Op::IfNonNull(offset) | Op::GotoW(offset) | Op::JsrW(offset) =>
+                format!("{} {}", prefix, instruction.branch_target(*offset)),
+            Op::TableSwitch(table) => {
+                let mut text = format!("{} {{ // {} to {}", prefix, table.low, table.high);
+                for (i, offset) in table.offsets.iter().enumerate() {
+                    text.push_str(&format!("\n{:>24}: {}", table.low as i64 + i as i64, instruction.branch_target(*offset)));
+                }
+                text.push_str(&format!("\n{:>24}: {}\n            }}", "default", instruction.branch_target(table.default)));
+                text
+            }
+            Op::LookupSwitch(lookup) => {
+                let mut text = format!("{} {{ // {}", prefix, lookup.pairs.len());
+                for (value, offset) in &lookup.pairs {
+                    text.push_str(&format!("\n{:>24}: {}", value, instruction.branch_target(*offset)));
+                }
+                text.push_str(&format!("\n{:>24}: {}\n            }}", "default", instruction.branch_target(lookup.default)));
+                text
+            }
+            _ => prefix.trim_end().to_string()
+        };
+        emit!(out, "{}", line);
+    }
+
+    if !code.exception_table.is_empty() {
+        emit!(out, "      Exception table:");
+        emit!(out, "         from    to  target type");
+        for entry in &code.exception_table {
+            let catch_type = if entry.catch_type == 0 {
+                String::from("any")
+            } else {
+                format!("Class {}", class_name(class_file, entry.catch_type))
+            };
+            emit!(out, "         {:>5} {:>5} {:>5}   {}", entry.start_pc, entry.end_pc, entry.handler_pc, catch_type);
+        }
+    }
+
+    for attribute in &code.attributes {
+        match attribute {
+            Attribute::LineNumberTable(table) => {
+                emit!(out, "      LineNumberTable:");
+                for line in &table.line_number_table {
+                    emit!(out, "        line {}: {}", line.line_number, line.start_pc);
+                }
+            }
+            Attribute::LocalVariableTable(table) => {
+                emit!(out, "      LocalVariableTable:");
+                emit!(out, "        Start  Length  Slot  Name   Signature");
+                for variable in &table.local_variable_table {
+                    emit!(out, "        {:>5} {:>7} {:>5} {:>5}   {}", variable.start_pc, variable.length, variable.index,
+                        utf8(class_file, variable.name_index), utf8(class_file, variable.descriptor_index));
+                }
+            }
+            Attribute::LocalVariableTypeTable(table) => {
+                emit!(out, "      LocalVariableTypeTable:");
+                emit!(out, "        Start  Length  Slot  Name   Signature");
+                for variable in &table.local_variable_type_table {
+                    emit!(out, "        {:>5} {:>7} {:>5} {:>5}   {}", variable.start_pc, variable.length, variable.index,
+                        utf8(class_file, variable.name_index), utf8(class_file, variable.signature_index));
+                }
+            }
+            // Lined up with the other attributes of the Code attribute
+            Attribute::Unknown(info) =>
+                write_unknown_attribute(out, 4, attribute.name(&class_file.constants_pool), &info.info),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Attributes javap doesn't know are dumped as hex, 16 bytes per line
+fn write_unknown_attribute(out: &mut String, indent: usize, name: &str, info: &[u8]) {
+    emit!(out, "{:indent$}  {}: length = 0x{:X} (unknown attribute)", "", name, info.len());
+    for chunk in info.chunks(16) {
+        let bytes: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+        emit!(out, "{:indent$}   {}", "", bytes.join(" "));
+    }
+}
+
+/// The attributes every kind of member can have, printed the same way wherever they appear
+fn write_common_attribute(out: &mut String, indent: usize, class_file: &ClassFile, attribute: &Attribute) {
+    match attribute {
+        Attribute::Signature(signature) => emit!(out, "{}", with_comment(indent,
+            &format!("{:indent$}Signature: #{}", "", signature.signature_index), utf8(class_file, signature.signature_index))),
+        Attribute::Deprecated => emit!(out, "{:indent$}Deprecated: true", ""),
+        Attribute::Synthetic => emit!(out, "{:indent$}Synthetic: true", ""),
+        Attribute::Unknown(info) => write_unknown_attribute(out, indent, attribute.name(&class_file.constants_pool), &info.info),
+        _ => {}
+    }
+}
+
+/// The type of a field or record component, from its generic signature when it has one
+fn field_type_name(descriptor: &str, signature: Option<TypeSignature>) -> String {
+    match signature {
+        Some(signature) => signature.to_string(),
+        None => FieldType::parse(descriptor).map(|field_type| field_type.java_name()).unwrap_or_default()
+    }
+}
+
+fn write_field(out: &mut String, class_file: &ClassFile, field: &FieldInfo) -> JvmResult<()> {
+    let descriptor = utf8(class_file, field.descriptor_index);
+    let type_name = field_type_name(descriptor, class_file.field_signature(field)?);
+    emit!(out, "  {}{} {};", format_modifiers(field.access_flags, 0x00DF), type_name, utf8(class_file, field.name_index));
+    emit!(out, "    descriptor: {}", descriptor);
+    emit!(out, "    flags: {}", format_flags(field.access_flags, &FIELD_FLAGS));
+
+    for attribute in &field.attributes {
+        match attribute {
+            Attribute::ConstantValue(value) =>
+                emit!(out, "    ConstantValue: {}", describe_constant(class_file, value.constant_value_index)),
+            _ => write_common_attribute(out, 4, class_file, attribute)
+        }
+    }
+
+    Ok(())
+}
+
+fn write_record_component(out: &mut String, class_file: &ClassFile, component: &RecordComponent) -> JvmResult<()> {
+    let descriptor = utf8(class_file, component.descriptor_index);
+    let signature = component.attributes.iter().find_map(|attribute| match attribute {
+        Attribute::Signature(signature) => Some(utf8(class_file, signature.signature_index)),
+        _ => None
+    });
+    let type_name = field_type_name(descriptor, signature.map(TypeSignature::parse_field).transpose()?);
+    emit!(out, "  {} {};", type_name, utf8(class_file, component.name_index));
+    emit!(out, "    descriptor: {}", descriptor);
+    for attribute in &component.attributes {
+        write_common_attribute(out, 4, class_file, attribute);
+    }
+    emit!(out);
+    Ok(())
+}
+
+fn write_method(out: &mut String, class_file: &ClassFile, method: &MethodInfo) -> JvmResult<()> {
+    let name = utf8(class_file, method.name_index);
+    let descriptor_string = utf8(class_file, method.descriptor_index);
+    let descriptor = MethodDescriptor::parse(descriptor_string)?;
+    let modifiers = format_modifiers(method.access_flags, 0x053F);
+
+    // The generic signature replaces the erased types from the descriptor
+    let signature = class_file.method_signature(method)?;
+    let (generics, parameters, return_type) = match &signature {
+        Some(signature) => (
+            type_parameters(&signature.type_parameters),
+            signature.parameters.iter().map(|parameter| parameter.to_string()).collect::<Vec<String>>(),
+            signature.return_type.as_ref().map_or(String::from("void"), |return_type| return_type.to_string())
+        ),
+        None => (
+            String::new(),
+            descriptor.parameters.iter().map(|parameter| parameter.java_name()).collect(),
+            descriptor.return_type.as_ref().map_or(String::from("void"), |return_type| return_type.java_name())
+        )
+    };
+    let generics = if generics.is_empty() { generics } else { format!("{} ", generics) };
+    let header = match name {
+        "<clinit>" => String::from("static {}"),
+        "<init>" => format!("{}{}{}({})", modifiers, generics, class_name(class_file, class_file.this_class).replace('/', "."), parameters.join(", ")),
+        _ => format!("{}{}{} {}({})", modifiers, generics, return_type, name, parameters.join(", "))
+    };
+    let throws: Vec<String> = match signature.filter(|signature| !signature.throws.is_empty()) {
+        Some(signature) => signature.throws.iter().map(|thrown| thrown.to_string()).collect(),
+        None => method.attributes.iter()
+            .filter_map(|attribute| match attribute {
+                Attribute::Exceptions(exceptions) => Some(exceptions),
+                _ => None
+            })
+            .flat_map(|exceptions| exceptions.exception_index_table.iter())
+            .map(|&index| class_name(class_file, index).replace('/', "."))
+            .collect()
+    };
+    if throws.is_empty() {
+        emit!(out, "  {};", header);
+    } else {
+        emit!(out, "  {} throws {};", header, throws.join(", "));
+    }
+
+    emit!(out, "    descriptor: {}", descriptor_string);
+    emit!(out, "    flags: {}", format_flags(method.access_flags, &METHOD_FLAGS));
+
+    let is_static = method.access_flags & MethodsAccessFlags::STATIC as u16 != 0;
+    // javap counts the parameters, not the local slots they take
+    let args_size = descriptor.parameters.len() + if is_static { 0 } else { 1 };
+    for attribute in &method.attributes {
+        match attribute {
+            Attribute::Code(code) => write_code(out, class_file, code, args_size)?,
+            Attribute::Exceptions(exceptions) => {
+                emit!(out, "    Exceptions:");
+                for &index in &exceptions.exception_index_table {
+                    emit!(out, "      throws {}", class_name(class_file, index).replace('/', "."));
+                }
+            }
+            Attribute::MethodParameters(method_parameters) => {
+                emit!(out, "    MethodParameters:");
+                emit!(out, "      Name                           Flags");
+                for parameter in &method_parameters.parameters {
+                    let name = if parameter.name_index == 0 { "<no name>" } else { utf8(class_file, parameter.name_index) };
+                    let flags: Vec<&str> = PARAMETER_FLAGS.iter()
+                        .filter(|(flag, _)| parameter.access_flags & flag != 0)
+                        .map(|(_, name)| *name)
+                        .collect();
+                    emit!(out, "{}", format!("      {:<31}{}", name, flags.join(" ")).trim_end());
+                }
+            }
+            _ => write_common_attribute(out, 4, class_file, attribute)
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the erased supertypes in the class signature are the ones in the class file, a
+/// signature that disagrees with them describes some other class and is not printed
+fn signature_matches(signature: &ClassSignature, class_file: &ClassFile) -> bool {
+    let super_class = class_file.super_class_name().unwrap_or("java/lang/Object");
+    let interfaces = class_file.interfaces.iter().map(|&index| class_name(class_file, index));
+    signature.super_class.binary_name() == super_class
+        && signature.super_interfaces.iter().map(ClassTypeSignature::binary_name).eq(interfaces)
+}
+
+pub fn disassemble(class_file: &ClassFile) -> JvmResult<String> {
+    let mut out = String::new();
+
+    let this_class = class_name(class_file, class_file.this_class);
+    let is_interface = class_file.access_flags & ClassAccessFlags::INTERFACE as u16 != 0;
+    let mut declaration = format!("{}{} {}",
+        format_modifiers(class_file.access_flags, if is_interface { 0x0001 } else { 0x0411 }),
+        if is_interface { "interface" } else { "class" },
+        this_class.replace('/', "."));
+    let implements = if is_interface { "extends" } else { "implements" };
+    match class_file.class_signature()?.filter(|signature| signature_matches(signature, class_file)) {
+        // Unlike the erased declaration this one names java.lang.Object as the superclass, and
+        // javap separates the interfaces with ", " rather than ","
+        Some(signature) => {
+            declaration.push_str(&type_parameters(&signature.type_parameters));
+            if !is_interface {
+                declaration.push_str(&format!(" extends {}", signature.super_class));
+            }
+            if !signature.super_interfaces.is_empty() {
+                let interfaces: Vec<String> = signature.super_interfaces.iter().map(|interface| interface.to_string()).collect();
+                declaration.push_str(&format!(" {} {}", implements, interfaces.join(", ")));
+            }
+        }
+        None => {
+            if class_file.super_class != 0 && !is_interface {
+                let super_class = class_name(class_file, class_file.super_class);
+                if super_class != "java/lang/Object" {
+                    declaration.push_str(&format!(" extends {}", super_class.replace('/', ".")));
+                }
+            }
+            if !class_file.interfaces.is_empty() {
+                let interfaces: Vec<String> = class_file.interfaces.iter()
+                    .map(|&index| class_name(class_file, index).replace('/', "."))
+                    .collect();
+                declaration.push_str(&format!(" {} {}", implements, interfaces.join(",")));
+            }
+        }
+    }
+
+    emit!(out, "{}", declaration);
+    emit!(out, "  minor version: {}", class_file.minor);
+    emit!(out, "  major version: {}", class_file.major);
+    emit!(out, "  flags: {}", format_flags(class_file.access_flags, &CLASS_FLAGS));
+    emit!(out, "{}", with_comment(2, &format!("  this_class: #{}", class_file.this_class), &this_class));
+    if class_file.super_class == 0 {
+        emit!(out, "  super_class: #0");
+    } else {
+        emit!(out, "{}", with_comment(2, &format!("  super_class: #{}", class_file.super_class), &class_name(class_file, class_file.super_class)));
+    }
+    emit!(out, "  interfaces: {}, fields: {}, methods: {}, attributes: {}",
+        class_file.interfaces.len(), class_file.fields.len(), class_file.methods.len(), class_file.attributes.len());
+
+    write_constant_pool(&mut out, class_file);
+
+    emit!(out, "{{");
+    // Members are separated by a blank line
+    for (i, field) in class_file.fields.iter().enumerate() {
+        if i > 0 {
+            emit!(out);
+        }
+        write_field(&mut out, class_file, field)?;
+    }
+    for (i, method) in class_file.methods.iter().enumerate() {
+        if i > 0 || !class_file.fields.is_empty() {
+            emit!(out);
+        }
+        write_method(&mut out, class_file, method)?;
+    }
+    emit!(out, "}}");
+
+    for attribute in &class_file.attributes {
+        match attribute {
+            Attribute::SourceFile(source_file) =>
+                emit!(out, "SourceFile: \"{}\"", utf8(class_file, source_file.source_file_index)),
+            Attribute::EnclosingMethod(enclosing) => {
+                let mut comment = class_name(class_file, enclosing.class_index).replace('/', ".");
+                if let Some(Constant::NameAndType(nat)) = class_file.get_constant(enclosing.method_index) {
+                    comment.push_str(&format!(".{}", utf8(class_file, nat.name_index)));
+                }
+                emit!(out, "{}", with_comment(0, &format!("EnclosingMethod: #{}.#{}", enclosing.class_index, enclosing.method_index), &comment));
+            }
+            Attribute::Record(record) => {
+                emit!(out, "Record:");
+                for component in &record.components {
+                    write_record_component(&mut out, class_file, component)?;
+                }
+            }
+            Attribute::NestHost(nest_host) =>
+                emit!(out, "NestHost: class {}", class_name(class_file, nest_host.host_class_index)),
+            Attribute::NestMembers(nest_members) => {
+                emit!(out, "NestMembers:");
+                for &index in &nest_members.classes {
+                    emit!(out, "  {}", class_name(class_file, index));
+                }
+            }
+            Attribute::PermittedSubclasses(permitted) => {
+                emit!(out, "PermittedSubclasses:");
+                for &index in &permitted.classes {
+                    emit!(out, "  {}", class_name(class_file, index));
+                }
+            }
+            Attribute::InnerClasses(inner_classes) => {
+                emit!(out, "InnerClasses:");
+                for inner in &inner_classes.classes {
+                    // Interfaces are always abstract, javap leaves it out for them
+                    let flags = inner.inner_class_access_flags;
+                    let allowed = if flags & ClassAccessFlags::INTERFACE as u16 != 0 { 0x001F } else { 0x041F };
+                    let mut line = format!("  {}", format_modifiers(flags, allowed));
+                    let mut comment = String::new();
+                    if inner.inner_name_index != 0 {
+                        line.push_str(&format!("#{}= ", inner.inner_name_index));
+                        comment.push_str(&format!("{}=", utf8(class_file, inner.inner_name_index)));
+                    }
+                    line.push_str(&format!("#{}", inner.inner_class_info_index));
+                    comment.push_str(&describe_constant(class_file, inner.inner_class_info_index));
+                    if inner.outer_class_info_index != 0 {
+                        line.push_str(&format!(" of #{}", inner.outer_class_info_index));
+                        comment.push_str(&format!(" of {}", describe_constant(class_file, inner.outer_class_info_index)));
+                    }
+                    line.push(';');
+                    emit!(out, "{}", with_comment(2, &line, &comment));
+                }
+            }
+            Attribute::BootstrapMethods(bootstrap_methods) => {
+                emit!(out, "BootstrapMethods:");
+                for (i, method) in bootstrap_methods.bootstrap_methods.iter().enumerate() {
+                    emit!(out, "  {}: #{} {}", i, method.bootstrap_method_ref, resolve_constant(class_file, method.bootstrap_method_ref));
+                    emit!(out, "    Method arguments:");
+                    for &argument in &method.bootstrap_arguments {
+                        emit!(out, "      #{} {}", argument, resolve_constant(class_file, argument));
+                    }
+                }
+            }
+            _ => write_common_attribute(&mut out, 0, class_file, attribute)
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::opcodes::*;
+
+    // Classes compiled from the .java files next to them
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/etc/tests");
+
+    fn fixture(name: &str) -> Vec<u8> {
+        std::fs::read(format!("{}/{}", FIXTURES, name)).unwrap()
+    }
+
+    fn parse(bytes: Vec<u8>) -> Box<ClassFile> {
+        ClassFile::new(&mut Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn invalid_code_is_shown_as_bytes() {
+        let mut bytes = fixture("Arithmetic.class");
+        let idiv = [OP_CODE_ILOAD_0, OP_CODE_ILOAD_1, OP_CODE_IDIV, OP_CODE_IRETURN];
+        let position = bytes.windows(idiv.len()).position(|window| window == idiv).unwrap();
+        bytes[position + 2] = OP_CODE_IMPDEP2;
+
+        let text = disassemble(&parse(bytes)).unwrap();
+        assert!(text.contains("      // Invalid instruction with opcode 0xff at pc 2\n         0: 1a 1b ff ac\n"), "{}", text);
+        // The other methods are still decoded
+        assert!(text.contains("         2: irem\n"), "{}", text);
+    }
+
+    #[test]
+    fn golden_output() {
+        // Switches.txt is the expected output, it has a tableswitch, a lookupswitch and an
+        // unknown StackMapTable under each Code attribute
+        let text = disassemble(&parse(fixture("Switches.class"))).unwrap();
+        assert_eq!(text, String::from_utf8(fixture("Switches.txt")).unwrap());
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+use std::io;
+
+/// Where in the input an error happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    /// Byte offset into the buffer being parsed
+    Offset(u64),
+    /// Program counter of the instruction being executed
+    Pc(u32)
+}
+
+#[derive(Debug)]
+pub enum JvmError {
+    Io(io::Error),
+    Truncated { offset: u64 },
+    InvalidMagic { magic: u32 },
+    UnsupportedVersion { major: u16, minor: u16 },
+    UnknownConstantTag { tag: u8, offset: u64 },
+    IllegalConstantTag { tag: u8, major: u16, offset: u64 },
+    MalformedUtf8 { offset: u64 },
+    InvalidConstantIndex { index: u16, location: Location },
+    UnexpectedConstant { index: u16, expected: &'static str, location: Location },
+    MalformedAttribute { name: String, offset: u64 },
+    InvalidDescriptor { descriptor: String, position: usize },
+    InvalidSignature { signature: String, position: usize },
+    InvalidInstruction { opcode: u8, pc: u32 },
+    UnsupportedOpcode { opcode: u8, pc: u32 },
+    OperandStackOverflow { max_stack: u16, pc: u32 },
+    OperandStackUnderflow { pc: u32 },
+    UnexpectedValue { expected: &'static str, found: &'static str, pc: u32 },
+    /// The class could not be found on the class path, NoClassDefFoundError
+    ClassNotFound { name: String },
+    /// The class is its own superclass or superinterface, ClassCircularityError
+    ClassCircularity { name: String },
+    /// The class was found but could not be loaded
+    InvalidClass { name: String, reason: String },
+    InvalidJar { path: String, reason: String },
+    /// The static initializer of the class threw an exception, ExceptionInInitializerError
+    ExceptionInInitializer { class_name: String, cause: Box<JvmError> },
+    /// A Java exception that was thrown and not caught, class_name is the binary name
+    UncaughtException { class_name: String, message: Option<String>, pc: u32 },
+    Runtime { message: String, pc: u32 }
+}
+
+pub type JvmResult<T> = Result<T, JvmError>;
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Offset(offset) => write!(f, "offset {}", offset),
+            Location::Pc(pc) => write!(f, "pc {}", pc)
+        }
+    }
+}
+
+impl fmt::Display for JvmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JvmError::Io(err) => write!(f, "{}", err),
+            JvmError::Truncated { offset } =>
+                write!(f, "Unexpected end of input at offset {}", offset),
+            JvmError::InvalidMagic { magic } =>
+                write!(f, "Not a class file, magic is 0x{:X} instead of 0xCAFEBABE", magic),
+            JvmError::UnsupportedVersion { major, minor } =>
+                write!(f, "Unsupported class file version {}.{}", major, minor),
+            JvmError::UnknownConstantTag { tag, offset } =>
+                write!(f, "Unknown constant pool tag {} at offset {}", tag, offset),
+            JvmError::IllegalConstantTag { tag, major, offset } =>
+                write!(f, "Constant pool tag {} is not allowed in version {} class files at offset {}", tag, major, offset),
+            JvmError::MalformedUtf8 { offset } =>
+                write!(f, "Malformed modified UTF-8 at offset {}", offset),
+            JvmError::InvalidConstantIndex { index, location } =>
+                write!(f, "Invalid constant pool index {} at {}", index, location),
+            JvmError::UnexpectedConstant { index, expected, location } =>
+                write!(f, "Expected {} at constant pool index {} at {}", expected, index, location),
+            JvmError::MalformedAttribute { name, offset } =>
+                write!(f, "Malformed {} attribute at offset {}", name, offset),
+            JvmError::InvalidDescriptor { descriptor, position } =>
+                write!(f, "Invalid descriptor '{}' at position {}", descriptor, position),
+            JvmError::InvalidSignature { signature, position } =>
+                write!(f, "Invalid signature '{}' at position {}", signature, position),
+            JvmError::InvalidInstruction { opcode, pc } =>
+                write!(f, "Invalid instruction with opcode 0x{:x} at pc {}", opcode, pc),
+            JvmError::OperandStackOverflow { max_stack, pc } =>
+                write!(f, "Operand stack overflow, max_stack is {}, at pc {}", max_stack, pc),
+            JvmError::OperandStackUnderflow { pc } =>
+                write!(f, "Operand stack underflow at pc {}", pc),
+            JvmError::UnexpectedValue { expected, found, pc } =>
+                write!(f, "Expected {} but found {} at pc {}", expected, found, pc),
+            JvmError::ClassNotFound { name } =>
+                write!(f, "java.lang.NoClassDefFoundError: {}", name),
+            JvmError::ClassCircularity { name } =>
+                write!(f, "java.lang.ClassCircularityError: {}", name),
+            JvmError::InvalidClass { name, reason } =>
+                write!(f, "Invalid class {}: {}", name, reason),
+            JvmError::InvalidJar { path, reason } =>
+                write!(f, "Invalid JAR file {}: {}", path, reason),
+            JvmError::ExceptionInInitializer { class_name, cause } =>
+                write!(f, "java.lang.ExceptionInInitializerError in {}, caused by: {}", class_name.replace('/', "."), cause),
+            JvmError::UncaughtException { class_name, message: Some(message), pc } =>
+                write!(f, "Uncaught {}: {} at pc {}", class_name.replace('/', "."), message, pc),
+            JvmError::UncaughtException { class_name, message: None, pc } =>
+                write!(f, "Uncaught {} at pc {}", class_name.replace('/', "."), pc),
+            JvmError::UnsupportedOpcode { opcode, pc } =>
+                write!(f, "Opcode 0x{:x} not implemented at pc {}", opcode, pc),
+            JvmError::Runtime { message, pc } =>
+                write!(f, "{} at pc {}", message, pc)
+        }
+    }
+}
+
+impl std::error::Error for JvmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JvmError::Io(err) => Some(err),
+            JvmError::ExceptionInInitializer { cause, .. } => Some(cause.as_ref()),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for JvmError {
+    fn from(err: io::Error) -> Self {
+        JvmError::Io(err)
+    }
+}
@@ -0,0 +1,413 @@
+use crate::error::{JvmError, JvmResult};
+use crate::value::{Reference, Value};
+
+pub struct OperandStack {
+    values: Vec<Value>,
+    // Depth in slots, long and double count twice
+    depth: usize,
+    max_depth: usize
+}
+
+impl OperandStack {
+    pub fn new(max_stack: u16) -> OperandStack {
+        OperandStack {
+            values: Vec::with_capacity(max_stack as usize),
+            depth: 0,
+            max_depth: max_stack as usize
+        }
+    }
+
+    pub fn push(&mut self, value: Value, pc: u32) -> JvmResult<()> {
+        if self.depth + value.slots() > self.max_depth {
+            return Err(JvmError::OperandStackOverflow { max_stack: self.max_depth as u16, pc });
+        }
+        self.depth += value.slots();
+        self.values.push(value);
+        Ok(())
+    }
+
+    pub fn pop(&mut self, pc: u32) -> JvmResult<Value> {
+        let value = self.values.pop().ok_or(JvmError::OperandStackUnderflow { pc })?;
+        self.depth -= value.slots();
+        Ok(value)
+    }
+
+    pub fn peek(&self, pc: u32) -> JvmResult<&Value> {
+        self.values.last().ok_or(JvmError::OperandStackUnderflow { pc })
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.depth = 0;
+    }
+}
+
+pub struct Frame {
+    // pc of the instruction being executed, used to report errors
+    pub pc: u32,
+    // None for slots that were never written and for the second slot of a long or double
+    locals: Vec<Option<Value>>,
+    stack: OperandStack
+}
+
+fn unexpected(expected: &'static str, value: &Value, pc: u32) -> JvmError {
+    JvmError::UnexpectedValue { expected, found: value.type_name(), pc }
+}
+
+impl Frame {
+    pub fn new(max_locals: u16, max_stack: u16) -> Frame {
+        Frame {
+            pc: 0,
+            locals: vec![None; max_locals as usize],
+            stack: OperandStack::new(max_stack)
+        }
+    }
+
+    pub fn push(&mut self, value: Value) -> JvmResult<()> {
+        self.stack.push(value, self.pc)
+    }
+
+    pub fn pop(&mut self) -> JvmResult<Value> {
+        self.stack.pop(self.pc)
+    }
+
+    pub fn peek(&self) -> JvmResult<&Value> {
+        self.stack.peek(self.pc)
+    }
+
+    pub fn clear_stack(&mut self) {
+        self.stack.clear();
+    }
+
+    pub fn pop_int(&mut self) -> JvmResult<i32> {
+        match self.pop()? {
+            Value::Int(value) => Ok(value),
+            value => Err(unexpected("int", &value, self.pc))
+        }
+    }
+
+    pub fn pop_long(&mut self) -> JvmResult<i64> {
+        match self.pop()? {
+            Value::Long(value) => Ok(value),
+            value => Err(unexpected("long", &value, self.pc))
+        }
+    }
+
+    pub fn pop_float(&mut self) -> JvmResult<f32> {
+        match self.pop()? {
+            Value::Float(value) => Ok(value),
+            value => Err(unexpected("float", &value, self.pc))
+        }
+    }
+
+    pub fn pop_double(&mut self) -> JvmResult<f64> {
+        match self.pop()? {
+            Value::Double(value) => Ok(value),
+            value => Err(unexpected("double", &value, self.pc))
+        }
+    }
+
+    pub fn pop_reference(&mut self) -> JvmResult<Reference> {
+        match self.pop()? {
+            Value::Reference(reference) => Ok(reference),
+            value => Err(unexpected("reference", &value, self.pc))
+        }
+    }
+
+    /// Pops a value of the given category, the dup and pop instructions work on categories
+    /// instead of types
+    fn pop_category(&mut self, category_2: bool) -> JvmResult<Value> {
+        let value = self.pop()?;
+        if value.is_category_2() != category_2 {
+            let expected = if category_2 { "category 2 value" } else { "category 1 value" };
+            return Err(unexpected(expected, &value, self.pc));
+        }
+        Ok(value)
+    }
+
+    /// Pops one category 2 value or two category 1 values, in stack order (top last)
+    fn pop_two_slots(&mut self) -> JvmResult<Vec<Value>> {
+        if self.peek()?.is_category_2() {
+            return Ok(vec![self.pop()?]);
+        }
+        let top = self.pop_category(false)?;
+        let below = self.pop_category(false)?;
+        Ok(vec![below, top])
+    }
+
+    fn push_all(&mut self, values: &[Value]) -> JvmResult<()> {
+        for value in values {
+            self.push(value.clone())?;
+        }
+        Ok(())
+    }
+
+    pub fn pop_discard(&mut self) -> JvmResult<()> {
+        self.pop_category(false)?;
+        Ok(())
+    }
+
+    pub fn pop2(&mut self) -> JvmResult<()> {
+        self.pop_two_slots()?;
+        Ok(())
+    }
+
+    pub fn dup(&mut self) -> JvmResult<()> {
+        let value = self.pop_category(false)?;
+        self.push_all(&[value.clone(), value])
+    }
+
+    pub fn dup_x1(&mut self) -> JvmResult<()> {
+        let top = self.pop_category(false)?;
+        let below = self.pop_category(false)?;
+        self.push_all(&[top.clone(), below, top])
+    }
+
+    pub fn dup_x2(&mut self) -> JvmResult<()> {
+        let top = self.pop_category(false)?;
+        let below = self.pop_two_slots()?;
+        self.push(top.clone())?;
+        self.push_all(&below)?;
+        self.push(top)
+    }
+
+    pub fn dup2(&mut self) -> JvmResult<()> {
+        let top = self.pop_two_slots()?;
+        self.push_all(&top)?;
+        self.push_all(&top)
+    }
+
+    pub fn dup2_x1(&mut self) -> JvmResult<()> {
+        let top = self.pop_two_slots()?;
+        let below = self.pop_category(false)?;
+        self.push_all(&top)?;
+        self.push(below)?;
+        self.push_all(&top)
+    }
+
+    pub fn dup2_x2(&mut self) -> JvmResult<()> {
+        let top = self.pop_two_slots()?;
+        let below = self.pop_two_slots()?;
+        self.push_all(&top)?;
+        self.push_all(&below)?;
+        self.push_all(&top)
+    }
+
+    pub fn swap(&mut self) -> JvmResult<()> {
+        let top = self.pop_category(false)?;
+        let below = self.pop_category(false)?;
+        self.push_all(&[top, below])
+    }
+
+    pub fn load(&mut self, index: u16, expected: &'static str, is_expected: fn(&Value) -> bool) -> JvmResult<()> {
+        match self.locals.get(index as usize) {
+            Some(Some(value)) if is_expected(value) => {
+                let value = value.clone();
+                self.push(value)
+            }
+            Some(Some(value)) => Err(unexpected(expected, value, self.pc)),
+            Some(None) => Err(JvmError::Runtime { message: format!("Load - Local {} is uninitialized", index), pc: self.pc }),
+            None => Err(JvmError::Runtime { message: format!("Load - Local {} out of bounds", index), pc: self.pc })
+        }
+    }
+
+    pub fn store(&mut self, index: u16, expected: &'static str, is_expected: fn(&Value) -> bool) -> JvmResult<()> {
+        let value = self.pop()?;
+        if !is_expected(&value) {
+            return Err(unexpected(expected, &value, self.pc));
+        }
+        self.set_local(index, value)
+    }
+
+    pub fn set_local(&mut self, index: u16, value: Value) -> JvmResult<()> {
+        let index = index as usize;
+        if index + value.slots() > self.locals.len() {
+            return Err(JvmError::Runtime { message: format!("Store - Local {} out of bounds", index), pc: self.pc });
+        }
+
+        // Overwriting the second slot of a long or double invalidates it
+        if index > 0 && self.locals[index - 1].as_ref().is_some_and(|previous| previous.is_category_2()) {
+            self.locals[index - 1] = None;
+        }
+        if value.is_category_2() {
+            self.locals[index + 1] = None;
+        }
+        self.locals[index] = Some(value);
+
+        Ok(())
+    }
+
+    pub fn iinc(&mut self, index: u16, constant: i16) -> JvmResult<()> {
+        match self.locals.get_mut(index as usize) {
+            Some(Some(Value::Int(value))) => {
+                *value = value.wrapping_add(constant as i32);
+                Ok(())
+            }
+            _ => Err(JvmError::Runtime { message: format!("Iinc - Local {} does not hold an int", index), pc: self.pc })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_int(value: &Value) -> bool {
+        matches!(value, Value::Int(_))
+    }
+
+    fn is_long(value: &Value) -> bool {
+        matches!(value, Value::Long(_))
+    }
+
+    fn frame_with(values: &[Value]) -> Frame {
+        let mut frame = Frame::new(0, 16);
+        for value in values {
+            frame.push(value.clone()).unwrap();
+        }
+        frame
+    }
+
+    // The whole operand stack, bottom first
+    fn stack_of(mut frame: Frame) -> String {
+        let mut values: Vec<Value> = Vec::new();
+        while let Ok(value) = frame.pop() {
+            values.push(value);
+        }
+        values.reverse();
+        format!("{:?}", values)
+    }
+
+    fn is_uninitialized(result: JvmResult<()>) -> bool {
+        matches!(result, Err(JvmError::Runtime { message, .. }) if message.contains("uninitialized"))
+    }
+
+    #[test]
+    fn long_locals_reserve_the_next_slot() {
+        let mut frame = Frame::new(4, 4);
+        frame.set_local(1, Value::Long(7)).unwrap();
+        frame.load(1, "long", is_long).unwrap();
+        assert_eq!(frame.pop_long().unwrap(), 7);
+        assert!(is_uninitialized(frame.load(2, "int", is_int)));
+
+        // A long in the last slot would need a slot past the end
+        assert!(frame.set_local(3, Value::Long(1)).is_err());
+        assert!(frame.set_local(4, Value::Int(1)).is_err());
+    }
+
+    #[test]
+    fn overwriting_half_of_a_long_invalidates_it() {
+        let mut frame = Frame::new(4, 4);
+        frame.set_local(0, Value::Long(7)).unwrap();
+        frame.set_local(1, Value::Int(1)).unwrap();
+        assert!(is_uninitialized(frame.load(0, "long", is_long)));
+        frame.load(1, "int", is_int).unwrap();
+
+        frame.set_local(2, Value::Long(8)).unwrap();
+        frame.set_local(1, Value::Long(9)).unwrap();
+        assert!(is_uninitialized(frame.load(2, "long", is_long)));
+        frame.load(1, "long", is_long).unwrap();
+        assert_eq!(frame.pop_long().unwrap(), 9);
+    }
+
+    #[test]
+    fn dup2_on_category_2_values() {
+        let mut frame = frame_with(&[Value::Long(1)]);
+        frame.dup2().unwrap();
+        assert_eq!(stack_of(frame), "[Long(1), Long(1)]");
+
+        let mut frame = frame_with(&[Value::Int(1), Value::Int(2)]);
+        frame.dup2().unwrap();
+        assert_eq!(stack_of(frame), "[Int(1), Int(2), Int(1), Int(2)]");
+
+        let mut frame = frame_with(&[Value::Long(1), Value::Int(2)]);
+        assert!(frame.dup2().is_err());
+    }
+
+    #[test]
+    fn dup2_x1_on_category_2_values() {
+        let mut frame = frame_with(&[Value::Int(1), Value::Double(2.0)]);
+        frame.dup2_x1().unwrap();
+        assert_eq!(stack_of(frame), "[Double(2.0), Int(1), Double(2.0)]");
+
+        let mut frame = frame_with(&[Value::Int(1), Value::Int(2), Value::Int(3)]);
+        frame.dup2_x1().unwrap();
+        assert_eq!(stack_of(frame), "[Int(2), Int(3), Int(1), Int(2), Int(3)]");
+
+        // The value below has to be category 1
+        let mut frame = frame_with(&[Value::Long(1), Value::Long(2)]);
+        assert!(matches!(frame.dup2_x1(), Err(JvmError::UnexpectedValue { .. })));
+    }
+
+    #[test]
+    fn dup2_x2_on_category_2_values() {
+        // Form 4, two category 2 values
+        let mut frame = frame_with(&[Value::Long(1), Value::Long(2)]);
+        frame.dup2_x2().unwrap();
+        assert_eq!(stack_of(frame), "[Long(2), Long(1), Long(2)]");
+
+        // Form 2, a category 2 value over two category 1 values
+        let mut frame = frame_with(&[Value::Int(1), Value::Int(2), Value::Long(3)]);
+        frame.dup2_x2().unwrap();
+        assert_eq!(stack_of(frame), "[Long(3), Int(1), Int(2), Long(3)]");
+
+        // Form 3, two category 1 values over a category 2 value
+        let mut frame = frame_with(&[Value::Double(1.0), Value::Int(2), Value::Int(3)]);
+        frame.dup2_x2().unwrap();
+        assert_eq!(stack_of(frame), "[Int(2), Int(3), Double(1.0), Int(2), Int(3)]");
+
+        // Form 1, four category 1 values
+        let mut frame = frame_with(&[Value::Int(1), Value::Int(2), Value::Int(3), Value::Int(4)]);
+        frame.dup2_x2().unwrap();
+        assert_eq!(stack_of(frame), "[Int(3), Int(4), Int(1), Int(2), Int(3), Int(4)]");
+    }
+
+    #[test]
+    fn category_1_instructions_reject_category_2_values() {
+        for operation in [Frame::dup, Frame::pop_discard, Frame::swap, Frame::dup_x1] {
+            let mut frame = frame_with(&[Value::Int(1), Value::Long(2)]);
+            assert!(matches!(operation(&mut frame), Err(JvmError::UnexpectedValue { .. })));
+        }
+
+        let mut frame = frame_with(&[Value::Long(1)]);
+        frame.pop2().unwrap();
+        assert!(matches!(frame.pop(), Err(JvmError::OperandStackUnderflow { .. })));
+    }
+
+    #[test]
+    fn pushing_past_max_stack_overflows() {
+        let mut stack = OperandStack::new(3);
+        stack.push(Value::Int(1), 0).unwrap();
+        stack.push(Value::Long(2), 1).unwrap();
+        assert!(matches!(stack.push(Value::Int(3), 2), Err(JvmError::OperandStackOverflow { max_stack: 3, pc: 2 })));
+
+        // A long needs two free slots, one is not enough
+        let mut stack = OperandStack::new(3);
+        stack.push(Value::Int(1), 0).unwrap();
+        stack.push(Value::Int(2), 0).unwrap();
+        assert!(matches!(stack.push(Value::Double(3.0), 5), Err(JvmError::OperandStackOverflow { max_stack: 3, pc: 5 })));
+        stack.pop(6).unwrap();
+        stack.push(Value::Double(3.0), 7).unwrap();
+    }
+
+    #[test]
+    fn duplicating_past_max_stack_overflows() {
+        let mut frame = Frame::new(0, 2);
+        frame.push(Value::Long(1)).unwrap();
+        assert!(matches!(frame.dup2(), Err(JvmError::OperandStackOverflow { max_stack: 2, .. })));
+    }
+
+    #[test]
+    fn popping_an_empty_stack_underflows() {
+        let mut stack = OperandStack::new(1);
+        assert!(matches!(stack.pop(4), Err(JvmError::OperandStackUnderflow { pc: 4 })));
+        assert!(matches!(stack.peek(5), Err(JvmError::OperandStackUnderflow { pc: 5 })));
+
+        let mut frame = Frame::new(0, 2);
+        frame.pc = 9;
+        assert!(matches!(frame.pop_int(), Err(JvmError::OperandStackUnderflow { pc: 9 })));
+        assert!(matches!(frame.pop2(), Err(JvmError::OperandStackUnderflow { pc: 9 })));
+        frame.push(Value::Int(1)).unwrap();
+        assert!(matches!(frame.swap(), Err(JvmError::OperandStackUnderflow { pc: 9 })));
+    }
+}
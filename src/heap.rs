@@ -0,0 +1,29 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use crate::class::Class;
+use crate::value::{Reference, Value};
+
+// Objects live as long as something references them, reference counting stands in for a
+// garbage collector. Cycles between objects are never freed.
+
+pub struct Object {
+    pub class: Rc<Class>,
+    // Instance fields in the order of Class::instance_fields
+    pub fields: Vec<Value>,
+    // Detail message given to the constructor of Throwable, for classes that extend a
+    // platform exception
+    pub message: Option<Rc<str>>
+}
+
+impl fmt::Debug for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Object({})", self.class.name)
+    }
+}
+
+/// Allocates an instance of the class with every field set to its default value
+pub fn new_object(class: &Rc<Class>) -> Reference {
+    let fields = class.instance_fields.iter().map(|field| Value::default_for(&field.field_type)).collect();
+    Reference::Object(Rc::new(RefCell::new(Object { class: class.clone(), fields, message: None })))
+}
@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use miniz_oxide::inflate::decompress_to_vec;
+use crate::error::{JvmError, JvmResult};
+
+// JAR files are ZIP archives, only the parts of the format needed to read entries are supported:
+// no ZIP64, no encryption, and only stored and deflated entries.
+// https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT
+
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32        = 0x02014b50;
+const LOCAL_HEADER_SIGNATURE: u32             = 0x04034b50;
+
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+const CENTRAL_DIRECTORY_HEADER_SIZE: usize = 46;
+const LOCAL_HEADER_SIZE: usize             = 30;
+
+const METHOD_STORED: u16   = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+const FLAG_ENCRYPTED: u16 = 0x0001;
+
+pub const MANIFEST_NAME: &str = "META-INF/MANIFEST.MF";
+
+struct JarEntry {
+    method: u16,
+    flags: u16,
+    crc32: u32,
+    compressed_size: usize,
+    uncompressed_size: usize,
+    local_header_offset: usize
+}
+
+pub struct JarFile {
+    pub path: PathBuf,
+    data: Vec<u8>,
+    entries: HashMap<String, JarEntry>
+}
+
+/// The main section of META-INF/MANIFEST.MF
+pub struct Manifest {
+    pub main_class: Option<String>,
+    // Relative URLs separated by spaces, resolved against the directory of the JAR
+    pub class_path: Vec<String>
+}
+
+// ZIP integers are little endian, unlike everything in a class file
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFFFFFF;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+impl JarFile {
+    pub fn open(path: &Path) -> JvmResult<JarFile> {
+        let data = std::fs::read(path)?;
+        let mut jar = JarFile { path: path.to_path_buf(), data, entries: HashMap::new() };
+        jar.read_central_directory()?;
+        Ok(jar)
+    }
+
+    fn invalid(&self, reason: &str) -> JvmError {
+        JvmError::InvalidJar { path: self.path.display().to_string(), reason: reason.to_string() }
+    }
+
+    /// The end of central directory record is at the end of the file, followed only by a comment
+    /// of at most 65535 bytes
+    fn find_end_of_central_directory(&self) -> JvmResult<usize> {
+        if self.data.len() < END_OF_CENTRAL_DIRECTORY_SIZE {
+            return Err(self.invalid("too short to be a ZIP archive"));
+        }
+        let last = self.data.len() - END_OF_CENTRAL_DIRECTORY_SIZE;
+        let first = last.saturating_sub(u16::MAX as usize);
+        (first..=last).rev()
+            .find(|&offset| u32_at(&self.data, offset) == Some(END_OF_CENTRAL_DIRECTORY_SIGNATURE))
+            .ok_or_else(|| self.invalid("end of central directory not found"))
+    }
+
+    fn read_central_directory(&mut self) -> JvmResult<()> {
+        let end = self.find_end_of_central_directory()?;
+        let truncated = || self.invalid("truncated central directory");
+        let entry_count = u16_at(&self.data, end + 10).ok_or_else(truncated)?;
+        let directory_offset = u32_at(&self.data, end + 16).ok_or_else(truncated)?;
+        if entry_count == u16::MAX || directory_offset == u32::MAX {
+            return Err(self.invalid("ZIP64 archives are not supported"));
+        }
+
+        let mut entries: HashMap<String, JarEntry> = HashMap::with_capacity(entry_count as usize);
+        let mut offset = directory_offset as usize;
+        for _ in 0..entry_count {
+            if u32_at(&self.data, offset) != Some(CENTRAL_DIRECTORY_SIGNATURE) {
+                return Err(self.invalid("bad central directory header"));
+            }
+            let field_u16 = |at: usize| u16_at(&self.data, offset + at).ok_or_else(truncated);
+            let field_u32 = |at: usize| u32_at(&self.data, offset + at).ok_or_else(truncated);
+
+            let entry = JarEntry {
+                flags: field_u16(8)?,
+                method: field_u16(10)?,
+                crc32: field_u32(16)?,
+                compressed_size: field_u32(20)? as usize,
+                uncompressed_size: field_u32(24)? as usize,
+                local_header_offset: field_u32(42)? as usize
+            };
+            let name_length = field_u16(28)? as usize;
+            let extra_length = field_u16(30)? as usize;
+            let comment_length = field_u16(32)? as usize;
+
+            let name_start = offset + CENTRAL_DIRECTORY_HEADER_SIZE;
+            let name = self.data.get(name_start..name_start + name_length).ok_or_else(truncated)?;
+            entries.insert(String::from_utf8_lossy(name).into_owned(), entry);
+
+            offset = name_start + name_length + extra_length + comment_length;
+        }
+
+        self.entries = entries;
+        Ok(())
+    }
+
+    /// Contents of the named entry, None if there is no such entry
+    pub fn read(&self, name: &str) -> JvmResult<Option<Vec<u8>>> {
+        let entry = match self.entries.get(name) {
+            Some(entry) => entry,
+            None => return Ok(None)
+        };
+        if entry.flags & FLAG_ENCRYPTED != 0 {
+            return Err(self.invalid(&format!("{} is encrypted", name)));
+        }
+
+        // The sizes in the local header can be 0 when they follow the data, so only the
+        // lengths of the variable fields are taken from it
+        let header = entry.local_header_offset;
+        if u32_at(&self.data, header) != Some(LOCAL_HEADER_SIGNATURE) {
+            return Err(self.invalid(&format!("bad local header for {}", name)));
+        }
+        let truncated = || self.invalid(&format!("{} is truncated", name));
+        let name_length = u16_at(&self.data, header + 26).ok_or_else(truncated)? as usize;
+        let extra_length = u16_at(&self.data, header + 28).ok_or_else(truncated)? as usize;
+        let data_start = header + LOCAL_HEADER_SIZE + name_length + extra_length;
+        let compressed = self.data.get(data_start..data_start + entry.compressed_size).ok_or_else(truncated)?;
+
+        let contents = match entry.method {
+            METHOD_STORED => compressed.to_vec(),
+            METHOD_DEFLATED => decompress_to_vec(compressed)
+                .map_err(|err| self.invalid(&format!("cannot inflate {}: {:?}", name, err.status)))?,
+            method => return Err(self.invalid(&format!("{} uses unsupported compression method {}", name, method)))
+        };
+        if contents.len() != entry.uncompressed_size || crc32(&contents) != entry.crc32 {
+            return Err(self.invalid(&format!("{} is corrupted", name)));
+        }
+
+        Ok(Some(contents))
+    }
+
+    pub fn manifest(&self) -> JvmResult<Option<Manifest>> {
+        Ok(self.read(MANIFEST_NAME)?.map(|contents| Manifest::parse(&String::from_utf8_lossy(&contents))))
+    }
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Manifest {
+        // Lines longer than 72 bytes continue on the next line, which starts with a single space
+        let mut lines: Vec<String> = Vec::new();
+        for line in text.lines() {
+            match (line.strip_prefix(' '), lines.last_mut()) {
+                (Some(continuation), Some(last)) => last.push_str(continuation),
+                _ => lines.push(line.to_string())
+            }
+        }
+
+        let mut manifest = Manifest { main_class: None, class_path: Vec::new() };
+        // The main section ends at the first blank line, the rest are per entry sections
+        for line in lines.iter().take_while(|line| !line.is_empty()) {
+            let (name, value) = match line.split_once(':') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => continue
+            };
+            if name.eq_ignore_ascii_case("Main-Class") {
+                manifest.main_class = Some(value.to_string());
+            } else if name.eq_ignore_ascii_case("Class-Path") {
+                manifest.class_path = value.split_whitespace().map(String::from).collect();
+            }
+        }
+        manifest
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A ZIP archive holding the given entries stored without compression
+    pub(crate) fn archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        let mut directory: Vec<u8> = Vec::new();
+        for (name, contents) in entries {
+            let header_offset = data.len() as u32;
+            let fields = |data: &mut Vec<u8>| {
+                data.extend_from_slice(&METHOD_STORED.to_le_bytes());
+                data.extend_from_slice(&[0; 4]); // Modification time and date
+                data.extend_from_slice(&crc32(contents).to_le_bytes());
+                data.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+                data.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+                data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+                data.extend_from_slice(&[0; 2]); // Extra field length
+            };
+
+            data.extend_from_slice(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+            data.extend_from_slice(&[20, 0, 0, 0]); // Version needed and flags
+            fields(&mut data);
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(contents);
+
+            directory.extend_from_slice(&CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+            directory.extend_from_slice(&[20, 0, 20, 0, 0, 0]); // Versions and flags
+            fields(&mut directory);
+            directory.extend_from_slice(&[0; 10]); // Comment length, disk, attributes
+            directory.extend_from_slice(&header_offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+
+        let directory_offset = data.len() as u32;
+        data.extend_from_slice(&directory);
+        data.extend_from_slice(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        data.extend_from_slice(&[0; 4]); // Disk numbers
+        data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        data.extend_from_slice(&directory_offset.to_le_bytes());
+        data.extend_from_slice(&[0; 2]); // Comment length
+        data
+    }
+
+    fn open(data: Vec<u8>) -> JvmResult<JarFile> {
+        let mut jar = JarFile { path: PathBuf::from("test.jar"), data, entries: HashMap::new() };
+        jar.read_central_directory()?;
+        Ok(jar)
+    }
+
+    fn error_reason<T>(result: JvmResult<T>) -> String {
+        match result {
+            Err(JvmError::InvalidJar { reason, .. }) => reason,
+            Err(other) => panic!("expected InvalidJar, got {:?}", other),
+            Ok(_) => panic!("expected InvalidJar, got a value")
+        }
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn reads_stored_entries() {
+        let jar = open(archive(&[(MANIFEST_NAME, b"Main-Class: Hello\r\n"), ("Hello.class", &[0xCA, 0xFE, 0xBA, 0xBE])])).unwrap();
+        assert_eq!(jar.read("Hello.class").unwrap(), Some(vec![0xCA, 0xFE, 0xBA, 0xBE]));
+        assert_eq!(jar.read("Missing.class").unwrap(), None);
+        assert_eq!(jar.manifest().unwrap().unwrap().main_class.as_deref(), Some("Hello"));
+    }
+
+    #[test]
+    fn truncated_archives() {
+        let data = archive(&[("Hello.class", &[0xCA, 0xFE, 0xBA, 0xBE])]);
+        for length in 0..data.len() {
+            assert!(open(data[..length].to_vec()).is_err(), "prefix of {} bytes was accepted", length);
+        }
+
+        // An intact directory whose entry runs past the end of the file
+        let mut data = archive(&[("Hello.class", &[0xCA, 0xFE, 0xBA, 0xBE])]);
+        let directory_offset = u32_at(&data, data.len() - 6).unwrap() as usize;
+        data[directory_offset + 20..directory_offset + 24].copy_from_slice(&1000u32.to_le_bytes());
+        let jar = open(data).unwrap();
+        assert_eq!(error_reason(jar.read("Hello.class")), "Hello.class is truncated");
+    }
+
+    #[test]
+    fn zip64_archives_are_rejected() {
+        let data = archive(&[("Hello.class", &[0xCA, 0xFE, 0xBA, 0xBE])]);
+        let end = data.len() - END_OF_CENTRAL_DIRECTORY_SIZE;
+
+        let mut entry_count = data.clone();
+        entry_count[end + 8..end + 12].copy_from_slice(&[0xFF; 4]);
+        assert_eq!(error_reason(open(entry_count)), "ZIP64 archives are not supported");
+
+        let mut directory_offset = data;
+        directory_offset[end + 16..end + 20].copy_from_slice(&[0xFF; 4]);
+        assert_eq!(error_reason(open(directory_offset)), "ZIP64 archives are not supported");
+    }
+
+    #[test]
+    fn manifest_continuation_lines() {
+        // Lines are cut at 72 bytes and continued after a single space
+        let manifest = Manifest::parse(concat!(
+            "Manifest-Version: 1.0\r\n",
+            "Class-Path: lib/first-library-with-a-long-name.jar lib/second-librar\r\n",
+            " y.jar lib/third.jar\r\n",
+            "Main-Class: com.example.app\r\n",
+            " lication.Main\r\n"));
+        assert_eq!(manifest.main_class.as_deref(), Some("com.example.application.Main"));
+        assert_eq!(manifest.class_path, vec!["lib/first-library-with-a-long-name.jar", "lib/second-library.jar", "lib/third.jar"]);
+    }
+
+    #[test]
+    fn manifest_keys_ignore_case() {
+        let manifest = Manifest::parse("main-class: Hello\nCLASS-PATH: a.jar  b.jar\n");
+        assert_eq!(manifest.main_class.as_deref(), Some("Hello"));
+        assert_eq!(manifest.class_path, vec!["a.jar", "b.jar"]);
+    }
+
+    #[test]
+    fn manifest_entry_sections_are_ignored() {
+        let manifest = Manifest::parse(concat!(
+            "Manifest-Version: 1.0\n",
+            "Main-Class: Hello\n",
+            "\n",
+            "Name: Other.class\n",
+            "Main-Class: Other\n",
+            "Class-Path: other.jar\n"));
+        assert_eq!(manifest.main_class.as_deref(), Some("Hello"));
+        assert!(manifest.class_path.is_empty());
+    }
+}
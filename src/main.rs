@@ -1,51 +1,266 @@
+
 mod utils;
+mod attributes;
+mod bytecode;
+mod class;
 mod class_file;
+mod class_loader;
 mod code;
+mod descriptor;
+mod disassembler;
+mod error;
+mod frame;
+mod heap;
+mod jar;
+mod modified_utf8;
+mod opcodes;
+mod signature;
+mod value;
 
 use utils::*;
 
+use std::cell::RefCell;
 use std::io::{Cursor};
+use std::path::Path;
+use std::rc::Rc;
+use crate::class::Class;
 use crate::class_file::*;
+use crate::class_loader::ClassLoader;
 use crate::code::*;
+use crate::descriptor::FieldType;
+use crate::disassembler::disassemble;
+use crate::error::JvmResult;
+use crate::value::{Array, Reference, Value};
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
-
-    if args.len() < 3 {
-        println!("Usage: {} <file_path> <method_name>", args[0]);
-        return;
-    }
-
-    let file_path = &args[1];
-    let method_to_run = &args[2];
+fn load_class_file(file_path: &str) -> Option<Box<ClassFile>> {
     let mut cursor: Cursor<Vec<u8>>;
 
     match read_file_to_buf(file_path) {
         Err(err) => {
             eprintln!("Error reading file {}: {}", file_path, err);
-            return;
+            return None;
         }
         Ok(buffer) => {
             cursor = Cursor::new(buffer);
         }
     }
 
-    let class_file: Box<ClassFile>;
     match ClassFile::new(&mut cursor) {
         Err(err) => {
             eprintln!("Error creating ClassFile: {}", err);
-            return;
+            None
+        }
+        Ok(cf) => Some(cf)
+    }
+}
+
+// Interpreted calls recurse on the native stack, so the interpreter gets a thread with a
+// bigger stack than the main thread's
+const INTERPRETER_STACK_SIZE: usize = 512 * 1024 * 1024;
+
+// Like java, any error, including an uncaught exception, exits with 1
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+
+fn main() {
+    let interpreter = std::thread::Builder::new()
+        .name(String::from("main"))
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(run);
+    let status = match interpreter {
+        // A panic in the interpreter is a failure like any other
+        Ok(handle) => handle.join().unwrap_or(EXIT_FAILURE),
+        Err(err) => {
+            eprintln!("Failed to start the interpreter thread: {}", err);
+            EXIT_FAILURE
+        }
+    };
+    std::process::exit(status);
+}
+
+const MAIN_METHOD_NAME: &str = "main";
+const MAIN_METHOD_DESCRIPTOR: &str = "([Ljava/lang/String;)V";
+
+/// Directory the package hierarchy of a class file starts at, for out/a/b/C.class holding a/b/C it is out
+fn package_root(file_path: &str, class_name: &str) -> String {
+    let mut root = Path::new(file_path).parent().unwrap_or(Path::new("")).to_path_buf();
+    let packages: Vec<&str> = class_name.split('/').collect();
+    for package in packages[..packages.len() - 1].iter().rev() {
+        if root.file_name().and_then(|name| name.to_str()) != Some(package) {
+            break;
         }
-        Ok(cf) => class_file = cf
+        root.pop();
+    }
+    if root.as_os_str().is_empty() {
+        return String::from(".");
     }
+    root.to_string_lossy().into_owned()
+}
+
+fn print_usage(program: &str) {
+    println!("Usage: {} [-cp <class_path>] [--method <method_name>] <file_path | class_name> [args...]", program);
+    println!("       {} [--method <method_name>] -jar <jar_file> [args...]", program);
+    println!("       {} disassemble <file_path>", program);
+}
 
-    if let Some(main_method) = class_file.find_method_by_name(method_to_run) {
-        if let Some(att) = class_file.find_attribute_by_name(&main_method.attributes, "Code") {
-            if let Ok(code_att) = CodeAttribute::new(att) {
-                let _ = execute_code(&class_file, code_att.code);
+/// The String[] passed to main
+fn string_array(strings: &[String]) -> Value {
+    let mut array = Array::new(FieldType::Object(String::from("java/lang/String")), strings.len());
+    for (element, string) in array.elements.iter_mut().zip(strings) {
+        *element = Value::Reference(Reference::String(Rc::from(string.as_str())));
+    }
+    Value::Reference(Reference::Array(Rc::new(RefCell::new(array))))
+}
+
+fn run() -> i32 {
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.len() < 2 {
+        print_usage(&args[0]);
+        return EXIT_FAILURE;
+    }
+
+    if args[1] == "disassemble" {
+        if args.len() < 3 {
+            print_usage(&args[0]);
+            return EXIT_FAILURE;
+        }
+        let file_path = &args[2];
+        let class_file = match load_class_file(file_path) {
+            None => return EXIT_FAILURE,
+            Some(cf) => cf
+        };
+        println!("Classfile {}", file_path);
+        return match disassemble(&class_file) {
+            Err(err) => {
+                eprintln!("Error disassembling {}: {}", file_path, err);
+                EXIT_FAILURE
             }
+            Ok(text) => {
+                print!("{}", text);
+                EXIT_SUCCESS
+            }
+        };
+    }
+
+    let mut method_to_run: Option<&str> = None;
+    let mut class_path: Option<&str> = None;
+    let mut jar_path: Option<&str> = None;
+    let mut first_arg = 1;
+    while first_arg < args.len() && args[first_arg].starts_with('-') {
+        let option = args[first_arg].as_str();
+        let value = match args.get(first_arg + 1) {
+            Some(value) => value.as_str(),
+            None => {
+                print_usage(&args[0]);
+                return EXIT_FAILURE;
+            }
+        };
+        match option {
+            "--method" => method_to_run = Some(value),
+            "-cp" | "-classpath" | "--class-path" => class_path = Some(value),
+            "-jar" => jar_path = Some(value),
+            _ => {
+                eprintln!("Unknown option {}", option);
+                print_usage(&args[0]);
+                return EXIT_FAILURE;
+            }
+        }
+        first_arg += 2;
+        // Everything after the JAR is passed to main
+        if jar_path.is_some() {
+            break;
         }
+    }
+
+    let target: &str;
+    let program_args: &[String];
+    if let Some(jar_path) = jar_path {
+        target = jar_path;
+        program_args = &args[first_arg..];
+    } else if first_arg < args.len() {
+        target = &args[first_arg];
+        program_args = &args[first_arg + 1..];
     } else {
-        eprintln!("Method '{}' not found in {}", method_to_run, file_path);
+        print_usage(&args[0]);
+        return EXIT_FAILURE;
+    }
+
+    // The target is either a JAR with a Main-Class, a class file, whose package root becomes
+    // the default class path, or the binary name of a class on the class path
+    let loaded: JvmResult<Rc<Class>>;
+    let loader: ClassLoader;
+    if jar_path.is_some() {
+        match ClassLoader::for_jar(Path::new(target)) {
+            Err(err) => {
+                eprintln!("Error opening {}: {}", target, err);
+                return EXIT_FAILURE;
+            }
+            Ok((jar_loader, main_class)) => {
+                loader = jar_loader;
+                loaded = loader.load_class(&main_class);
+            }
+        }
+    } else if target.ends_with(".class") {
+        let class_file = match load_class_file(target) {
+            None => return EXIT_FAILURE,
+            Some(cf) => cf
+        };
+        let default_class_path = package_root(target, class_file.name().unwrap_or_default());
+        loader = ClassLoader::new(ClassLoader::parse_class_path(class_path.unwrap_or(&default_class_path)));
+        loaded = loader.define_class(class_file);
+    } else {
+        let default_class_path = std::env::var("CLASSPATH").unwrap_or(String::from("."));
+        loader = ClassLoader::new(ClassLoader::parse_class_path(class_path.unwrap_or(&default_class_path)));
+        loaded = loader.load_class(&target.replace('.', "/"));
+    }
+
+    let class = match loaded {
+        Err(err) => {
+            eprintln!("Error loading {}: {}", target, err);
+            return EXIT_FAILURE;
+        }
+        Ok(c) => c
+    };
+    let class_file = &class.class_file;
+    let file_path = target;
+
+    let method: &MethodInfo;
+    match method_to_run {
+        Some(name) => match class_file.find_method_by_name(name) {
+            Some(m) => method = m,
+            None => {
+                eprintln!("Method '{}' not found in {}", name, file_path);
+                return EXIT_FAILURE;
+            }
+        },
+        None => match class_file.find_method(MAIN_METHOD_NAME, MAIN_METHOD_DESCRIPTOR) {
+            Some(m) if m.has_flag(MethodsAccessFlags::PUBLIC) && m.has_flag(MethodsAccessFlags::STATIC) => method = m,
+            _ => {
+                eprintln!("Main method not found in {}, please define it as:", file_path);
+                eprintln!("   public static void main(String[] args)");
+                return EXIT_FAILURE;
+            }
+        }
+    }
+
+    // Methods picked by name get the program arguments only if they take a String[]
+    let descriptor = class_file.get_utf8(method.descriptor_index).unwrap_or_default();
+    let arguments: Vec<Value>;
+    if descriptor == MAIN_METHOD_DESCRIPTOR {
+        arguments = vec![string_array(program_args)];
+    } else if descriptor.starts_with("()") {
+        arguments = Vec::new();
+    } else {
+        eprintln!("Method '{}' must take no arguments or a String[], its descriptor is {}", method_to_run.unwrap_or(MAIN_METHOD_NAME), descriptor);
+        return EXIT_FAILURE;
+    }
+
+    match execute_method(&loader, &class, method, arguments) {
+        Err(err) => {
+            eprintln!("Error executing '{}': {}", method_to_run.unwrap_or(MAIN_METHOD_NAME), err);
+            EXIT_FAILURE
+        }
+        Ok(_) => EXIT_SUCCESS
     }
 }
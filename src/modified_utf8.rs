@@ -0,0 +1,108 @@
+// Modified UTF-8 as used by CONSTANT_Utf8 (JVMS 4.4.7). It differs from standard UTF-8 in that
+// NUL is encoded as two bytes (0xC0 0x80) and characters outside the BMP are stored as a
+// surrogate pair, each half encoded as its own three byte sequence.
+
+/// Decodes into UTF-16 code units, the way Java holds strings.
+/// On malformed input returns the index of the byte where decoding failed.
+pub fn decode(bytes: &[u8]) -> Result<Vec<u16>, usize> {
+    let mut units: Vec<u16> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    let continuation = |index: usize| -> Result<u16, usize> {
+        match bytes.get(index) {
+            Some(&byte) if byte & 0xC0 == 0x80 => Ok((byte & 0x3F) as u16),
+            _ => Err(index)
+        }
+    };
+
+    while i < bytes.len() {
+        let byte = bytes[i];
+        match byte {
+            0x01..=0x7F => {
+                units.push(byte as u16);
+                i += 1;
+            }
+            0xC0..=0xDF => {
+                units.push(((byte & 0x1F) as u16) << 6 | continuation(i + 1)?);
+                i += 2;
+            }
+            0xE0..=0xEF => {
+                units.push(((byte & 0x0F) as u16) << 12 | continuation(i + 1)? << 6 | continuation(i + 2)?);
+                i += 3;
+            }
+            // 0x00 never appears and neither do the four byte forms
+            _ => return Err(i)
+        }
+    }
+
+    Ok(units)
+}
+
+pub fn encode(units: &[u16]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(units.len());
+
+    for &unit in units {
+        match unit {
+            0x0001..=0x007F => bytes.push(unit as u8),
+            0x0000 | 0x0080..=0x07FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(units: &[u16], bytes: &[u8]) {
+        assert_eq!(encode(units), bytes);
+        assert_eq!(decode(bytes), Ok(units.to_vec()));
+    }
+
+    #[test]
+    fn ascii_and_multi_byte_characters() {
+        let units: Vec<u16> = "héllo €".encode_utf16().collect();
+        round_trip(&units, "héllo €".as_bytes());
+    }
+
+    #[test]
+    fn nul_takes_two_bytes() {
+        round_trip(&[0x41, 0x0000, 0x42], &[0x41, 0xC0, 0x80, 0x42]);
+    }
+
+    #[test]
+    fn supplementary_characters_are_surrogate_pairs() {
+        // U+1F600 is the pair D83D DE00, each half in its own three byte sequence
+        let units: Vec<u16> = "😀".encode_utf16().collect();
+        round_trip(&units, &[0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]);
+    }
+
+    #[test]
+    fn unpaired_surrogates_are_kept() {
+        round_trip(&[0xD800], &[0xED, 0xA0, 0x80]);
+        round_trip(&[0xDC00, 0x61], &[0xED, 0xB0, 0x80, 0x61]);
+    }
+
+    #[test]
+    fn invalid_sequences_report_the_failing_byte() {
+        // A raw NUL byte
+        assert_eq!(decode(&[0x61, 0x00]), Err(1));
+        // The four byte form of standard UTF-8
+        assert_eq!(decode("😀".as_bytes()), Err(0));
+        // A continuation byte without a leading byte
+        assert_eq!(decode(&[0x80]), Err(0));
+        // A leading byte followed by something other than a continuation byte
+        assert_eq!(decode(&[0xC3, 0x41]), Err(1));
+        // Input ending in the middle of a sequence
+        assert_eq!(decode(&[0x61, 0xE2, 0x82]), Err(3));
+    }
+}
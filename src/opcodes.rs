@@ -0,0 +1,420 @@
+// Opcodes of the JVM instruction set (JVMS 6.5)
+
+pub const OP_CODE_NOP: u8              = 0x00;
+pub const OP_CODE_ACONST_NULL: u8      = 0x01;
+pub const OP_CODE_ICONST_M1: u8        = 0x02;
+pub const OP_CODE_ICONST_0: u8         = 0x03;
+pub const OP_CODE_ICONST_1: u8         = 0x04;
+pub const OP_CODE_ICONST_2: u8         = 0x05;
+pub const OP_CODE_ICONST_3: u8         = 0x06;
+pub const OP_CODE_ICONST_4: u8         = 0x07;
+pub const OP_CODE_ICONST_5: u8         = 0x08;
+pub const OP_CODE_LCONST_0: u8         = 0x09;
+pub const OP_CODE_LCONST_1: u8         = 0x0A;
+pub const OP_CODE_FCONST_0: u8         = 0x0B;
+pub const OP_CODE_FCONST_1: u8         = 0x0C;
+pub const OP_CODE_FCONST_2: u8         = 0x0D;
+pub const OP_CODE_DCONST_0: u8         = 0x0E;
+pub const OP_CODE_DCONST_1: u8         = 0x0F;
+pub const OP_CODE_BI_PUSH: u8          = 0x10;
+pub const OP_CODE_SI_PUSH: u8          = 0x11;
+pub const OP_CODE_LDC: u8              = 0x12;
+pub const OP_CODE_LDC_W: u8            = 0x13;
+pub const OP_CODE_LDC2_W: u8           = 0x14;
+pub const OP_CODE_ILOAD: u8            = 0x15;
+pub const OP_CODE_LLOAD: u8            = 0x16;
+pub const OP_CODE_FLOAD: u8            = 0x17;
+pub const OP_CODE_DLOAD: u8            = 0x18;
+pub const OP_CODE_ALOAD: u8            = 0x19;
+pub const OP_CODE_ILOAD_0: u8          = 0x1A;
+pub const OP_CODE_ILOAD_1: u8          = 0x1B;
+pub const OP_CODE_ILOAD_2: u8          = 0x1C;
+pub const OP_CODE_ILOAD_3: u8          = 0x1D;
+pub const OP_CODE_LLOAD_0: u8          = 0x1E;
+pub const OP_CODE_LLOAD_1: u8          = 0x1F;
+pub const OP_CODE_LLOAD_2: u8          = 0x20;
+pub const OP_CODE_LLOAD_3: u8          = 0x21;
+pub const OP_CODE_FLOAD_0: u8          = 0x22;
+pub const OP_CODE_FLOAD_1: u8          = 0x23;
+pub const OP_CODE_FLOAD_2: u8          = 0x24;
+pub const OP_CODE_FLOAD_3: u8          = 0x25;
+pub const OP_CODE_DLOAD_0: u8          = 0x26;
+pub const OP_CODE_DLOAD_1: u8          = 0x27;
+pub const OP_CODE_DLOAD_2: u8          = 0x28;
+pub const OP_CODE_DLOAD_3: u8          = 0x29;
+pub const OP_CODE_ALOAD_0: u8          = 0x2A;
+pub const OP_CODE_ALOAD_1: u8          = 0x2B;
+pub const OP_CODE_ALOAD_2: u8          = 0x2C;
+pub const OP_CODE_ALOAD_3: u8          = 0x2D;
+pub const OP_CODE_IALOAD: u8           = 0x2E;
+pub const OP_CODE_LALOAD: u8           = 0x2F;
+pub const OP_CODE_FALOAD: u8           = 0x30;
+pub const OP_CODE_DALOAD: u8           = 0x31;
+pub const OP_CODE_AALOAD: u8           = 0x32;
+pub const OP_CODE_BALOAD: u8           = 0x33;
+pub const OP_CODE_CALOAD: u8           = 0x34;
+pub const OP_CODE_SALOAD: u8           = 0x35;
+pub const OP_CODE_ISTORE: u8           = 0x36;
+pub const OP_CODE_LSTORE: u8           = 0x37;
+pub const OP_CODE_FSTORE: u8           = 0x38;
+pub const OP_CODE_DSTORE: u8           = 0x39;
+pub const OP_CODE_ASTORE: u8           = 0x3A;
+pub const OP_CODE_ISTORE_0: u8         = 0x3B;
+pub const OP_CODE_ISTORE_1: u8         = 0x3C;
+pub const OP_CODE_ISTORE_2: u8         = 0x3D;
+pub const OP_CODE_ISTORE_3: u8         = 0x3E;
+pub const OP_CODE_LSTORE_0: u8         = 0x3F;
+pub const OP_CODE_LSTORE_1: u8         = 0x40;
+pub const OP_CODE_LSTORE_2: u8         = 0x41;
+pub const OP_CODE_LSTORE_3: u8         = 0x42;
+pub const OP_CODE_FSTORE_0: u8         = 0x43;
+pub const OP_CODE_FSTORE_1: u8         = 0x44;
+pub const OP_CODE_FSTORE_2: u8         = 0x45;
+pub const OP_CODE_FSTORE_3: u8         = 0x46;
+pub const OP_CODE_DSTORE_0: u8         = 0x47;
+pub const OP_CODE_DSTORE_1: u8         = 0x48;
+pub const OP_CODE_DSTORE_2: u8         = 0x49;
+pub const OP_CODE_DSTORE_3: u8         = 0x4A;
+pub const OP_CODE_ASTORE_0: u8         = 0x4B;
+pub const OP_CODE_ASTORE_1: u8         = 0x4C;
+pub const OP_CODE_ASTORE_2: u8         = 0x4D;
+pub const OP_CODE_ASTORE_3: u8         = 0x4E;
+pub const OP_CODE_IASTORE: u8          = 0x4F;
+pub const OP_CODE_LASTORE: u8          = 0x50;
+pub const OP_CODE_FASTORE: u8          = 0x51;
+pub const OP_CODE_DASTORE: u8          = 0x52;
+pub const OP_CODE_AASTORE: u8          = 0x53;
+pub const OP_CODE_BASTORE: u8          = 0x54;
+pub const OP_CODE_CASTORE: u8          = 0x55;
+pub const OP_CODE_SASTORE: u8          = 0x56;
+pub const OP_CODE_POP: u8              = 0x57;
+pub const OP_CODE_POP2: u8             = 0x58;
+pub const OP_CODE_DUP: u8              = 0x59;
+pub const OP_CODE_DUP_X1: u8           = 0x5A;
+pub const OP_CODE_DUP_X2: u8           = 0x5B;
+pub const OP_CODE_DUP2: u8             = 0x5C;
+pub const OP_CODE_DUP2_X1: u8          = 0x5D;
+pub const OP_CODE_DUP2_X2: u8          = 0x5E;
+pub const OP_CODE_SWAP: u8             = 0x5F;
+pub const OP_CODE_IADD: u8             = 0x60;
+pub const OP_CODE_LADD: u8             = 0x61;
+pub const OP_CODE_FADD: u8             = 0x62;
+pub const OP_CODE_DADD: u8             = 0x63;
+pub const OP_CODE_ISUB: u8             = 0x64;
+pub const OP_CODE_LSUB: u8             = 0x65;
+pub const OP_CODE_FSUB: u8             = 0x66;
+pub const OP_CODE_DSUB: u8             = 0x67;
+pub const OP_CODE_IMUL: u8             = 0x68;
+pub const OP_CODE_LMUL: u8             = 0x69;
+pub const OP_CODE_FMUL: u8             = 0x6A;
+pub const OP_CODE_DMUL: u8             = 0x6B;
+pub const OP_CODE_IDIV: u8             = 0x6C;
+pub const OP_CODE_LDIV: u8             = 0x6D;
+pub const OP_CODE_FDIV: u8             = 0x6E;
+pub const OP_CODE_DDIV: u8             = 0x6F;
+pub const OP_CODE_IREM: u8             = 0x70;
+pub const OP_CODE_LREM: u8             = 0x71;
+pub const OP_CODE_FREM: u8             = 0x72;
+pub const OP_CODE_DREM: u8             = 0x73;
+pub const OP_CODE_INEG: u8             = 0x74;
+pub const OP_CODE_LNEG: u8             = 0x75;
+pub const OP_CODE_FNEG: u8             = 0x76;
+pub const OP_CODE_DNEG: u8             = 0x77;
+pub const OP_CODE_ISHL: u8             = 0x78;
+pub const OP_CODE_LSHL: u8             = 0x79;
+pub const OP_CODE_ISHR: u8             = 0x7A;
+pub const OP_CODE_LSHR: u8             = 0x7B;
+pub const OP_CODE_IUSHR: u8            = 0x7C;
+pub const OP_CODE_LUSHR: u8            = 0x7D;
+pub const OP_CODE_IAND: u8             = 0x7E;
+pub const OP_CODE_LAND: u8             = 0x7F;
+pub const OP_CODE_IOR: u8              = 0x80;
+pub const OP_CODE_LOR: u8              = 0x81;
+pub const OP_CODE_IXOR: u8             = 0x82;
+pub const OP_CODE_LXOR: u8             = 0x83;
+pub const OP_CODE_IINC: u8             = 0x84;
+pub const OP_CODE_I2L: u8              = 0x85;
+pub const OP_CODE_I2F: u8              = 0x86;
+pub const OP_CODE_I2D: u8              = 0x87;
+pub const OP_CODE_L2I: u8              = 0x88;
+pub const OP_CODE_L2F: u8              = 0x89;
+pub const OP_CODE_L2D: u8              = 0x8A;
+pub const OP_CODE_F2I: u8              = 0x8B;
+pub const OP_CODE_F2L: u8              = 0x8C;
+pub const OP_CODE_F2D: u8              = 0x8D;
+pub const OP_CODE_D2I: u8              = 0x8E;
+pub const OP_CODE_D2L: u8              = 0x8F;
+pub const OP_CODE_D2F: u8              = 0x90;
+pub const OP_CODE_I2B: u8              = 0x91;
+pub const OP_CODE_I2C: u8              = 0x92;
+pub const OP_CODE_I2S: u8              = 0x93;
+pub const OP_CODE_LCMP: u8             = 0x94;
+pub const OP_CODE_FCMPL: u8            = 0x95;
+pub const OP_CODE_FCMPG: u8            = 0x96;
+pub const OP_CODE_DCMPL: u8            = 0x97;
+pub const OP_CODE_DCMPG: u8            = 0x98;
+pub const OP_CODE_IF_EQ: u8            = 0x99;
+pub const OP_CODE_IF_NE: u8            = 0x9A;
+pub const OP_CODE_IF_LT: u8            = 0x9B;
+pub const OP_CODE_IF_GE: u8            = 0x9C;
+pub const OP_CODE_IF_GT: u8            = 0x9D;
+pub const OP_CODE_IF_LE: u8            = 0x9E;
+pub const OP_CODE_IF_ICMP_EQ: u8       = 0x9F;
+pub const OP_CODE_IF_ICMP_NE: u8       = 0xA0;
+pub const OP_CODE_IF_ICMP_LT: u8       = 0xA1;
+pub const OP_CODE_IF_ICMP_GE: u8       = 0xA2;
+pub const OP_CODE_IF_ICMP_GT: u8       = 0xA3;
+pub const OP_CODE_IF_ICMP_LE: u8       = 0xA4;
+pub const OP_CODE_IF_ACMP_EQ: u8       = 0xA5;
+pub const OP_CODE_IF_ACMP_NE: u8       = 0xA6;
+pub const OP_CODE_GOTO: u8             = 0xA7;
+pub const OP_CODE_JSR: u8              = 0xA8;
+pub const OP_CODE_RET: u8              = 0xA9;
+pub const OP_CODE_TABLE_SWITCH: u8     = 0xAA;
+pub const OP_CODE_LOOKUP_SWITCH: u8    = 0xAB;
+pub const OP_CODE_IRETURN: u8          = 0xAC;
+pub const OP_CODE_LRETURN: u8          = 0xAD;
+pub const OP_CODE_FRETURN: u8          = 0xAE;
+pub const OP_CODE_DRETURN: u8          = 0xAF;
+pub const OP_CODE_ARETURN: u8          = 0xB0;
+pub const OP_CODE_RETURN: u8           = 0xB1;
+pub const OP_CODE_GET_STATIC: u8       = 0xB2;
+pub const OP_CODE_PUT_STATIC: u8       = 0xB3;
+pub const OP_CODE_GET_FIELD: u8        = 0xB4;
+pub const OP_CODE_PUT_FIELD: u8        = 0xB5;
+pub const OP_CODE_INVOKE_VIRTUAL: u8   = 0xB6;
+pub const OP_CODE_INVOKE_SPECIAL: u8   = 0xB7;
+pub const OP_CODE_INVOKE_STATIC: u8    = 0xB8;
+pub const OP_CODE_INVOKE_INTERFACE: u8 = 0xB9;
+pub const OP_CODE_INVOKE_DYNAMIC: u8   = 0xBA;
+pub const OP_CODE_NEW: u8              = 0xBB;
+pub const OP_CODE_NEW_ARRAY: u8        = 0xBC;
+pub const OP_CODE_ANEW_ARRAY: u8       = 0xBD;
+pub const OP_CODE_ARRAY_LENGTH: u8     = 0xBE;
+pub const OP_CODE_ATHROW: u8           = 0xBF;
+pub const OP_CODE_CHECK_CAST: u8       = 0xC0;
+pub const OP_CODE_INSTANCE_OF: u8      = 0xC1;
+pub const OP_CODE_MONITOR_ENTER: u8    = 0xC2;
+pub const OP_CODE_MONITOR_EXIT: u8     = 0xC3;
+pub const OP_CODE_WIDE: u8             = 0xC4;
+pub const OP_CODE_MULTI_ANEW_ARRAY: u8 = 0xC5;
+pub const OP_CODE_IF_NULL: u8          = 0xC6;
+pub const OP_CODE_IF_NON_NULL: u8      = 0xC7;
+pub const OP_CODE_GOTO_W: u8           = 0xC8;
+pub const OP_CODE_JSR_W: u8            = 0xC9;
+pub const OP_CODE_BREAKPOINT: u8       = 0xCA;
+pub const OP_CODE_IMPDEP1: u8          = 0xFE;
+pub const OP_CODE_IMPDEP2: u8          = 0xFF;
+
+pub fn mnemonic(opcode: u8) -> Option<&'static str> {
+    let mnemonic = match opcode {
+        OP_CODE_NOP => "nop",
+        OP_CODE_ACONST_NULL => "aconst_null",
+        OP_CODE_ICONST_M1 => "iconst_m1",
+        OP_CODE_ICONST_0 => "iconst_0",
+        OP_CODE_ICONST_1 => "iconst_1",
+        OP_CODE_ICONST_2 => "iconst_2",
+        OP_CODE_ICONST_3 => "iconst_3",
+        OP_CODE_ICONST_4 => "iconst_4",
+        OP_CODE_ICONST_5 => "iconst_5",
+        OP_CODE_LCONST_0 => "lconst_0",
+        OP_CODE_LCONST_1 => "lconst_1",
+        OP_CODE_FCONST_0 => "fconst_0",
+        OP_CODE_FCONST_1 => "fconst_1",
+        OP_CODE_FCONST_2 => "fconst_2",
+        OP_CODE_DCONST_0 => "dconst_0",
+        OP_CODE_DCONST_1 => "dconst_1",
+        OP_CODE_BI_PUSH => "bipush",
+        OP_CODE_SI_PUSH => "sipush",
+        OP_CODE_LDC => "ldc",
+        OP_CODE_LDC_W => "ldc_w",
+        OP_CODE_LDC2_W => "ldc2_w",
+        OP_CODE_ILOAD => "iload",
+        OP_CODE_LLOAD => "lload",
+        OP_CODE_FLOAD => "fload",
+        OP_CODE_DLOAD => "dload",
+        OP_CODE_ALOAD => "aload",
+        OP_CODE_ILOAD_0 => "iload_0",
+        OP_CODE_ILOAD_1 => "iload_1",
+        OP_CODE_ILOAD_2 => "iload_2",
+        OP_CODE_ILOAD_3 => "iload_3",
+        OP_CODE_LLOAD_0 => "lload_0",
+        OP_CODE_LLOAD_1 => "lload_1",
+        OP_CODE_LLOAD_2 => "lload_2",
+        OP_CODE_LLOAD_3 => "lload_3",
+        OP_CODE_FLOAD_0 => "fload_0",
+        OP_CODE_FLOAD_1 => "fload_1",
+        OP_CODE_FLOAD_2 => "fload_2",
+        OP_CODE_FLOAD_3 => "fload_3",
+        OP_CODE_DLOAD_0 => "dload_0",
+        OP_CODE_DLOAD_1 => "dload_1",
+        OP_CODE_DLOAD_2 => "dload_2",
+        OP_CODE_DLOAD_3 => "dload_3",
+        OP_CODE_ALOAD_0 => "aload_0",
+        OP_CODE_ALOAD_1 => "aload_1",
+        OP_CODE_ALOAD_2 => "aload_2",
+        OP_CODE_ALOAD_3 => "aload_3",
+        OP_CODE_IALOAD => "iaload",
+        OP_CODE_LALOAD => "laload",
+        OP_CODE_FALOAD => "faload",
+        OP_CODE_DALOAD => "daload",
+        OP_CODE_AALOAD => "aaload",
+        OP_CODE_BALOAD => "baload",
+        OP_CODE_CALOAD => "caload",
+        OP_CODE_SALOAD => "saload",
+        OP_CODE_ISTORE => "istore",
+        OP_CODE_LSTORE => "lstore",
+        OP_CODE_FSTORE => "fstore",
+        OP_CODE_DSTORE => "dstore",
+        OP_CODE_ASTORE => "astore",
+        OP_CODE_ISTORE_0 => "istore_0",
+        OP_CODE_ISTORE_1 => "istore_1",
+        OP_CODE_ISTORE_2 => "istore_2",
+        OP_CODE_ISTORE_3 => "istore_3",
+        OP_CODE_LSTORE_0 => "lstore_0",
+        OP_CODE_LSTORE_1 => "lstore_1",
+        OP_CODE_LSTORE_2 => "lstore_2",
+        OP_CODE_LSTORE_3 => "lstore_3",
+        OP_CODE_FSTORE_0 => "fstore_0",
+        OP_CODE_FSTORE_1 => "fstore_1",
+        OP_CODE_FSTORE_2 => "fstore_2",
+        OP_CODE_FSTORE_3 => "fstore_3",
+        OP_CODE_DSTORE_0 => "dstore_0",
+        OP_CODE_DSTORE_1 => "dstore_1",
+        OP_CODE_DSTORE_2 => "dstore_2",
+        OP_CODE_DSTORE_3 => "dstore_3",
+        OP_CODE_ASTORE_0 => "astore_0",
+        OP_CODE_ASTORE_1 => "astore_1",
+        OP_CODE_ASTORE_2 => "astore_2",
+        OP_CODE_ASTORE_3 => "astore_3",
+        OP_CODE_IASTORE => "iastore",
+        OP_CODE_LASTORE => "lastore",
+        OP_CODE_FASTORE => "fastore",
+        OP_CODE_DASTORE => "dastore",
+        OP_CODE_AASTORE => "aastore",
+        OP_CODE_BASTORE => "bastore",
+        OP_CODE_CASTORE => "castore",
+        OP_CODE_SASTORE => "sastore",
+        OP_CODE_POP => "pop",
+        OP_CODE_POP2 => "pop2",
+        OP_CODE_DUP => "dup",
+        OP_CODE_DUP_X1 => "dup_x1",
+        OP_CODE_DUP_X2 => "dup_x2",
+        OP_CODE_DUP2 => "dup2",
+        OP_CODE_DUP2_X1 => "dup2_x1",
+        OP_CODE_DUP2_X2 => "dup2_x2",
+        OP_CODE_SWAP => "swap",
+        OP_CODE_IADD => "iadd",
+        OP_CODE_LADD => "ladd",
+        OP_CODE_FADD => "fadd",
+        OP_CODE_DADD => "dadd",
+        OP_CODE_ISUB => "isub",
+        OP_CODE_LSUB => "lsub",
+        OP_CODE_FSUB => "fsub",
+        OP_CODE_DSUB => "dsub",
+        OP_CODE_IMUL => "imul",
+        OP_CODE_LMUL => "lmul",
+        OP_CODE_FMUL => "fmul",
+        OP_CODE_DMUL => "dmul",
+        OP_CODE_IDIV => "idiv",
+        OP_CODE_LDIV => "ldiv",
+        OP_CODE_FDIV => "fdiv",
+        OP_CODE_DDIV => "ddiv",
+        OP_CODE_IREM => "irem",
+        OP_CODE_LREM => "lrem",
+        OP_CODE_FREM => "frem",
+        OP_CODE_DREM => "drem",
+        OP_CODE_INEG => "ineg",
+        OP_CODE_LNEG => "lneg",
+        OP_CODE_FNEG => "fneg",
+        OP_CODE_DNEG => "dneg",
+        OP_CODE_ISHL => "ishl",
+        OP_CODE_LSHL => "lshl",
+        OP_CODE_ISHR => "ishr",
+        OP_CODE_LSHR => "lshr",
+        OP_CODE_IUSHR => "iushr",
+        OP_CODE_LUSHR => "lushr",
+        OP_CODE_IAND => "iand",
+        OP_CODE_LAND => "land",
+        OP_CODE_IOR => "ior",
+        OP_CODE_LOR => "lor",
+        OP_CODE_IXOR => "ixor",
+        OP_CODE_LXOR => "lxor",
+        OP_CODE_IINC => "iinc",
+        OP_CODE_I2L => "i2l",
+        OP_CODE_I2F => "i2f",
+        OP_CODE_I2D => "i2d",
+        OP_CODE_L2I => "l2i",
+        OP_CODE_L2F => "l2f",
+        OP_CODE_L2D => "l2d",
+        OP_CODE_F2I => "f2i",
+        OP_CODE_F2L => "f2l",
+        OP_CODE_F2D => "f2d",
+        OP_CODE_D2I => "d2i",
+        OP_CODE_D2L => "d2l",
+        OP_CODE_D2F => "d2f",
+        OP_CODE_I2B => "i2b",
+        OP_CODE_I2C => "i2c",
+        OP_CODE_I2S => "i2s",
+        OP_CODE_LCMP => "lcmp",
+        OP_CODE_FCMPL => "fcmpl",
+        OP_CODE_FCMPG => "fcmpg",
+        OP_CODE_DCMPL => "dcmpl",
+        OP_CODE_DCMPG => "dcmpg",
+        OP_CODE_IF_EQ => "ifeq",
+        OP_CODE_IF_NE => "ifne",
+        OP_CODE_IF_LT => "iflt",
+        OP_CODE_IF_GE => "ifge",
+        OP_CODE_IF_GT => "ifgt",
+        OP_CODE_IF_LE => "ifle",
+        OP_CODE_IF_ICMP_EQ => "if_icmpeq",
+        OP_CODE_IF_ICMP_NE => "if_icmpne",
+        OP_CODE_IF_ICMP_LT => "if_icmplt",
+        OP_CODE_IF_ICMP_GE => "if_icmpge",
+        OP_CODE_IF_ICMP_GT => "if_icmpgt",
+        OP_CODE_IF_ICMP_LE => "if_icmple",
+        OP_CODE_IF_ACMP_EQ => "if_acmpeq",
+        OP_CODE_IF_ACMP_NE => "if_acmpne",
+        OP_CODE_GOTO => "goto",
+        OP_CODE_JSR => "jsr",
+        OP_CODE_RET => "ret",
+        OP_CODE_TABLE_SWITCH => "tableswitch",
+        OP_CODE_LOOKUP_SWITCH => "lookupswitch",
+        OP_CODE_IRETURN => "ireturn",
+        OP_CODE_LRETURN => "lreturn",
+        OP_CODE_FRETURN => "freturn",
+        OP_CODE_DRETURN => "dreturn",
+        OP_CODE_ARETURN => "areturn",
+        OP_CODE_RETURN => "return",
+        OP_CODE_GET_STATIC => "getstatic",
+        OP_CODE_PUT_STATIC => "putstatic",
+        OP_CODE_GET_FIELD => "getfield",
+        OP_CODE_PUT_FIELD => "putfield",
+        OP_CODE_INVOKE_VIRTUAL => "invokevirtual",
+        OP_CODE_INVOKE_SPECIAL => "invokespecial",
+        OP_CODE_INVOKE_STATIC => "invokestatic",
+        OP_CODE_INVOKE_INTERFACE => "invokeinterface",
+        OP_CODE_INVOKE_DYNAMIC => "invokedynamic",
+        OP_CODE_NEW => "new",
+        OP_CODE_NEW_ARRAY => "newarray",
+        OP_CODE_ANEW_ARRAY => "anewarray",
+        OP_CODE_ARRAY_LENGTH => "arraylength",
+        OP_CODE_ATHROW => "athrow",
+        OP_CODE_CHECK_CAST => "checkcast",
+        OP_CODE_INSTANCE_OF => "instanceof",
+        OP_CODE_MONITOR_ENTER => "monitorenter",
+        OP_CODE_MONITOR_EXIT => "monitorexit",
+        OP_CODE_WIDE => "wide",
+        OP_CODE_MULTI_ANEW_ARRAY => "multianewarray",
+        OP_CODE_IF_NULL => "ifnull",
+        OP_CODE_IF_NON_NULL => "ifnonnull",
+        OP_CODE_GOTO_W => "goto_w",
+        OP_CODE_JSR_W => "jsr_w",
+        OP_CODE_BREAKPOINT => "breakpoint",
+        OP_CODE_IMPDEP1 => "impdep1",
+        OP_CODE_IMPDEP2 => "impdep2",
+        _ => return None
+    };
+
+    Some(mnemonic)
+}
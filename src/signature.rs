@@ -0,0 +1,548 @@
+use std::fmt;
+use crate::error::{JvmError, JvmResult};
+
+// Generic signatures from the Signature attribute (JVMS 4.7.9.1)
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeSignature {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+    Class(ClassTypeSignature),
+    TypeVariable(String),
+    Array(Box<TypeSignature>)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassTypeSignature {
+    // Package in internal form including the trailing slash, e.g. java/util/
+    pub package: String,
+    // The outermost class followed by any nested classes, e.g. Map and Entry for Map<K, V>.Entry
+    pub classes: Vec<SimpleClassTypeSignature>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimpleClassTypeSignature {
+    pub name: String,
+    pub type_arguments: Vec<TypeArgument>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeArgument {
+    // ?
+    Any,
+    Exact(TypeSignature),
+    // ? extends T
+    Extends(TypeSignature),
+    // ? super T
+    Super(TypeSignature)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeParameter {
+    pub name: String,
+    // Can be missing when the only bounds are interfaces
+    pub class_bound: Option<TypeSignature>,
+    pub interface_bounds: Vec<TypeSignature>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub super_class: ClassTypeSignature,
+    pub super_interfaces: Vec<ClassTypeSignature>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub parameters: Vec<TypeSignature>,
+    // None for void
+    pub return_type: Option<TypeSignature>,
+    pub throws: Vec<TypeSignature>
+}
+
+// How deeply type arguments and array types can nest, well past anything written by hand
+// but low enough that a malicious signature can't overflow the stack
+const MAX_NESTING: usize = 255;
+
+struct SignatureParser<'a> {
+    signature: &'a str,
+    position: usize,
+    // Reference types being parsed around the current position
+    depth: usize
+}
+
+impl<'a> SignatureParser<'a> {
+    fn new(signature: &'a str) -> SignatureParser<'a> {
+        SignatureParser { signature, position: 0, depth: 0 }
+    }
+
+    fn error(&self) -> JvmError {
+        JvmError::InvalidSignature { signature: self.signature.to_string(), position: self.position }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.signature.as_bytes().get(self.position).copied()
+    }
+
+    fn consume(&mut self, byte: u8) -> bool {
+        if self.peek() == Some(byte) {
+            self.position += 1;
+            return true
+        }
+        false
+    }
+
+    fn expect(&mut self, byte: u8) -> JvmResult<()> {
+        if !self.consume(byte) {
+            return Err(self.error());
+        }
+        Ok(())
+    }
+
+    fn identifier(&mut self) -> JvmResult<String> {
+        let start = self.position;
+        while let Some(byte) = self.peek() {
+            if matches!(byte, b'.' | b';' | b'[' | b'/' | b'<' | b'>' | b':') {
+                break;
+            }
+            self.position += 1;
+        }
+        if self.position == start {
+            return Err(self.error());
+        }
+        Ok(self.signature[start..self.position].to_string())
+    }
+
+    fn type_parameters(&mut self) -> JvmResult<Vec<TypeParameter>> {
+        let mut type_parameters: Vec<TypeParameter> = Vec::new();
+        if !self.consume(b'<') {
+            return Ok(type_parameters);
+        }
+
+        loop {
+            let name = self.identifier()?;
+            self.expect(b':')?;
+            let class_bound = match self.peek() {
+                Some(b':') => None,
+                _ => Some(self.reference_type()?)
+            };
+            let mut interface_bounds: Vec<TypeSignature> = Vec::new();
+            while self.consume(b':') {
+                interface_bounds.push(self.reference_type()?);
+            }
+            type_parameters.push(TypeParameter { name, class_bound, interface_bounds });
+
+            if self.consume(b'>') {
+                return Ok(type_parameters);
+            }
+        }
+    }
+
+    fn type_arguments(&mut self) -> JvmResult<Vec<TypeArgument>> {
+        let mut type_arguments: Vec<TypeArgument> = Vec::new();
+        if !self.consume(b'<') {
+            return Ok(type_arguments);
+        }
+
+        loop {
+            let type_argument = match self.peek() {
+                Some(b'*') => {
+                    self.position += 1;
+                    TypeArgument::Any
+                }
+                Some(b'+') => {
+                    self.position += 1;
+                    TypeArgument::Extends(self.reference_type()?)
+                }
+                Some(b'-') => {
+                    self.position += 1;
+                    TypeArgument::Super(self.reference_type()?)
+                }
+                _ => TypeArgument::Exact(self.reference_type()?)
+            };
+            type_arguments.push(type_argument);
+
+            if self.consume(b'>') {
+                return Ok(type_arguments);
+            }
+        }
+    }
+
+    fn class_type(&mut self) -> JvmResult<ClassTypeSignature> {
+        self.expect(b'L')?;
+
+        let mut package = String::new();
+        let mut name = self.identifier()?;
+        while self.consume(b'/') {
+            package.push_str(&name);
+            package.push('/');
+            name = self.identifier()?;
+        }
+
+        let mut classes = vec![SimpleClassTypeSignature { name, type_arguments: self.type_arguments()? }];
+        while self.consume(b'.') {
+            let name = self.identifier()?;
+            classes.push(SimpleClassTypeSignature { name, type_arguments: self.type_arguments()? });
+        }
+        self.expect(b';')?;
+
+        Ok(ClassTypeSignature { package, classes })
+    }
+
+    /// Every nested type goes through here, so this is where the nesting is limited
+    fn reference_type(&mut self) -> JvmResult<TypeSignature> {
+        if self.depth == MAX_NESTING {
+            return Err(self.error());
+        }
+        self.depth += 1;
+        let reference_type = self.unlimited_reference_type();
+        self.depth -= 1;
+        reference_type
+    }
+
+    fn unlimited_reference_type(&mut self) -> JvmResult<TypeSignature> {
+        match self.peek() {
+            Some(b'L') => Ok(TypeSignature::Class(self.class_type()?)),
+            Some(b'T') => {
+                self.position += 1;
+                let name = self.identifier()?;
+                self.expect(b';')?;
+                Ok(TypeSignature::TypeVariable(name))
+            }
+            Some(b'[') => {
+                self.position += 1;
+                Ok(TypeSignature::Array(Box::new(self.java_type()?)))
+            }
+            _ => Err(self.error())
+        }
+    }
+
+    fn java_type(&mut self) -> JvmResult<TypeSignature> {
+        let base_type = match self.peek() {
+            Some(b'B') => TypeSignature::Byte,
+            Some(b'C') => TypeSignature::Char,
+            Some(b'D') => TypeSignature::Double,
+            Some(b'F') => TypeSignature::Float,
+            Some(b'I') => TypeSignature::Int,
+            Some(b'J') => TypeSignature::Long,
+            Some(b'S') => TypeSignature::Short,
+            Some(b'Z') => TypeSignature::Boolean,
+            _ => return self.reference_type()
+        };
+
+        self.position += 1;
+        Ok(base_type)
+    }
+
+    fn finish(&self) -> JvmResult<()> {
+        if self.position != self.signature.len() {
+            return Err(self.error());
+        }
+        Ok(())
+    }
+}
+
+impl ClassSignature {
+    pub fn parse(signature: &str) -> JvmResult<ClassSignature> {
+        let mut parser = SignatureParser::new(signature);
+        let type_parameters = parser.type_parameters()?;
+        let super_class = parser.class_type()?;
+        let mut super_interfaces: Vec<ClassTypeSignature> = Vec::new();
+        while parser.peek().is_some() {
+            super_interfaces.push(parser.class_type()?);
+        }
+        parser.finish()?;
+
+        Ok(ClassSignature { type_parameters, super_class, super_interfaces })
+    }
+}
+
+impl MethodSignature {
+    pub fn parse(signature: &str) -> JvmResult<MethodSignature> {
+        let mut parser = SignatureParser::new(signature);
+        let type_parameters = parser.type_parameters()?;
+
+        parser.expect(b'(')?;
+        let mut parameters: Vec<TypeSignature> = Vec::new();
+        while !parser.consume(b')') {
+            parameters.push(parser.java_type()?);
+        }
+
+        let return_type = if parser.consume(b'V') {
+            None
+        } else {
+            Some(parser.java_type()?)
+        };
+
+        let mut throws: Vec<TypeSignature> = Vec::new();
+        while parser.consume(b'^') {
+            let thrown = match parser.peek() {
+                Some(b'L') | Some(b'T') => parser.reference_type()?,
+                _ => return Err(parser.error())
+            };
+            throws.push(thrown);
+        }
+        parser.finish()?;
+
+        Ok(MethodSignature { type_parameters, parameters, return_type, throws })
+    }
+}
+
+impl TypeSignature {
+    /// Parses a field signature, which is always a reference type
+    pub fn parse_field(signature: &str) -> JvmResult<TypeSignature> {
+        let mut parser = SignatureParser::new(signature);
+        let field_type = parser.reference_type()?;
+        parser.finish()?;
+        Ok(field_type)
+    }
+}
+
+impl ClassTypeSignature {
+    /// Binary name in internal form with the type arguments erased, e.g. java/util/Map$Entry
+    pub fn binary_name(&self) -> String {
+        let names: Vec<&str> = self.classes.iter().map(|class| class.name.as_str()).collect();
+        format!("{}{}", self.package, names.join("$"))
+    }
+}
+
+// The Display impls print the types the way they are written in Java source
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[T], separator: &str) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, "{}", separator)?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+fn write_type_parameters(f: &mut fmt::Formatter<'_>, type_parameters: &[TypeParameter]) -> fmt::Result {
+    if !type_parameters.is_empty() {
+        write!(f, "<")?;
+        write_list(f, type_parameters, ", ")?;
+        write!(f, "> ")?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for TypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeSignature::Byte => write!(f, "byte"),
+            TypeSignature::Char => write!(f, "char"),
+            TypeSignature::Double => write!(f, "double"),
+            TypeSignature::Float => write!(f, "float"),
+            TypeSignature::Int => write!(f, "int"),
+            TypeSignature::Long => write!(f, "long"),
+            TypeSignature::Short => write!(f, "short"),
+            TypeSignature::Boolean => write!(f, "boolean"),
+            TypeSignature::Class(class) => write!(f, "{}", class),
+            TypeSignature::TypeVariable(name) => write!(f, "{}", name),
+            TypeSignature::Array(component) => write!(f, "{}[]", component)
+        }
+    }
+}
+
+impl fmt::Display for ClassTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.package.replace('/', "."))?;
+        write_list(f, &self.classes, ".")
+    }
+}
+
+impl fmt::Display for SimpleClassTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.type_arguments.is_empty() {
+            write!(f, "<")?;
+            write_list(f, &self.type_arguments, ", ")?;
+            write!(f, ">")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TypeArgument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeArgument::Any => write!(f, "?"),
+            TypeArgument::Exact(type_signature) => write!(f, "{}", type_signature),
+            TypeArgument::Extends(type_signature) => write!(f, "? extends {}", type_signature),
+            TypeArgument::Super(type_signature) => write!(f, "? super {}", type_signature)
+        }
+    }
+}
+
+impl fmt::Display for TypeParameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        let bounds: Vec<&TypeSignature> = self.class_bound.iter().chain(self.interface_bounds.iter()).collect();
+        if !bounds.is_empty() {
+            write!(f, " extends ")?;
+            write_list(f, &bounds, " & ")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ClassSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_type_parameters(f, &self.type_parameters)?;
+        write!(f, "extends {}", self.super_class)?;
+        if !self.super_interfaces.is_empty() {
+            write!(f, " implements ")?;
+            write_list(f, &self.super_interfaces, ", ")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MethodSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_type_parameters(f, &self.type_parameters)?;
+        match &self.return_type {
+            Some(return_type) => write!(f, "{}", return_type)?,
+            None => write!(f, "void")?
+        }
+        write!(f, " (")?;
+        write_list(f, &self.parameters, ", ")?;
+        write!(f, ")")?;
+        if !self.throws.is_empty() {
+            write!(f, " throws ")?;
+            write_list(f, &self.throws, ", ")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(package: &str, classes: Vec<(&str, Vec<TypeArgument>)>) -> ClassTypeSignature {
+        ClassTypeSignature {
+            package: package.to_string(),
+            classes: classes.into_iter()
+                .map(|(name, type_arguments)| SimpleClassTypeSignature { name: name.to_string(), type_arguments })
+                .collect()
+        }
+    }
+
+    fn type_variable(name: &str) -> TypeSignature {
+        TypeSignature::TypeVariable(name.to_string())
+    }
+
+    fn error_position<T: fmt::Debug>(result: JvmResult<T>) -> usize {
+        match result {
+            Err(JvmError::InvalidSignature { position, .. }) => position,
+            other => panic!("expected InvalidSignature, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn field_signature_with_wildcards() {
+        let signature = TypeSignature::parse_field("Ljava/util/Map<Ljava/lang/String;+TT;>;").unwrap();
+        let expected = class("java/util/", vec![("Map", vec![
+            TypeArgument::Exact(TypeSignature::Class(class("java/lang/", vec![("String", vec![])]))),
+            TypeArgument::Extends(type_variable("T"))
+        ])]);
+        assert_eq!(signature, TypeSignature::Class(expected));
+
+        let signature = TypeSignature::parse_field("Ljava/util/List<*>;").unwrap();
+        assert_eq!(signature.to_string(), "java.util.List<?>");
+        let signature = TypeSignature::parse_field("[Ljava/util/List<-Ljava/lang/Integer;>;").unwrap();
+        assert_eq!(signature.to_string(), "java.util.List<? super java.lang.Integer>[]");
+        assert_eq!(TypeSignature::parse_field("[[TT;").unwrap().to_string(), "T[][]");
+    }
+
+    #[test]
+    fn inner_class_suffixes() {
+        let signature = TypeSignature::parse_field("Lpkg/Outer<TT;>.Inner<TU;>.Deepest;").unwrap();
+        let expected = class("pkg/", vec![
+            ("Outer", vec![TypeArgument::Exact(type_variable("T"))]),
+            ("Inner", vec![TypeArgument::Exact(type_variable("U"))]),
+            ("Deepest", vec![])
+        ]);
+        assert_eq!(signature, TypeSignature::Class(expected));
+        assert_eq!(signature.to_string(), "pkg.Outer<T>.Inner<U>.Deepest");
+
+        // Without type arguments on the outer class the binary name keeps its $
+        let signature = TypeSignature::parse_field("Ljava/util/Map$Entry<TK;TV;>;").unwrap();
+        assert_eq!(signature.to_string(), "java.util.Map$Entry<K, V>");
+    }
+
+    #[test]
+    fn binary_names_erase_type_arguments() {
+        let signature = TypeSignature::parse_field("Ljava/util/Map<TK;TV;>.Entry<TK;TV;>;").unwrap();
+        match signature {
+            TypeSignature::Class(class) => assert_eq!(class.binary_name(), "java/util/Map$Entry"),
+            other => panic!("expected a class type, got {:?}", other)
+        }
+        assert_eq!(class("", vec![("Top", Vec::new())]).binary_name(), "Top");
+    }
+
+    #[test]
+    fn class_signature_with_bounded_type_parameters() {
+        let signature = ClassSignature::parse(
+            "<T::Ljava/lang/Comparable<TT;>;U:Ljava/lang/Number;:Ljava/lang/Runnable;>Ljava/util/AbstractList<TT;>;Ljava/io/Serializable;"
+        ).unwrap();
+        assert_eq!(signature.type_parameters.len(), 2);
+        assert_eq!(signature.type_parameters[0].class_bound, None);
+        assert_eq!(signature.type_parameters[0].to_string(), "T extends java.lang.Comparable<T>");
+        assert_eq!(signature.type_parameters[1].to_string(), "U extends java.lang.Number & java.lang.Runnable");
+        assert_eq!(signature.super_class.to_string(), "java.util.AbstractList<T>");
+        assert_eq!(signature.super_interfaces, vec![class("java/io/", vec![("Serializable", vec![])])]);
+    }
+
+    #[test]
+    fn method_signature() {
+        let signature = MethodSignature::parse("<R:Ljava/lang/Object;>(Ljava/util/List<-TR;>;I)TR;^Ljava/io/IOException;^TE;").unwrap();
+        assert_eq!(signature.type_parameters[0].name, "R");
+        assert_eq!(signature.parameters.len(), 2);
+        assert_eq!(signature.parameters[0].to_string(), "java.util.List<? super R>");
+        assert_eq!(signature.parameters[1], TypeSignature::Int);
+        assert_eq!(signature.return_type, Some(type_variable("R")));
+        assert_eq!(signature.throws.len(), 2);
+        assert_eq!(signature.throws[1], type_variable("E"));
+
+        let signature = MethodSignature::parse("()V").unwrap();
+        assert_eq!(signature.return_type, None);
+        assert!(signature.parameters.is_empty());
+    }
+
+    #[test]
+    fn invalid_signatures() {
+        // Field signatures are always reference types
+        assert_eq!(error_position(TypeSignature::parse_field("I")), 0);
+        assert_eq!(error_position(TypeSignature::parse_field("Ljava/util/List<>;")), 16);
+        assert_eq!(error_position(TypeSignature::parse_field("Ljava/util/List<TT;")), 19);
+        assert_eq!(error_position(TypeSignature::parse_field("Ljava/lang/String;X")), 18);
+        assert_eq!(error_position(TypeSignature::parse_field("TT")), 2);
+        assert_eq!(error_position(TypeSignature::parse_field("Lpkg/Outer.;")), 11);
+        assert_eq!(error_position(ClassSignature::parse("<T>Ljava/lang/Object;")), 2);
+        assert_eq!(error_position(ClassSignature::parse("<>Ljava/lang/Object;")), 1);
+        assert_eq!(error_position(MethodSignature::parse("(I")), 2);
+        // Only class types and type variables can be thrown
+        assert_eq!(error_position(MethodSignature::parse("()V^I")), 4);
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |depth: usize| format!("{}Ljava/lang/Object;{}", "Ljava/util/List<".repeat(depth - 1), ">;".repeat(depth - 1));
+        assert!(TypeSignature::parse_field(&nested(MAX_NESTING)).is_ok());
+        let too_deep = nested(MAX_NESTING + 1);
+        assert_eq!(error_position(TypeSignature::parse_field(&too_deep)), MAX_NESTING * "Ljava/util/List<".len());
+
+        assert!(TypeSignature::parse_field(&format!("{}TT;", "[".repeat(MAX_NESTING - 1))).is_ok());
+        assert_eq!(error_position(TypeSignature::parse_field(&"[".repeat(1_000_000))), MAX_NESTING);
+    }
+}
@@ -0,0 +1,261 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::descriptor::FieldType;
+use crate::heap::Object;
+
+#[derive(Debug)]
+pub struct Array {
+    pub component: FieldType,
+    // Elements of boolean, byte, char and short arrays are kept as Int, already narrowed
+    pub elements: Vec<Value>
+}
+
+#[derive(Clone, Debug)]
+pub enum Reference {
+    Null,
+    String(Rc<str>),
+    Array(Rc<RefCell<Array>>),
+    Object(Rc<RefCell<Object>>),
+    // Stands in for System.out until there are real objects
+    PrintStream,
+    // A caught exception, what an exception handler finds on the stack
+    Throwable(Rc<Throwable>)
+}
+
+/// Stands in for a caught exception, platform classes can't be instantiated yet. Exceptions
+/// of user classes are caught as the same stand-in, they keep their class and message but
+/// not their fields
+#[derive(Debug)]
+pub struct Throwable {
+    // Binary name, e.g. java/lang/ArithmeticException
+    pub class_name: String,
+    pub message: Option<String>
+}
+
+impl Reference {
+    /// Reference equality, what == compares in Java
+    pub fn is_same(&self, other: &Reference) -> bool {
+        match (self, other) {
+            (Reference::Null, Reference::Null) => true,
+            (Reference::String(left), Reference::String(right)) => Rc::ptr_eq(left, right),
+            (Reference::Array(left), Reference::Array(right)) => Rc::ptr_eq(left, right),
+            (Reference::Object(left), Reference::Object(right)) => Rc::ptr_eq(left, right),
+            (Reference::PrintStream, Reference::PrintStream) => true,
+            (Reference::Throwable(left), Reference::Throwable(right)) => Rc::ptr_eq(left, right),
+            _ => false
+        }
+    }
+}
+
+/// A value on the operand stack or in a local variable. boolean, byte, char and short are
+/// all represented as Int, the same as in the JVM.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Reference(Reference)
+}
+
+impl Array {
+    pub fn new(component: FieldType, length: usize) -> Array {
+        let elements = vec![Value::default_for(&component); length];
+        Array { component, elements }
+    }
+
+    /// The class name Java reports for the array, e.g. [I or [Ljava.lang.String;
+    pub fn class_name(&self) -> String {
+        FieldType::Array(Box::new(self.component.clone())).to_string().replace('/', ".")
+    }
+}
+
+impl Value {
+    /// Initial value of fields and array elements of the given type
+    pub fn default_for(field_type: &FieldType) -> Value {
+        match field_type {
+            FieldType::Long => Value::Long(0),
+            FieldType::Float => Value::Float(0.0),
+            FieldType::Double => Value::Double(0.0),
+            FieldType::Object(_) | FieldType::Array(_) => Value::Reference(Reference::Null),
+            _ => Value::Int(0)
+        }
+    }
+
+    /// Long and double are category 2 values, they take two slots in the locals and on the stack
+    pub fn is_category_2(&self) -> bool {
+        matches!(self, Value::Long(_) | Value::Double(_))
+    }
+
+    pub fn slots(&self) -> usize {
+        if self.is_category_2() { 2 } else { 1 }
+    }
+
+    /// Whether the value can be passed where the descriptor expects the given type
+    pub fn matches(&self, field_type: &FieldType) -> bool {
+        match field_type {
+            FieldType::Boolean | FieldType::Byte | FieldType::Char | FieldType::Short | FieldType::Int =>
+                matches!(self, Value::Int(_)),
+            FieldType::Long => matches!(self, Value::Long(_)),
+            FieldType::Float => matches!(self, Value::Float(_)),
+            FieldType::Double => matches!(self, Value::Double(_)),
+            FieldType::Object(_) | FieldType::Array(_) => matches!(self, Value::Reference(_))
+        }
+    }
+
+    /// Converts an int to the boolean, byte, char or short it is stored as, the way bastore,
+    /// castore, sastore and putfield do
+    pub fn narrow_to(self, field_type: &FieldType) -> Value {
+        match (field_type, self) {
+            (FieldType::Boolean, Value::Int(value)) => Value::Int(value & 1),
+            (FieldType::Byte, Value::Int(value)) => Value::Int(value as i8 as i32),
+            (FieldType::Char, Value::Int(value)) => Value::Int(value as u16 as i32),
+            (FieldType::Short, Value::Int(value)) => Value::Int(value as i16 as i32),
+            (_, value) => value
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Long(_) => "long",
+            Value::Float(_) => "float",
+            Value::Double(_) => "double",
+            Value::Reference(_) => "reference"
+        }
+    }
+}
+
+/// Formats a double the way Double.toString does, Rust prints 1.0 as "1" and never switches
+/// to scientific notation
+pub fn double_to_string(value: f64) -> String {
+    if value.is_nan() {
+        return String::from("NaN");
+    }
+    if value.is_infinite() {
+        return String::from(if value > 0.0 { "Infinity" } else { "-Infinity" });
+    }
+    if value == 0.0 {
+        return String::from(if value.is_sign_negative() { "-0.0" } else { "0.0" });
+    }
+
+    // {:e} gives the shortest digits that round trip, like "-1.2345e-7". Java always renders
+    // at least two digits and picks the closest ones, so 4.9E-324 rather than 5.0E-324
+    let mut scientific = format!("{:e}", value);
+    if !scientific.contains('.') {
+        scientific = format!("{:.1e}", value);
+    }
+    java_notation(&scientific)
+}
+
+/// Formats a float the way Float.toString does, using the shortest digits for the float
+/// itself rather than for the value widened to double
+pub fn float_to_string(value: f32) -> String {
+    if !value.is_finite() || value == 0.0 {
+        return double_to_string(value as f64);
+    }
+    let mut scientific = format!("{:e}", value);
+    if !scientific.contains('.') {
+        scientific = format!("{:.1e}", value);
+    }
+    java_notation(&scientific)
+}
+
+/// Rewrites the output of {:e} in Java's notation: plain decimals from 10^-3 up to 10^7,
+/// otherwise one digit before the point and an E exponent
+fn java_notation(scientific: &str) -> String {
+    let mut negative = false;
+    let mut digits = String::new();
+    let mut in_exponent = false;
+    let mut exponent_sign = 1;
+    let mut exponent: i32 = 0;
+    for c in scientific.chars() {
+        match c {
+            '-' if in_exponent => exponent_sign = -1,
+            '-' => negative = true,
+            'e' => in_exponent = true,
+            '0'..='9' if in_exponent => exponent = exponent * 10 + (c as u8 - b'0') as i32,
+            '0'..='9' => digits.push(c),
+            // The decimal point, the digits are all that matter
+            _ => {}
+        }
+    }
+    let exponent = exponent * exponent_sign;
+    while digits.len() > 1 && digits.ends_with('0') {
+        digits.pop();
+    }
+    let sign = if negative { "-" } else { "" };
+
+    if (-3..7).contains(&exponent) {
+        if exponent >= 0 {
+            let integer_length = exponent as usize + 1;
+            if digits.len() > integer_length {
+                return format!("{}{}.{}", sign, &digits[..integer_length], &digits[integer_length..]);
+            }
+            return format!("{}{}{}.0", sign, digits, "0".repeat(integer_length - digits.len()));
+        }
+        return format!("{}0.{}{}", sign, "0".repeat((-exponent - 1) as usize), digits);
+    }
+
+    let fraction = if digits.len() > 1 { &digits[1..] } else { "0" };
+    format!("{}{}.{}E{}", sign, &digits[..1], fraction, exponent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubles_like_java() {
+        let cases = [
+            (1.0e10, "1.0E10"),
+            (1.0e-5, "1.0E-5"),
+            (0.001, "0.001"),
+            (1.0e7, "1.0E7"),
+            (9999999.0, "9999999.0"),
+            (1.0, "1.0"),
+            (100.0, "100.0"),
+            (123.456, "123.456"),
+            (0.1, "0.1"),
+            (-2.5e-4, "-2.5E-4"),
+            (-0.0, "-0.0"),
+            (0.0, "0.0"),
+            (f64::NAN, "NaN"),
+            (f64::INFINITY, "Infinity"),
+            (f64::NEG_INFINITY, "-Infinity"),
+            (f64::MAX, "1.7976931348623157E308"),
+            (f64::MIN, "-1.7976931348623157E308"),
+            (f64::MIN_POSITIVE, "2.2250738585072014E-308"),
+            // Double.MIN_VALUE, the smallest subnormal
+            (f64::from_bits(1), "4.9E-324"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(double_to_string(value), expected);
+        }
+    }
+
+    #[test]
+    fn floats_like_java() {
+        let cases = [
+            (1.0e10, "1.0E10"),
+            (1.0e-5, "1.0E-5"),
+            (0.001, "0.001"),
+            (1.0e7, "1.0E7"),
+            (16777216.0, "1.6777216E7"),
+            // The shortest digits of the float, not of the float widened to double
+            (0.1, "0.1"),
+            (1.0 / 3.0, "0.33333334"),
+            (-0.0, "-0.0"),
+            (f32::NAN, "NaN"),
+            (f32::INFINITY, "Infinity"),
+            (f32::NEG_INFINITY, "-Infinity"),
+            (f32::MAX, "3.4028235E38"),
+            (f32::MIN, "-3.4028235E38"),
+            // Float.MIN_VALUE, the smallest subnormal
+            (f32::from_bits(1), "1.4E-45"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(float_to_string(value), expected);
+        }
+    }
+}